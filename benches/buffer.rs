@@ -0,0 +1,61 @@
+//! Converting many short strings, with and without reusing buffers.
+//!
+//! Run with `cargo bench --bench buffer`. Script archives consist of many short strings like
+//! these, where allocating for each one is a large part of the cost.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use falcom_sjis::SjisBuffer;
+
+fn measure(name: &str, strings: usize, mut f: impl FnMut()) {
+	let iterations = 200;
+	let start = Instant::now();
+	for _ in 0..iterations {
+		f();
+	}
+	let elapsed = start.elapsed();
+	println!(
+		"{name}: {:.2} ns/string",
+		elapsed.as_nanos() as f64 / (strings * iterations) as f64
+	);
+}
+
+fn main() {
+	let text =
+		"第一章、日本ファルコムの英雄伝説「空の軌跡」は、遊撃士協会に所属する少女の物語である。";
+	let chars = text.chars().collect::<Vec<_>>();
+	let strings = (0..5000)
+		.map(|i| {
+			chars[i % 20..i % 20 + 1 + i % 12]
+				.iter()
+				.collect::<String>()
+		})
+		.collect::<Vec<_>>();
+	let encoded = strings
+		.iter()
+		.map(|s| falcom_sjis::encode(s).unwrap())
+		.collect::<Vec<_>>();
+
+	measure("decode", encoded.len(), || {
+		for input in &encoded {
+			black_box(falcom_sjis::decode(black_box(input)).unwrap());
+		}
+	});
+	let mut buf = SjisBuffer::new();
+	measure("SjisBuffer::decode", encoded.len(), || {
+		for input in &encoded {
+			black_box(buf.decode(black_box(input)).unwrap());
+		}
+	});
+	measure("encode", strings.len(), || {
+		for s in &strings {
+			black_box(falcom_sjis::encode(black_box(s)).unwrap());
+		}
+	});
+	measure("SjisBuffer::encode", strings.len(), || {
+		for s in &strings {
+			black_box(buf.encode(black_box(s)).unwrap());
+		}
+	});
+}
@@ -0,0 +1,53 @@
+//! A simple decoding benchmark on kanji-heavy, kana-heavy and ASCII-heavy text.
+//!
+//! Run with `cargo bench --bench decode`, optionally with `--features unsafe-fast`. The second
+//! measurement calls `decode_char` from outside the crate, as a tight loop in a caller would.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+fn measure(name: &str, chars: usize, mut f: impl FnMut()) {
+	let iterations = 200;
+	let start = Instant::now();
+	for _ in 0..iterations {
+		f();
+	}
+	let elapsed = start.elapsed();
+	println!(
+		"{name}: {:.2} ns/char",
+		elapsed.as_nanos() as f64 / (chars * iterations) as f64
+	);
+}
+
+fn main() {
+	let corpora = [
+		(
+			"kanji",
+			"第一章、日本ファルコムの英雄伝説「空の軌跡」は、遊撃士協会に所属する少女の物語である。\n",
+		),
+		(
+			"kana",
+			"「えっと、それじゃあ、いっしょにいこうか？」　ｴｽﾃﾙはそういって、にっこりとわらった。\n",
+		),
+		(
+			"ascii",
+			"#Message 0x1F: Estelle(\"Let's go!\") -> flag[12] = 1; 「はい」\n",
+		),
+	];
+	for (name, line) in corpora {
+		let text = line.repeat(2000);
+		let input = falcom_sjis::encode(&text).unwrap();
+		let chars = text.chars().count();
+		measure(&format!("{name} decode"), chars, || {
+			black_box(falcom_sjis::decode(black_box(&input)).unwrap());
+		});
+		measure(&format!("{name} decode_char"), chars, || {
+			let mut iter = black_box(&input).iter().copied();
+			let mut sum = 0u32;
+			while let Some(c) = falcom_sjis::decode_char(&mut iter) {
+				sum = sum.wrapping_add(c.unwrap() as u32);
+			}
+			black_box(sum);
+		});
+	}
+}
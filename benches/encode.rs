@@ -0,0 +1,40 @@
+//! A simple encoding benchmark, for comparing table representations.
+//!
+//! Run with `cargo bench --bench encode`, optionally with `--features flat-encode-table`. The
+//! second measurement calls `encode_char` from outside the crate, as a tight loop in a caller
+//! would.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+fn measure(name: &str, chars: usize, mut f: impl FnMut()) {
+	let iterations = 200;
+	let start = Instant::now();
+	for _ in 0..iterations {
+		f();
+	}
+	let elapsed = start.elapsed();
+	println!(
+		"{name}: {:.2} ns/char",
+		elapsed.as_nanos() as f64 / (chars * iterations) as f64
+	);
+}
+
+fn main() {
+	let text = (0..=0xFFFF)
+		.filter_map(char::from_u32)
+		.filter(|&c| falcom_sjis::encode_char(c).is_some())
+		.collect::<String>();
+	let chars = text.chars().count();
+	measure("encode", chars, || {
+		black_box(falcom_sjis::encode(black_box(&text)).unwrap());
+	});
+	let ascii = "The quick brown fox jumps over the lazy dog. ".repeat(500);
+	measure("encode_char (ascii)", ascii.len(), || {
+		let mut sum = 0u32;
+		for c in black_box(&ascii).chars() {
+			sum = sum.wrapping_add(falcom_sjis::encode_char(c).unwrap()[0] as u32);
+		}
+		black_box(sum);
+	});
+}
@@ -10,6 +10,7 @@ fn main() -> anyhow::Result<()> {
 	let mut table = phf_codegen::Map::new();
 	let f = &mut Reader::new(include_bytes!("utf8sjis.dat"));
 	let mut dup = HashSet::new();
+	let mut flat = vec![0; 0x20000];
 	for _ in 0..f.u32_le()? {
 		let char = f.u32_be()?.to_le_bytes();
 		let b = f.array::<2>()?;
@@ -18,27 +19,150 @@ fn main() -> anyhow::Result<()> {
 		// There are duplicate encodings for √∠∩∪∫∵≒≡⊥￢
 		if dup.insert(char) {
 			table.entry(char, &format!("{:#02X?}", b));
+			let i = usize::try_from(char as u32)? * 2;
+			flat[i..i + 2].copy_from_slice(&b);
 		}
 	}
 	assert!(f.remaining().is_empty());
 	std::fs::write(out.join("utf8sjis.rs"), table.build().to_string())?;
+	if env::var_os("CARGO_FEATURE_FLAT_ENCODE_TABLE").is_some() {
+		std::fs::write(out.join("utf8sjis_flat.bin"), flat)?;
+	}
 
 	let mut table = Vec::new();
+	let mut exceptions = Vec::new();
 	let f = &mut Reader::new(include_bytes!("sjisutf8.dat"));
 	let mut first_dot = true;
 	for _ in 0..f.u32_le()? {
 		let char = f.array::<4>()?;
 		let char = std::str::from_utf8(&char)?.chars().next().unwrap();
+		// Unassigned cells are filled with ・, except for the real one. They are stored as 0.
 		if char == '・' && !std::mem::take(&mut first_dot) {
-			table.push('�');
+			table.push(0);
+		} else if let Ok(c) = u16::try_from(char as u32) {
+			table.push(c);
 		} else {
-			table.push(char);
+			// Surrogates are not valid chars, so they can be used as indices into the exceptions.
+			table.push(0xD800 + u16::try_from(exceptions.len())?);
+			exceptions.push(char);
 		}
 	}
 	assert!(f.remaining().is_empty());
-	table.extend(['�'; 188]);
+	assert!(exceptions.len() <= 0x800);
+	table.resize(188 * 50, 0);
+
+	// The IBM extensions are not in the data, but are all duplicates of other characters.
+	let from = |start: [u8; 2], n| codes().skip_while(move |&c| c != start).take(n);
+	let sources = std::iter::empty()
+		.chain(from([0xEE, 0xEF], 10)) // ⅰ..ⅹ
+		.chain(from([0x87, 0x54], 10)) // Ⅰ..Ⅹ
+		.chain([[0xEE, 0xF9], [0xEE, 0xFA], [0xEE, 0xFB], [0xEE, 0xFC]]) // ￢￤＇＂
+		.chain([[0x87, 0x8A], [0x87, 0x82], [0x87, 0x84], [0x81, 0xE6]]) // ㈱№℡∵
+		.chain(from([0xED, 0x40], 360)); // NEC-selected IBM extension kanji
+	let mut ibm = Vec::new();
+	for (code, source) in from([0xFA, 0x40], 388).zip(sources) {
+		let v = table[index(source)];
+		assert_ne!(v, 0);
+		table[index(code)] = v;
+		let char = char::from_u32(v as u32).unwrap_or_else(|| exceptions[v as usize - 0xD800]);
+		ibm.push((char, code));
+	}
+	assert_eq!(ibm.len(), 388);
+	ibm.sort();
+	std::fs::write(out.join("ibm.rs"), format!("&{:?}", ibm))?;
+
+	// Compressed as deltas between consecutive cells. The low bit tells whether the delta is
+	// followed by a repeat count.
+	let mut z = Vec::new();
+	let mut prev = 0;
+	let deltas = table
+		.iter()
+		.map(|&v| zigzag(v as i32 - std::mem::replace(&mut prev, v as i32)));
+	let mut deltas = deltas.peekable();
+	while let Some(delta) = deltas.next() {
+		let mut run = 0;
+		while deltas.next_if_eq(&delta).is_some() {
+			run += 1;
+		}
+		write_varint(&mut z, delta << 1 | (run != 0) as u32);
+		if run != 0 {
+			write_varint(&mut z, run);
+		}
+	}
+	std::fs::write(out.join("sjisutf8.bin"), z)?;
+
 	let table = table.chunks(188).collect::<Vec<_>>();
 	std::fs::write(out.join("sjisutf8.rs"), format!("{:?}", table))?;
+	std::fs::write(out.join("sjisutf8_ex.rs"), format!("&{:?}", exceptions))?;
+
+	cp932(&out)?;
+
+	// An FNV-1a hash of the mapping data, so that builds with edited data can be told apart.
+	let mut hash = 0xCBF29CE484222325u64;
+	for &b in [
+		&include_bytes!("utf8sjis.dat")[..],
+		include_bytes!("sjisutf8.dat"),
+	]
+	.concat()
+	.iter()
+	{
+		hash = (hash ^ b as u64).wrapping_mul(0x100000001B3);
+	}
+	std::fs::write(out.join("table_version.rs"), format!("\"{hash:016x}\""))?;
+
+	Ok(())
+}
 
+/// The reference CP932 tables, for comparison: decoding indexed like this crate's table, but with
+/// all 60 lead bytes, and encoding sorted by char.
+fn cp932(out: &std::path::Path) -> anyhow::Result<()> {
+	let lead = |b1: u8| match b1 {
+		0x81..=0x9F => Some(b1 - 0x81),
+		0xE0..=0xFC => Some(b1 - 0xE0 + 0x1F),
+		_ => None,
+	};
+	let mut decode = vec![[0u16; 188]; 60];
+	let mut encode = std::collections::BTreeMap::new();
+	for line in include_str!("cp932.txt").lines() {
+		if line.starts_with('#') {
+			continue;
+		}
+		let mut parts = line.split(' ');
+		let code = u16::from_str_radix(parts.next().unwrap(), 16)?.to_be_bytes();
+		let char = u16::from_str_radix(parts.next().unwrap(), 16)?;
+		let preferred = parts.next() == Some("*");
+		let [b1, b2] = code;
+		let b = b2 - 0x40 - (b2 > 0x7F) as u8;
+		decode[lead(b1).unwrap() as usize][b as usize] = char;
+		let char = char::from_u32(char as u32).unwrap();
+		if preferred || !encode.contains_key(&char) {
+			encode.insert(char, code);
+		}
+	}
+	std::fs::write(out.join("cp932_decode.rs"), format!("{:?}", decode))?;
+	let encode = encode.into_iter().collect::<Vec<_>>();
+	std::fs::write(out.join("cp932_encode.rs"), format!("&{:?}", encode))?;
 	Ok(())
 }
+
+/// All structurally valid two-byte codes covered by the table, in order.
+fn codes() -> impl Iterator<Item = [u8; 2]> {
+	let b1 = (0x81..=0x9F).chain(0xE0..=0xEF).chain(0xFA..=0xFC);
+	b1.flat_map(|b1| (0x40..=0x7E).chain(0x80..=0xFC).map(move |b2| [b1, b2]))
+}
+
+fn index(code: [u8; 2]) -> usize {
+	codes().position(|c| c == code).unwrap()
+}
+
+fn zigzag(v: i32) -> u32 {
+	((v << 1) ^ (v >> 31)) as u32
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u32) {
+	while v >= 0x80 {
+		out.push(v as u8 | 0x80);
+		v >>= 7;
+	}
+	out.push(v as u8);
+}
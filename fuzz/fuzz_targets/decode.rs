@@ -0,0 +1,59 @@
+//! Decodes arbitrary bytes with the strict, lossy and streaming decoders, checking that they
+//! agree with each other.
+//!
+//! Run with `cargo +nightly fuzz run decode`. The first byte of the input sets the chunk size of
+//! the streaming reader, so that sequences split across reads are exercised as well.
+
+#![no_main]
+
+use std::io::Read;
+
+use libfuzzer_sys::fuzz_target;
+
+/// A reader that returns at most `chunk` bytes per read.
+struct Chunked<'a> {
+	data: &'a [u8],
+	chunk: usize,
+}
+
+impl Read for Chunked<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let n = buf.len().min(self.chunk).min(self.data.len());
+		buf[..n].copy_from_slice(&self.data[..n]);
+		self.data = &self.data[n..];
+		Ok(n)
+	}
+}
+
+fuzz_target!(|data: &[u8]| {
+	let Some((&chunk, input)) = data.split_first() else {
+		return;
+	};
+	let chunk = chunk as usize + 1;
+
+	let lossy = falcom_sjis::decode_lossy(input);
+	// Every byte gives at most one character, of at most three bytes.
+	assert!(lossy.chars().count() <= input.len());
+	assert!(lossy.len() <= input.len() * 3);
+
+	let strict = falcom_sjis::decode(input);
+	match &strict {
+		Ok(s) => assert_eq!(*s, lossy),
+		Err((pos, bytes)) => {
+			assert!(pos + bytes.len() <= input.len());
+			assert_eq!(input[*pos..].first(), bytes.first());
+		}
+	}
+
+	let mut out = Vec::new();
+	let reader = Chunked { data: input, chunk };
+	falcom_sjis::transcode_sjis_to_utf8(reader, &mut out, true).unwrap();
+	assert_eq!(std::str::from_utf8(&out).unwrap(), lossy);
+
+	let mut out = Vec::new();
+	let reader = Chunked { data: input, chunk };
+	match falcom_sjis::transcode_sjis_to_utf8(reader, &mut out, false) {
+		Ok(_) => assert_eq!(strict.as_deref(), Ok(std::str::from_utf8(&out).unwrap())),
+		Err(e) => assert_eq!(Some(e.position as usize), strict.err().map(|e| e.0)),
+	}
+});
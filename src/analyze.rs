@@ -0,0 +1,146 @@
+//! Character class statistics for encoded text.
+
+use crate::iter::decode_units;
+use crate::{classify_encoded, EncodedChar, JisClass};
+
+/// A number of characters and the bytes they take up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Count {
+	/// The number of characters, counting each invalid sequence as one.
+	pub chars: usize,
+	/// The number of bytes.
+	pub bytes: usize,
+}
+
+impl Count {
+	fn add(&mut self, bytes: usize) {
+		self.chars += 1;
+		self.bytes += bytes;
+	}
+}
+
+/// The composition of a byte buffer, as returned by [`analyze`].
+///
+/// Apart from [`kana`](Self::kana), which is a subset of [`non_kanji`](Self::non_kanji), each
+/// character is counted in exactly one category, so these add up to [`total`](Self::total).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Analysis {
+	/// [`JisClass::Ascii`].
+	pub ascii: Count,
+	/// [`JisClass::HalfwidthKatakana`].
+	pub halfwidth_katakana: Count,
+	/// [`JisClass::NonKanji`].
+	pub non_kanji: Count,
+	/// Hiragana and katakana, rows 4 and 5. These are also counted in `non_kanji`.
+	pub kana: Count,
+	/// [`JisClass::Level1Kanji`].
+	pub level1_kanji: Count,
+	/// [`JisClass::Level2Kanji`].
+	pub level2_kanji: Count,
+	/// [`JisClass::Extension`].
+	pub extension: Count,
+	/// Sequences that [`crate::decode`] would reject, including unassigned cells.
+	pub invalid: Count,
+	/// All characters.
+	pub total: Count,
+	/// The offset of the first invalid sequence, if any.
+	pub first_invalid: Option<usize>,
+}
+
+/// Counts the characters of each [`JisClass`] in a byte buffer.
+///
+/// Characters are split up the same way as [`crate::decode_lossy`], and classified by
+/// [`classify_encoded`].
+pub fn analyze(input: &[u8]) -> Analysis {
+	let mut a = Analysis::default();
+	for (range, result) in decode_units(input) {
+		let len = range.len();
+		a.total.add(len);
+		let enc = match (result, &input[range.clone()]) {
+			(Err(_), _) => None,
+			(Ok(_), &[b1]) => Some(EncodedChar::One([b1])),
+			(Ok(_), &[b1, b2]) => Some(EncodedChar::Two([b1, b2])),
+			_ => unreachable!(),
+		};
+		let count = match enc.and_then(classify_encoded) {
+			Some(JisClass::Ascii) => &mut a.ascii,
+			Some(JisClass::HalfwidthKatakana) => &mut a.halfwidth_katakana,
+			Some(JisClass::NonKanji) => &mut a.non_kanji,
+			Some(JisClass::Level1Kanji) => &mut a.level1_kanji,
+			Some(JisClass::Level2Kanji) => &mut a.level2_kanji,
+			Some(JisClass::Extension) => &mut a.extension,
+			None => {
+				a.first_invalid.get_or_insert(range.start);
+				&mut a.invalid
+			}
+		};
+		count.add(len);
+		if let Some((4..=5, _)) = enc.and_then(|e| e.kuten()) {
+			a.kana.add(len);
+		}
+	}
+	a
+}
+
+/// Whether any two-byte sequence has a trail byte in the ASCII range `40`–`7E`, which a tool that
+/// assumes ASCII would take for a character of its own.
+///
+/// The best known case is `5C`, `\`, as in `表` (`95 5C`), which can escape the next byte or end a
+/// path component. Sequences are split up the same way as [`crate::decode_lossy`], so invalid
+/// two-byte sequences count as well.
+pub fn has_ascii_hazard(input: &[u8]) -> bool {
+	decode_units(input).any(|(range, _)| matches!(input[range], [_, 0x40..=0x7E]))
+}
+
+#[test]
+fn fixture() {
+	let mut input = crate::encode("Aｱ、あア亜弌①").unwrap();
+	input.extend([0x80, 0x85, 0x40, b'z', 0x88]);
+	let c = |chars, bytes| Count { chars, bytes };
+	assert_eq!(
+		analyze(&input),
+		Analysis {
+			ascii: c(2, 2),
+			halfwidth_katakana: c(1, 1),
+			non_kanji: c(3, 6),
+			kana: c(2, 4),
+			level1_kanji: c(1, 2),
+			level2_kanji: c(1, 2),
+			extension: c(1, 2),
+			invalid: c(3, 4),
+			total: c(12, 19),
+			first_invalid: Some(14),
+		}
+	);
+}
+
+#[test]
+fn empty() {
+	assert_eq!(analyze(b""), Analysis::default());
+}
+
+#[test]
+fn all_invalid() {
+	let a = analyze(&[0x80, 0xA0, 0xFD, 0xFD, 0x81, 0x7F, 0xFF]);
+	assert_eq!(a.invalid, Count { chars: 5, bytes: 7 });
+	assert_eq!(a.total, a.invalid);
+	assert_eq!(a.first_invalid, Some(0));
+	assert_eq!(a.ascii, Count::default());
+}
+
+#[test]
+fn ascii_hazard() {
+	assert!(!has_ascii_hazard(b""));
+	assert!(!has_ascii_hazard(b"plain ASCII \\ [text]"));
+	assert!(!has_ascii_hazard(&crate::encode("あいうえお").unwrap()));
+	assert!(has_ascii_hazard(&crate::encode("表示").unwrap()));
+	// Not only `\`: the trail byte of 本 is `{`.
+	assert!(has_ascii_hazard(&crate::encode("日本").unwrap()));
+	assert!(has_ascii_hazard(&crate::encode("ソフト").unwrap()));
+	// `@` is a trail byte only when it follows a lead byte.
+	assert!(has_ascii_hazard(b"\x81\x40"));
+	assert!(!has_ascii_hazard(b"a@\x82\xA0@"));
+	// Invalid sequences count too, but a lone lead byte at the end has no trail byte.
+	assert!(has_ascii_hazard(b"\xF0\x5C"));
+	assert!(!has_ascii_hazard(b"a\x81"));
+}
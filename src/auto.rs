@@ -0,0 +1,167 @@
+//! Decoding input of unknown encoding.
+
+use crate::euc::from_euc_jp;
+use crate::{decode, decode_char, decode_lossy, DecodeError, Encoding};
+
+/// Decodes input that may be Shift JIS, UTF-8 or EUC-JP, and tells which one it was.
+///
+/// The first of these that the whole input is valid in is chosen:
+/// 1. UTF-8, if the input contains any non-ASCII characters;
+/// 2. Shift JIS, so pure ASCII (including empty input) is always decoded as Shift JIS;
+/// 3. EUC-JP, without JIS X 0212.
+///
+/// If none of them are valid, the one with the highest fraction of valid non-ASCII sequences is
+/// decoded lossily, preferring them in the same order in case of ties. See
+/// [`decode_auto_strict`] to get an error instead.
+///
+/// The returned encoding is never [`Encoding::Unknown`].
+pub fn decode_auto(input: &[u8]) -> (String, Encoding) {
+	if let Ok(result) = decode_auto_strict(input) {
+		return result;
+	}
+	let candidates = [
+		(Encoding::ShiftJis, crate::detect::sjis_validity(input)),
+		(Encoding::Utf8, utf8_validity(input)),
+		(Encoding::EucJp, euc_validity(input)),
+	];
+	let mut best = candidates[0];
+	for c in candidates {
+		if c.1 > best.1 {
+			best = c;
+		}
+	}
+	let out = match best.0 {
+		Encoding::Utf8 => String::from_utf8_lossy(input).into_owned(),
+		Encoding::EucJp => decode_euc(input, true).unwrap(),
+		_ => decode_lossy(input),
+	};
+	(out, best.0)
+}
+
+/// Like [`decode_auto`], but fails if the input is not entirely valid in any of the encodings.
+///
+/// The error is the one from decoding as Shift JIS.
+pub fn decode_auto_strict(input: &[u8]) -> Result<(String, Encoding), DecodeError> {
+	let utf8 = std::str::from_utf8(input);
+	if let Ok(s) = utf8 {
+		if !s.is_ascii() {
+			return Ok((s.to_owned(), Encoding::Utf8));
+		}
+	}
+	let error = match decode(input) {
+		Ok(s) => return Ok((s, Encoding::ShiftJis)),
+		Err(e) => e,
+	};
+	if let Ok(s) = decode_euc(input, false) {
+		return Ok((s, Encoding::EucJp));
+	}
+	Err(error.into())
+}
+
+/// The fraction of non-ASCII characters that are valid UTF-8, counting each invalid sequence as
+/// one character.
+fn utf8_validity(input: &[u8]) -> f64 {
+	let (mut valid, mut invalid) = (0, 0);
+	for chunk in input.utf8_chunks() {
+		valid += chunk.valid().chars().filter(|c| !c.is_ascii()).count();
+		invalid += !chunk.invalid().is_empty() as usize;
+	}
+	crate::detect::fraction(valid, invalid)
+}
+
+/// The fraction of non-ASCII characters that are valid EUC-JP.
+fn euc_validity(input: &[u8]) -> f64 {
+	let (mut valid, mut invalid) = (0, 0);
+	let mut pos = 0;
+	while pos < input.len() {
+		match euc_char(&input[pos..]) {
+			Some((c, len)) => {
+				valid += !c.is_ascii() as usize;
+				pos += len;
+			}
+			None => {
+				invalid += 1;
+				pos += 1;
+			}
+		}
+	}
+	crate::detect::fraction(valid, invalid)
+}
+
+/// Decodes EUC-JP by converting each character to Shift JIS.
+///
+/// Returns the offset of the first invalid sequence, unless `lossy` is set, in which case invalid
+/// bytes are replaced with the unicode replacement character.
+fn decode_euc(input: &[u8], lossy: bool) -> Result<String, usize> {
+	let mut out = String::new();
+	let mut pos = 0;
+	while pos < input.len() {
+		match euc_char(&input[pos..]) {
+			Some((c, len)) => {
+				out.push(c);
+				pos += len;
+			}
+			None if lossy => {
+				out.push('�');
+				pos += 1;
+			}
+			None => return Err(pos),
+		}
+	}
+	Ok(out)
+}
+
+fn euc_char(input: &[u8]) -> Option<(char, usize)> {
+	let (enc, len) = from_euc_jp(input)?;
+	let char = decode_char(&mut enc.into_iter())?.ok()?;
+	Some((char, len))
+}
+
+#[cfg(test)]
+const SAMPLE: &str = "日本ファルコムの「英雄伝説」は、ｶﾀｶﾅもある。";
+
+#[cfg(test)]
+fn to_euc(s: &str) -> Vec<u8> {
+	let sjis = crate::encode(s).unwrap();
+	crate::sjis_to_euc_jp(&sjis).unwrap()
+}
+
+#[test]
+fn routing() {
+	let sjis = crate::encode(SAMPLE).unwrap();
+	let euc = to_euc(SAMPLE);
+	for (input, encoding) in [
+		(&sjis[..], Encoding::ShiftJis),
+		(SAMPLE.as_bytes(), Encoding::Utf8),
+		(&euc[..], Encoding::EucJp),
+	] {
+		let expected = (SAMPLE.to_owned(), encoding);
+		assert_eq!(decode_auto(input), expected);
+		assert_eq!(decode_auto_strict(input).unwrap(), expected);
+	}
+}
+
+#[test]
+fn ascii() {
+	let expected = ("Hello".to_owned(), Encoding::ShiftJis);
+	assert_eq!(decode_auto(b"Hello"), expected);
+	assert_eq!(decode_auto_strict(b"Hello").unwrap(), expected);
+	assert_eq!(decode_auto(b"").1, Encoding::ShiftJis);
+}
+
+#[test]
+fn invalid() {
+	// Valid UTF-8 followed by a byte that is invalid in all three.
+	let mut input = SAMPLE.as_bytes().to_vec();
+	input.push(0xFF);
+	let err = decode_auto_strict(&input).unwrap_err();
+	assert_eq!(err.position, crate::decode(&input).unwrap_err().0);
+	let (s, encoding) = decode_auto(&input);
+	assert_eq!(encoding, Encoding::Utf8);
+	assert_eq!(s, format!("{SAMPLE}�"));
+
+	let mut input = crate::encode(SAMPLE).unwrap();
+	input.insert(4, 0x80);
+	assert_eq!(decode_auto(&input).1, Encoding::ShiftJis);
+	assert_eq!(decode_auto(&[0x80]), ("�".to_owned(), Encoding::ShiftJis));
+}
@@ -0,0 +1,109 @@
+//! Reusable buffers for converting many short strings.
+
+use crate::sink::{self, DecodeSinkError, SinkError};
+use crate::trace::{Trace, DEFAULT_LEVEL};
+use crate::{decode_char_from, encode_char, DecodeError, EncodeError, EncodedChar};
+
+/// Buffers that are reused across conversions, to avoid allocating for each one.
+///
+/// Each method returns a borrow of the buffer, which is valid until the next call:
+///
+/// ```compile_fail
+/// let mut buf = falcom_sjis::SjisBuffer::new();
+/// let a = buf.decode(b"a").unwrap();
+/// let b = buf.decode(b"b").unwrap();
+/// assert_eq!(a, b);
+/// ```
+///
+/// The results are the same as from [`crate::decode`], [`crate::encode`] and their lossy variants.
+#[derive(Debug, Clone, Default)]
+pub struct SjisBuffer {
+	text: String,
+	bytes: Vec<u8>,
+}
+
+impl SjisBuffer {
+	/// Creates empty buffers, which grow as needed.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Decodes a byte slice into the buffer.
+	pub fn decode(&mut self, input: &[u8]) -> Result<&str, DecodeError> {
+		self.text.clear();
+		match sink::decode_into(
+			&mut self.text,
+			input,
+			|b1, rest| decode_char_from(b1, || rest.next()),
+			|_, _, _| false,
+		) {
+			Ok(()) => Ok(&self.text),
+			Err(DecodeSinkError::Undecodable(pos, enc)) => Err(DecodeError::from((pos, enc))),
+			Err(DecodeSinkError::Sink(e)) => match e {},
+		}
+	}
+
+	/// Decodes a byte slice into the buffer, lossily, as per [`crate::decode_lossy`].
+	pub fn decode_lossy(&mut self, input: &[u8]) -> &str {
+		self.text.clear();
+		sink::decode_replacing(&mut self.text, input, |b1, rest| {
+			decode_char_from(b1, || rest.next())
+		});
+		&self.text
+	}
+
+	/// Encodes a string into the buffer.
+	pub fn encode(&mut self, str: &str) -> Result<&[u8], EncodeError> {
+		self.bytes.clear();
+		match sink::encode_into(&mut self.bytes, str, encode_char, |_, _| None) {
+			Ok(()) => Ok(&self.bytes),
+			Err(SinkError::Unencodable(pos)) => Err(EncodeError::at(str, pos)),
+			Err(SinkError::Sink(_, e)) => match e {},
+		}
+	}
+
+	/// Encodes a string into the buffer, lossily, as per [`crate::encode_lossy`].
+	pub fn encode_lossy(&mut self, str: &str) -> &[u8] {
+		self.bytes.clear();
+		let mut trace = Trace::encode(str.len(), DEFAULT_LEVEL);
+		let result = sink::encode_into(&mut self.bytes, str, encode_char, |pos, char| {
+			trace.replaced_char(pos, char, EncodedChar::REPLACEMENT);
+			Some(EncodedChar::REPLACEMENT)
+		});
+		let Ok(()) = result else { unreachable!() };
+		&self.bytes
+	}
+}
+
+#[test]
+fn reuse() {
+	let mut buf = SjisBuffer::new();
+	let inputs = [
+		"日本ファルコム",
+		"",
+		"a",
+		"英雄伝説 空の軌跡 the 3rd",
+		"x₂y",
+		"ab",
+	];
+	for s in inputs {
+		let expected = crate::encode(s).map_err(|pos| EncodeError::at(s, pos));
+		assert_eq!(buf.encode(s).map(<[u8]>::to_vec), expected);
+		assert_eq!(buf.encode_lossy(s), crate::encode_lossy(s));
+		let bytes = crate::encode_lossy(s);
+		assert_eq!(buf.decode(&bytes), Ok(s.replace('₂', "・").as_str()));
+	}
+	for input in [&b"\x93\xFA\x80"[..], b"", b"\x81", b"abc\xF0\x40d"] {
+		let expected = crate::decode(input).map_err(DecodeError::from);
+		assert_eq!(buf.decode(input).map(str::to_owned), expected);
+		assert_eq!(buf.decode_lossy(input), crate::decode_lossy(input));
+	}
+
+	// Once grown, the buffers are not reallocated for shorter inputs.
+	let ptr = buf.encode("英雄伝説 空の軌跡 the 3rd").unwrap().as_ptr();
+	assert_eq!(buf.encode("a").unwrap().as_ptr(), ptr);
+	let ptr = buf
+		.decode_lossy(b"\x93\xFA\x96\x7B\x93\xFA\x96\x7B")
+		.as_ptr();
+	assert_eq!(buf.decode(b"a").unwrap().as_ptr(), ptr);
+}
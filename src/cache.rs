@@ -0,0 +1,64 @@
+//! Cached decoding, under the `decode-cache` feature.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::decode_lossy;
+
+/// The number of strings kept in each thread's cache.
+pub const DECODE_CACHE_SIZE: usize = 256;
+
+#[derive(Default)]
+struct Cache {
+	entries: HashMap<Box<[u8]>, (Rc<str>, u64)>,
+	clock: u64,
+}
+
+thread_local! {
+	static CACHE: RefCell<Cache> = RefCell::default();
+}
+
+/// Decodes a byte slice lossily, as per [`decode_lossy`], reusing the result of an earlier call
+/// with the same input.
+///
+/// The cache is per thread, which is also why this returns an [`Rc`]: nothing is shared or locked
+/// between threads, and each thread warms up its own cache. It holds the
+/// [`DECODE_CACHE_SIZE`] most recently used strings; when it is full, the least recently used one
+/// is evicted.
+pub fn decode_cached(input: &[u8]) -> Rc<str> {
+	CACHE.with_borrow_mut(|cache| {
+		cache.clock += 1;
+		let clock = cache.clock;
+		if let Some((s, used)) = cache.entries.get_mut(input) {
+			*used = clock;
+			return s.clone();
+		}
+		if cache.entries.len() >= DECODE_CACHE_SIZE {
+			let oldest = cache.entries.iter().min_by_key(|(_, (_, used))| *used);
+			let oldest = oldest.map(|(key, _)| key.clone()).unwrap();
+			cache.entries.remove(&oldest);
+		}
+		let s = Rc::<str>::from(decode_lossy(input));
+		cache.entries.insert(input.into(), (s.clone(), clock));
+		s
+	})
+}
+
+#[test]
+fn cached() {
+	let a = decode_cached(b"\x93\xFA\x96\x7B");
+	assert_eq!(&*a, "日本");
+	assert!(Rc::ptr_eq(&a, &decode_cached(b"\x93\xFA\x96\x7B")));
+	assert_eq!(&*decode_cached(b"a\x80"), "a�");
+
+	// Filling the cache evicts the least recently used entries, but not ones used since.
+	let b = decode_cached(b"b");
+	for i in 0..DECODE_CACHE_SIZE as u32 - 1 {
+		decode_cached(&i.to_le_bytes());
+		decode_cached(b"b");
+	}
+	assert!(Rc::ptr_eq(&b, &decode_cached(b"b")));
+	assert!(!Rc::ptr_eq(&a, &decode_cached(b"\x93\xFA\x96\x7B")));
+	CACHE.with_borrow(|cache| assert_eq!(cache.entries.len(), DECODE_CACHE_SIZE));
+}
@@ -0,0 +1,85 @@
+//! Decoding on a worker thread.
+
+use std::sync::mpsc::{SendError, Sender};
+
+use crate::decode_char_from;
+use crate::sink::{self, DecodeSink};
+
+/// Decoded text, sent in chunks as they fill up.
+struct ChunkSink {
+	sender: Sender<String>,
+	chunk: String,
+	count: usize,
+	chunk_chars: usize,
+}
+
+impl DecodeSink for ChunkSink {
+	type Error = SendError<String>;
+
+	fn put(&mut self, char: char) -> Result<(), SendError<String>> {
+		self.chunk.push(char);
+		self.count += 1;
+		if self.count == self.chunk_chars {
+			self.count = 0;
+			self.sender.send(std::mem::take(&mut self.chunk))?;
+		}
+		Ok(())
+	}
+}
+
+/// Decodes a byte slice lossily, sending the result in chunks of `chunk_chars` characters.
+///
+/// The chunks are as per [`crate::decode_lossy`], and each is sent as soon as it is complete, with
+/// a shorter one at the end if needed. A two-byte sequence is never split between chunks. A
+/// `chunk_chars` of 0 is treated as 1.
+///
+/// Returns early if the receiver is dropped. Nothing is sent for empty input.
+pub fn decode_to_channel(input: &[u8], sender: Sender<String>, chunk_chars: usize) {
+	let mut sink = ChunkSink {
+		sender,
+		chunk: String::new(),
+		count: 0,
+		chunk_chars: chunk_chars.max(1),
+	};
+	// A failed send stops decoding, since the fallback cannot write either.
+	let replace = |sink: &mut ChunkSink, _, _| sink.put('�').is_ok();
+	if sink::decode_into(
+		&mut sink,
+		input,
+		|b1, rest| decode_char_from(b1, || rest.next()),
+		replace,
+	)
+	.is_err()
+	{
+		return;
+	}
+	if !sink.chunk.is_empty() {
+		let _ = sink.sender.send(sink.chunk);
+	}
+}
+
+#[test]
+fn chunks() {
+	use std::sync::mpsc::channel;
+	let text = "英雄伝説、空の軌跡。ABC\u{FF71}".repeat(50);
+	let mut input = crate::encode(&text).unwrap();
+	input.push(0x80);
+	let (tx, rx) = channel();
+	let handle = std::thread::spawn(move || decode_to_channel(&input, tx, 7));
+	let chunks = rx.iter().collect::<Vec<_>>();
+	handle.join().unwrap();
+	assert_eq!(chunks.concat(), text.clone() + "�");
+	let (last, rest) = chunks.split_last().unwrap();
+	assert!(rest.iter().all(|c| c.chars().count() == 7));
+	assert_eq!(last.chars().count(), (text.chars().count() + 1) % 7);
+
+	let (tx, rx) = channel();
+	decode_to_channel(b"", tx, 7);
+	assert_eq!(rx.iter().count(), 0);
+	let (tx, rx) = channel();
+	decode_to_channel(b"ab", tx, 0);
+	assert_eq!(rx.iter().collect::<Vec<_>>(), ["a", "b"]);
+	let (tx, rx) = channel();
+	drop(rx);
+	decode_to_channel(b"abc", tx, 1);
+}
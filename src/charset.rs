@@ -0,0 +1,388 @@
+//! Sets of Shift JIS codes.
+//!
+//! This is mainly useful for font subsetting: collecting every code used by a set of texts, to
+//! compare against the glyphs a font has. A [`Whitelist`] uses one to restrict input to the
+//! characters a game can display, such as on a name-entry screen.
+
+use std::convert::Infallible;
+use std::fmt;
+
+use crate::sink::{self, DecodeSink, DecodeSinkError, Rest};
+use crate::{decode_char_from, encode_char, DecodeError, EncodeError, EncodedChar};
+
+/// Number of rows covered, including the user-defined and IBM extension areas.
+const ROWS: usize = 120;
+const BITS: usize = 256 + ROWS * 94;
+const WORDS: usize = BITS.div_ceil(64);
+
+/// A set of single-byte and two-byte codes.
+///
+/// This is a bitset over all single bytes and every row and cell of the two-byte code space, so it
+/// is compact and cheap to combine. Iteration is in code order, which puts single bytes first and
+/// two-byte codes in row-major order, as glyphs are usually laid out in a font.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct SjisCharset {
+	bits: [u64; WORDS],
+}
+
+impl Default for SjisCharset {
+	fn default() -> Self {
+		SjisCharset { bits: [0; WORDS] }
+	}
+}
+
+fn index(enc: EncodedChar) -> Option<usize> {
+	match enc {
+		EncodedChar::One([b]) => Some(b as usize),
+		EncodedChar::Two(_) => {
+			let (ku, ten) = enc.kuten()?;
+			Some(256 + (ku as usize - 1) * 94 + (ten as usize - 1))
+		}
+	}
+}
+
+fn from_index(index: usize) -> EncodedChar {
+	if index < 256 {
+		EncodedChar::One([index as u8])
+	} else {
+		let i = index - 256;
+		EncodedChar::from_kuten((i / 94) as u8 + 1, (i % 94) as u8 + 1).unwrap()
+	}
+}
+
+impl SjisCharset {
+	/// Creates an empty set.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a code to the set, returning whether it was not already present.
+	///
+	/// # Panics
+	/// If `enc` is a two-byte sequence with an invalid trail byte, such as `[81, 7F]`.
+	pub fn insert(&mut self, enc: EncodedChar) -> bool {
+		let i = index(enc).unwrap_or_else(|| panic!("{:02X?} is not a valid code", &*enc));
+		let (word, bit) = (i / 64, 1 << (i % 64));
+		let new = self.bits[word] & bit == 0;
+		self.bits[word] |= bit;
+		new
+	}
+
+	/// Whether the set contains a code.
+	pub fn contains(&self, enc: EncodedChar) -> bool {
+		index(enc).is_some_and(|i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+	}
+
+	/// The number of codes in the set.
+	pub fn len(&self) -> usize {
+		self.bits.iter().map(|w| w.count_ones() as usize).sum()
+	}
+
+	/// Whether the set is empty.
+	pub fn is_empty(&self) -> bool {
+		self.bits.iter().all(|&w| w == 0)
+	}
+
+	/// The codes in either set.
+	pub fn union(&self, other: &SjisCharset) -> SjisCharset {
+		let mut out = self.clone();
+		for (a, b) in out.bits.iter_mut().zip(&other.bits) {
+			*a |= b;
+		}
+		out
+	}
+
+	/// The codes in this set but not in `other`.
+	pub fn difference(&self, other: &SjisCharset) -> SjisCharset {
+		let mut out = self.clone();
+		for (a, b) in out.bits.iter_mut().zip(&other.bits) {
+			*a &= !b;
+		}
+		out
+	}
+
+	/// The codes in this set that are missing from `other`, such as the glyphs a font lacks.
+	///
+	/// This is the same as [`difference`](Self::difference).
+	pub fn missing_from(&self, other: &SjisCharset) -> SjisCharset {
+		self.difference(other)
+	}
+
+	/// Iterates over the codes in code order.
+	pub fn iter(&self) -> impl Iterator<Item = EncodedChar> + '_ {
+		self.bits.iter().enumerate().flat_map(|(i, &word)| {
+			(0..64)
+				.filter(move |b| word & (1 << b) != 0)
+				.map(move |b| from_index(i * 64 + b))
+		})
+	}
+
+	/// Writes the set as text, one code per line in code order, as uppercase hex.
+	pub fn to_text(&self) -> String {
+		let mut out = String::new();
+		for enc in self.iter() {
+			for b in enc {
+				out.push_str(&format!("{b:02X}"));
+			}
+			out.push('\n');
+		}
+		out
+	}
+}
+
+impl fmt::Debug for SjisCharset {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_set().entries(self.iter()).finish()
+	}
+}
+
+impl FromIterator<EncodedChar> for SjisCharset {
+	fn from_iter<I: IntoIterator<Item = EncodedChar>>(iter: I) -> Self {
+		let mut set = SjisCharset::new();
+		set.extend(iter);
+		set
+	}
+}
+
+impl Extend<EncodedChar> for SjisCharset {
+	fn extend<I: IntoIterator<Item = EncodedChar>>(&mut self, iter: I) {
+		for enc in iter {
+			self.insert(enc);
+		}
+	}
+}
+
+/// Collects the codes used by a string.
+///
+/// Fails on the first character that cannot be encoded.
+pub fn collect_charset(s: &str) -> Result<SjisCharset, EncodeError> {
+	let mut set = SjisCharset::new();
+	for (position, char) in s.char_indices() {
+		set.insert(encode_char(char).ok_or(EncodeError { position, char })?);
+	}
+	Ok(set)
+}
+
+/// Collects the codes used by already-encoded data.
+///
+/// Fails on the first invalid byte sequence.
+pub fn collect_charset_bytes(input: &[u8]) -> Result<SjisCharset, DecodeError> {
+	let mut set = SjisCharset::new();
+	let decode_char = |b1, rest: &mut Rest| {
+		let mut enc = EncodedChar::One([b1]);
+		decode_char_from(b1, || {
+			let b2 = rest.next()?;
+			enc = EncodedChar::Two([b1, b2]);
+			Some(b2)
+		})?;
+		Ok(enc)
+	};
+	match sink::decode_into(&mut set, input, decode_char, |_, _, _| false) {
+		Ok(()) => Ok(set),
+		Err(DecodeSinkError::Undecodable(pos, enc)) => Err(DecodeError::from((pos, enc))),
+		Err(DecodeSinkError::Sink(e)) => match e {},
+	}
+}
+
+impl DecodeSink<EncodedChar> for SjisCharset {
+	type Error = Infallible;
+
+	fn put(&mut self, enc: EncodedChar) -> Result<(), Infallible> {
+		self.insert(enc);
+		Ok(())
+	}
+}
+
+/// A set of allowed characters, such as those on a name-entry screen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Whitelist {
+	set: SjisCharset,
+}
+
+/// An error from [`Whitelist::encode_checked`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhitelistError {
+	/// Every disallowed character, with its UTF-8 offset in the input. This is never empty.
+	pub disallowed: Vec<(usize, char)>,
+}
+
+impl Whitelist {
+	/// Creates a whitelist of the characters in `chars`.
+	///
+	/// Fails if any of them cannot be encoded, since they could never be allowed.
+	pub fn from_chars(chars: &str) -> Result<Self, EncodeError> {
+		Ok(Whitelist {
+			set: collect_charset(chars)?,
+		})
+	}
+
+	/// Creates a whitelist of the codes in `set`.
+	pub fn from_charset(set: SjisCharset) -> Self {
+		Whitelist { set }
+	}
+
+	/// Whether a character is allowed.
+	pub fn allows(&self, char: char) -> bool {
+		encode_char(char).is_some_and(|e| self.set.contains(e))
+	}
+
+	/// Checks a string, listing every disallowed character with its UTF-8 offset.
+	pub fn check(&self, s: &str) -> Result<(), Vec<(usize, char)>> {
+		let disallowed = s
+			.char_indices()
+			.filter(|&(_, c)| !self.allows(c))
+			.collect::<Vec<_>>();
+		if disallowed.is_empty() {
+			Ok(())
+		} else {
+			Err(disallowed)
+		}
+	}
+
+	/// Removes the disallowed characters from a string.
+	pub fn filter(&self, s: &str) -> String {
+		s.chars().filter(|&c| self.allows(c)).collect()
+	}
+
+	/// Encodes a string, failing if it contains any disallowed characters.
+	pub fn encode_checked(&self, s: &str) -> Result<Vec<u8>, WhitelistError> {
+		let mut out = Vec::with_capacity(s.len());
+		let mut disallowed = Vec::new();
+		for (pos, char) in s.char_indices() {
+			match encode_char(char).filter(|&e| self.set.contains(e)) {
+				Some(enc) => out.extend(enc),
+				None => disallowed.push((pos, char)),
+			}
+		}
+		if disallowed.is_empty() {
+			Ok(out)
+		} else {
+			Err(WhitelistError { disallowed })
+		}
+	}
+}
+
+impl From<SjisCharset> for Whitelist {
+	fn from(set: SjisCharset) -> Self {
+		Whitelist::from_charset(set)
+	}
+}
+
+impl fmt::Display for WhitelistError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let (position, char) = self.disallowed[0];
+		write!(f, "{char:?} is not allowed at position {position}")?;
+		if self.disallowed.len() > 1 {
+			write!(f, ", and {} more", self.disallowed.len() - 1)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for WhitelistError {}
+
+#[test]
+fn collect() {
+	let s = "英雄伝説 空の軌跡 ｶﾀｶﾅ①\n英雄";
+	let set = collect_charset(s).unwrap();
+	let bytes = crate::encode(s).unwrap();
+	assert_eq!(collect_charset_bytes(&bytes).unwrap(), set);
+	assert_eq!(set.len(), 14);
+	assert!(set.contains(EncodedChar::Two([0x89, 0x70])));
+	assert!(set.contains(EncodedChar::One([b' '])));
+	assert!(set.contains(EncodedChar::One([0xB6])));
+	assert!(set.contains(EncodedChar::Two([0x87, 0x40])));
+	assert!(!set.contains(EncodedChar::One([b'a'])));
+	assert!(!set.contains(EncodedChar::Two([0x81, 0x7F])));
+	assert_eq!(
+		collect_charset("a₂"),
+		Err(EncodeError {
+			position: 1,
+			char: '₂'
+		})
+	);
+	assert_eq!(
+		collect_charset_bytes(b"ab\x81\x7F").unwrap_err().position,
+		2
+	);
+	assert!(collect_charset("").unwrap().is_empty());
+}
+
+#[test]
+fn algebra() {
+	let a = collect_charset("あいうabc").unwrap();
+	let b = collect_charset("うえおcde").unwrap();
+	let union = a.union(&b);
+	assert_eq!(union, collect_charset("あいうえおabcde").unwrap());
+	assert_eq!(a.difference(&b), collect_charset("あいab").unwrap());
+	assert_eq!(b.missing_from(&a), collect_charset("えおde").unwrap());
+	assert_eq!(union.difference(&a).union(&a), union);
+	assert!(a.difference(&a).is_empty());
+	assert_eq!(a.union(&SjisCharset::new()), a);
+
+	let mut set = SjisCharset::new();
+	assert!(set.insert(EncodedChar::Two([0xFC, 0x4B])));
+	assert!(!set.insert(EncodedChar::Two([0xFC, 0x4B])));
+	assert!(set.insert(EncodedChar::Two([0xF0, 0x40])));
+	assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn order() {
+	let font_order = |e: &EncodedChar| (e.len(), e.as_bytes().to_vec());
+	let set = collect_charset("纊日ｱ亜A①\0")
+		.unwrap()
+		.union(&[EncodedChar::Two([0xF0, 0x40])].into_iter().collect());
+	let codes = set.iter().collect::<Vec<_>>();
+	let mut sorted = codes.clone();
+	sorted.sort_by_key(font_order);
+	assert_eq!(codes, sorted);
+	assert_eq!(set.to_text(), "00\n41\nB1\n8740\n889F\n93FA\nED40\nF040\n");
+	assert_eq!(
+		format!("{:?}", collect_charset("aあ").unwrap()),
+		"{One([97]), Two([130, 160])}"
+	);
+
+	let all = (0..BITS).map(from_index).collect::<Vec<_>>();
+	for (i, &enc) in all.iter().enumerate() {
+		assert_eq!(index(enc), Some(i));
+	}
+	assert!(all
+		.windows(2)
+		.all(|w| font_order(&w[0]) < font_order(&w[1])));
+}
+
+#[test]
+fn whitelist() {
+	let kana = ('ぁ'..='ん').chain('ァ'..='ヶ').collect::<String>();
+	let list = Whitelist::from_chars(&(kana + "ABCabc 0123ー")).unwrap();
+	assert!(list.allows('ア'));
+	assert!(!list.allows('日'));
+	assert!(!list.allows('₂'));
+	assert_eq!(list.check("アリサ 01"), Ok(()));
+	assert_eq!(
+		list.check("エステル・B₂日"),
+		Err(vec![(12, '・'), (16, '₂'), (19, '日')])
+	);
+	assert_eq!(list.filter("エステル・B₂日"), "エステルB");
+	assert_eq!(
+		list.encode_checked("カシウス"),
+		Ok(crate::encode("カシウス").unwrap())
+	);
+	let err = list.encode_checked("aｶ日b").unwrap_err();
+	assert_eq!(err.disallowed, [(1, 'ｶ'), (4, '日')]);
+	assert_eq!(
+		err.to_string(),
+		"'ｶ' is not allowed at position 1, and 1 more"
+	);
+
+	let empty = Whitelist::default();
+	assert_eq!(empty.check(""), Ok(()));
+	assert_eq!(empty.check("ab"), Err(vec![(0, 'a'), (1, 'b')]));
+	assert_eq!(empty.filter("ab"), "");
+	assert_eq!(empty.encode_checked("").as_deref(), Ok(&[][..]));
+
+	assert_eq!(Whitelist::from_chars("あ₂").unwrap_err().position, 3);
+	let set = collect_charset("あ").unwrap();
+	assert_eq!(Whitelist::from(set), Whitelist::from_chars("あ").unwrap());
+}
@@ -0,0 +1,123 @@
+//! Lookup tables classifying bytes, shared by the decoder and the scanning functions.
+
+/// What a byte means at the start of a character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Lead {
+	/// ASCII, `00`–`7F`, which decodes to itself.
+	Ascii,
+	/// Half-width katakana, `A1`–`DF`, which decodes to `｡` plus the given offset.
+	Kana(u8),
+	/// The lead byte of a two-byte character, with the index of its pair of rows in the table.
+	Row(u8),
+	/// The user-defined area, `F0`–`F9`. It takes a trail byte, but is not in the table.
+	User,
+	/// `FD`–`FF`, which take a trail byte but are never valid.
+	Reserved,
+	/// `80` and `A0`, which are invalid on their own.
+	Invalid,
+}
+
+impl Lead {
+	/// Whether the byte always starts a two-byte sequence.
+	pub(crate) fn is_double(self) -> bool {
+		matches!(self, Lead::Row(_) | Lead::User | Lead::Reserved)
+	}
+}
+
+/// The class of every byte as a lead byte.
+pub(crate) static LEAD: [Lead; 256] = {
+	let mut table = [Lead::Invalid; 256];
+	let mut b = 0;
+	while b < 256 {
+		table[b] = match b as u8 {
+			0x00..=0x7F => Lead::Ascii,
+			a @ 0xA1..=0xDF => Lead::Kana(a - 0xA1),
+			a @ 0x81..=0x9F => Lead::Row(a - 0x81),
+			a @ 0xE0..=0xEF => Lead::Row(a - 0xE0 + 0x1F),
+			a @ 0xFA..=0xFC => Lead::Row(a - 0xFA + 0x2F),
+			0xF0..=0xF9 => Lead::User,
+			0xFD.. => Lead::Reserved,
+			0x80 | 0xA0 => Lead::Invalid,
+		};
+		b += 1;
+	}
+	table
+};
+
+/// The half-width katakana, indexed by [`Lead::Kana`].
+pub(crate) static KANA: [char; 63] = {
+	let mut table = ['\0'; 63];
+	let mut k = 0;
+	while k < 63 {
+		table[k] = match char::from_u32('｡' as u32 + k as u32) {
+			Some(char) => char,
+			None => panic!(),
+		};
+		k += 1;
+	}
+	table
+};
+
+/// The column of every byte as a trail byte within its pair of rows, or `None` if it is not a
+/// trail byte.
+pub(crate) static TRAIL: [Option<u8>; 256] = {
+	let mut table = [None; 256];
+	let mut b = 0;
+	while b < 256 {
+		table[b] = match b as u8 {
+			b @ 0x40..=0x7E => Some(b - 0x40),
+			b @ 0x80..=0xFC => Some(b - 0x80 + 0x3F),
+			..=0x3F | 0x7F | 0xFD.. => None,
+		};
+		b += 1;
+	}
+	table
+};
+
+/// Whether a byte may start a two-byte character, including the user-defined area.
+pub(crate) fn is_lead_byte(b: u8) -> bool {
+	matches!(LEAD[b as usize], Lead::Row(_) | Lead::User)
+}
+
+/// Whether a byte may end a two-byte character.
+pub(crate) fn is_trail_byte(b: u8) -> bool {
+	TRAIL[b as usize].is_some()
+}
+
+#[test]
+fn tables_match_ranges() {
+	for b in 0..=0xFF {
+		let lead = match b {
+			0x00..=0x7F => Lead::Ascii,
+			0xA1..=0xDF => Lead::Kana(b - 0xA1),
+			0x81..=0x9F => Lead::Row(b - 0x81),
+			0xE0..=0xEF => Lead::Row(b - 0xE0 + 0x1F),
+			0xFA..=0xFC => Lead::Row(b - 0xFA + 0x2F),
+			0xF0..=0xF9 => Lead::User,
+			0xFD.. => Lead::Reserved,
+			0x80 | 0xA0 => Lead::Invalid,
+		};
+		assert_eq!(LEAD[b as usize], lead, "{b:02X}");
+		assert_eq!(
+			LEAD[b as usize].is_double(),
+			matches!(b, 0x81..=0x9F | 0xE0..=0xFF)
+		);
+		assert_eq!(is_lead_byte(b), matches!(b, 0x81..=0x9F | 0xE0..=0xFC));
+		if let Lead::Kana(k) = lead {
+			assert_eq!(
+				crate::encode_char(KANA[k as usize]),
+				Some(crate::EncodedChar::One([b]))
+			);
+		}
+		assert_eq!(is_trail_byte(b), matches!(b, 0x40..=0x7E | 0x80..=0xFC));
+	}
+	// The table leaves out the user-defined area, so its rows only match JIS ones below it.
+	for [b1, b2] in (0..=0xFFFF).map(u16::to_be_bytes) {
+		if let (Lead::Row(a), Some(b), ..=0xEF) = (LEAD[b1 as usize], TRAIL[b2 as usize], b1) {
+			let enc = crate::EncodedChar::Two([b1, b2]);
+			let (ku, ten) = enc.kuten().unwrap();
+			assert_eq!((ku - 1) / 2, a);
+			assert_eq!(ten as usize + (ku as usize - 1) % 2 * 94, b as usize + 1);
+		}
+	}
+}
@@ -0,0 +1,676 @@
+//! A configurable encoder and decoder.
+//!
+//! The free functions each cover one behavior; a [`Codec`] combines several of them, such as
+//! lossiness, vendor preference, gaiji handling and newline normalization, in a single pass.
+
+use std::sync::Arc;
+
+use crate::error::invisible_name;
+use crate::gaiji::{pua_to_user_code, user_code_to_pua};
+use crate::trace::{self, Trace};
+use crate::whatwg;
+use crate::{
+	decode_char_from, DecodeError, EncodeError, EncodedChar, GaijiMap, RowFilter, VendorPreference,
+};
+
+/// How to handle the user-defined area, lead bytes `F0`–`F9`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Gaiji {
+	/// Treat it as invalid, as [`crate::decode`] does.
+	#[default]
+	Reject,
+	/// Map it to the private use area U+E000–U+E757, as Windows does.
+	Pua,
+	/// Use the given assignments. Unassigned codes are invalid.
+	Map(Arc<GaijiMap>),
+}
+
+/// Which line ending to write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LineEnding {
+	/// Leave line endings as they are.
+	#[default]
+	Keep,
+	/// Write both `\n` and `\r\n` as `\n`.
+	Lf,
+	/// Write both `\n` and `\r\n` as `\r\n`.
+	CrLf,
+}
+
+impl LineEnding {
+	fn as_str(self) -> Option<&'static str> {
+		match self {
+			LineEnding::Keep => None,
+			LineEnding::Lf => Some("\n"),
+			LineEnding::CrLf => Some("\r\n"),
+		}
+	}
+}
+
+/// What to encode U+00A0 NO-BREAK SPACE as, which Shift JIS lacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Nbsp {
+	/// Treat it as unencodable, as [`crate::encode`] does.
+	#[default]
+	Unencodable,
+	/// Encode it as the ideographic space `8140`, which is also non-breaking but full-width.
+	Ideographic,
+	/// Encode it as an ASCII space `20`, which keeps the width but may break.
+	Ascii,
+}
+
+/// Which standard the mapping follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Spec {
+	/// Falcom's tables, as used by [`crate::encode`] and [`crate::decode`], adjusted by the other
+	/// options.
+	#[default]
+	Falcom,
+	/// The `shift_jis` encoding of the WHATWG Encoding Standard, which browsers and
+	/// `TextDecoder` implement.
+	///
+	/// This differs from [`Spec::Falcom`] in that:
+	/// - `80` decodes to U+0080, and U+0080 encodes to it.
+	/// - The user-defined area decodes to U+E000–U+E757, but these do not encode.
+	/// - U+00A5 YEN SIGN, U+203E OVERLINE and U+2212 MINUS SIGN encode to `5C`, `7E` and `817C`.
+	/// - The IBM extensions are preferred over the NEC-selected ones when encoding.
+	/// - When a lead byte is followed by an ASCII byte that does not complete a character, only the
+	///   lead byte is invalid, and the ASCII byte is decoded on its own.
+	///
+	/// The options that change the mapping, namely [`vendor`](CodecBuilder::vendor),
+	/// [`gaiji`](CodecBuilder::gaiji), [`nbsp`](CodecBuilder::nbsp),
+	/// [`unassigned`](CodecBuilder::unassigned) and [`rows`](CodecBuilder::rows), are ignored.
+	/// The others still apply; in particular, lossy decoding replaces each error with `�` as the
+	/// standard does, but lossy encoding writes `・` where browsers would write a numeric
+	/// character reference.
+	Whatwg,
+}
+
+/// An encoder and decoder with a set of options.
+///
+/// The default codec behaves like [`crate::encode`] and [`crate::decode`]. Other options are set
+/// with [`Codec::builder`]. A lone `\r` is never treated as a line ending.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Codec {
+	lossy: bool,
+	vendor: VendorPreference,
+	gaiji: Gaiji,
+	newline: LineEnding,
+	nbsp: Nbsp,
+	unassigned: Option<char>,
+	strip_invisible: bool,
+	rows: Option<RowFilter>,
+	spec: Spec,
+	#[cfg(feature = "tracing")]
+	trace_level: Option<tracing::Level>,
+}
+
+/// A builder for a [`Codec`].
+#[derive(Debug, Clone, Default)]
+pub struct CodecBuilder {
+	codec: Codec,
+}
+
+impl CodecBuilder {
+	/// Whether to replace invalid input instead of failing, as per [`crate::encode_lossy`] and
+	/// [`crate::decode_lossy`].
+	pub fn lossy(mut self, lossy: bool) -> Self {
+		self.codec.lossy = lossy;
+		self
+	}
+
+	/// Which code to encode NEC/IBM duplicates to.
+	pub fn vendor(mut self, vendor: VendorPreference) -> Self {
+		self.codec.vendor = vendor;
+		self
+	}
+
+	/// How to handle the user-defined area.
+	pub fn gaiji(mut self, gaiji: Gaiji) -> Self {
+		self.codec.gaiji = gaiji;
+		self
+	}
+
+	/// Which line ending to write, in both directions.
+	pub fn newline(mut self, newline: LineEnding) -> Self {
+		self.codec.newline = newline;
+		self
+	}
+
+	/// What to encode U+00A0 NO-BREAK SPACE as. This does not affect decoding, since neither
+	/// target decodes to U+00A0.
+	pub fn nbsp(mut self, nbsp: Nbsp) -> Self {
+		self.codec.nbsp = nbsp;
+		self
+	}
+
+	/// What to decode unassigned codes to, as per [`DecodeError::is_unassigned`].
+	///
+	/// The conventional choice is the geta mark `〓`, meaning there is a character that cannot be
+	/// shown, as opposed to `�` for bytes that are not a character at all. With `None`, the
+	/// default, they are treated like other invalid sequences. Gaiji take precedence.
+	pub fn unassigned(mut self, unassigned: Option<char>) -> Self {
+		self.codec.unassigned = unassigned;
+		self
+	}
+
+	/// Whether to drop invisible characters when encoding, such as variation selectors, zero-width
+	/// joiners and byte order marks. Shift JIS has none of them, and they are easily left in text
+	/// by editors and input methods. The default is to treat them as unencodable.
+	///
+	/// The characters dropped are exactly the ones special-cased in the [`EncodeError`] message;
+	/// other format characters such as U+00AD SOFT HYPHEN are not included.
+	pub fn strip_invisible(mut self, strip_invisible: bool) -> Self {
+		self.codec.strip_invisible = strip_invisible;
+		self
+	}
+
+	/// Which JIS rows to allow when encoding, as per [`RowFilter`]. Gaiji and the no-break space
+	/// options are not affected, and neither is decoding. The default, `None`, allows all rows.
+	pub fn rows(mut self, rows: Option<RowFilter>) -> Self {
+		self.codec.rows = rows;
+		self
+	}
+
+	/// Which standard the mapping follows. The default is [`Spec::Falcom`].
+	pub fn spec(mut self, spec: Spec) -> Self {
+		self.codec.spec = spec;
+		self
+	}
+
+	/// The level to emit replacement events at when lossy. The default is `DEBUG`.
+	#[cfg(feature = "tracing")]
+	pub fn trace_level(mut self, level: tracing::Level) -> Self {
+		self.codec.trace_level = Some(level);
+		self
+	}
+
+	/// Creates the codec.
+	pub fn build(self) -> Codec {
+		self.codec
+	}
+}
+
+impl Codec {
+	/// Creates a codec with the default options.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Creates a builder, starting from the default options.
+	pub fn builder() -> CodecBuilder {
+		CodecBuilder::default()
+	}
+
+	/// Creates a builder, starting from this codec's options. This is mainly for adjusting one of
+	/// the presets.
+	pub fn to_builder(&self) -> CodecBuilder {
+		CodecBuilder {
+			codec: self.clone(),
+		}
+	}
+
+	/// A preset for text in Falcom's PC releases.
+	///
+	/// This sets:
+	/// - [`lossy`](CodecBuilder::lossy): `false`
+	/// - [`vendor`](CodecBuilder::vendor): [`VendorPreference::Nec`], the codes in Falcom's tables
+	/// - [`gaiji`](CodecBuilder::gaiji): [`Gaiji::Pua`], so that custom glyphs round-trip
+	/// - [`newline`](CodecBuilder::newline): [`LineEnding::Keep`]
+	/// - [`nbsp`](CodecBuilder::nbsp): [`Nbsp::Unencodable`]
+	/// - [`unassigned`](CodecBuilder::unassigned): `None`
+	/// - [`strip_invisible`](CodecBuilder::strip_invisible): `true`, since the games cannot show them
+	/// - [`rows`](CodecBuilder::rows): `None`
+	/// - [`spec`](CodecBuilder::spec): [`Spec::Falcom`]
+	pub fn falcom_pc() -> Self {
+		Codec::builder()
+			.lossy(false)
+			.vendor(VendorPreference::Nec)
+			.gaiji(Gaiji::Pua)
+			.newline(LineEnding::Keep)
+			.nbsp(Nbsp::Unencodable)
+			.unassigned(None)
+			.strip_invisible(true)
+			.rows(None)
+			.spec(Spec::Falcom)
+			.build()
+	}
+
+	/// A preset that only encodes JIS X 0208 and JIS X 0201, for tools and fonts without vendor
+	/// extensions.
+	///
+	/// This sets:
+	/// - [`lossy`](CodecBuilder::lossy): `false`
+	/// - [`vendor`](CodecBuilder::vendor): [`VendorPreference::Nec`], though no character in the
+	///   allowed rows has an IBM code
+	/// - [`gaiji`](CodecBuilder::gaiji): [`Gaiji::Reject`]
+	/// - [`newline`](CodecBuilder::newline): [`LineEnding::Keep`]
+	/// - [`nbsp`](CodecBuilder::nbsp): [`Nbsp::Unencodable`]
+	/// - [`unassigned`](CodecBuilder::unassigned): `None`
+	/// - [`strip_invisible`](CodecBuilder::strip_invisible): `false`
+	/// - [`rows`](CodecBuilder::rows): rows 1–8 and 16–84
+	/// - [`spec`](CodecBuilder::spec): [`Spec::Falcom`]
+	///
+	/// Decoding still accepts the extension rows, since [`rows`](CodecBuilder::rows) only affects
+	/// encoding.
+	pub fn strict_jis() -> Self {
+		Codec::builder()
+			.lossy(false)
+			.vendor(VendorPreference::Nec)
+			.gaiji(Gaiji::Reject)
+			.newline(LineEnding::Keep)
+			.nbsp(Nbsp::Unencodable)
+			.unassigned(None)
+			.strip_invisible(false)
+			.rows(Some(RowFilter::from_rows((1..=8).chain(16..=84))))
+			.spec(Spec::Falcom)
+			.build()
+	}
+
+	#[cfg(feature = "tracing")]
+	fn trace_level(&self) -> trace::Level {
+		self.trace_level.unwrap_or(trace::DEFAULT_LEVEL)
+	}
+
+	#[cfg(not(feature = "tracing"))]
+	fn trace_level(&self) -> trace::Level {
+		trace::DEFAULT_LEVEL
+	}
+
+	/// Encodes a single character, without regard for lossiness or newlines.
+	pub fn encode_char(&self, char: char) -> Option<EncodedChar> {
+		if self.spec == Spec::Whatwg {
+			return whatwg::encode_char(char);
+		}
+		match (char, self.nbsp) {
+			('\u{A0}', Nbsp::Ideographic) => return Some(EncodedChar::Two([0x81, 0x40])),
+			('\u{A0}', Nbsp::Ascii) => return Some(EncodedChar::One([b' '])),
+			_ => {}
+		}
+		let enc = self.vendor.encode_char(char);
+		let enc = enc.filter(|&e| self.rows.is_none_or(|rows| rows.allows(e)));
+		enc.or_else(|| match &self.gaiji {
+			Gaiji::Reject => None,
+			Gaiji::Pua => pua_to_user_code(char).map(EncodedChar::Two),
+			Gaiji::Map(map) => map.encode_char(char),
+		})
+	}
+
+	/// Encodes a string into a byte vec.
+	///
+	/// If the codec is lossy, this never fails.
+	pub fn encode(&self, str: &str) -> Result<Vec<u8>, EncodeError> {
+		let mut out = Vec::with_capacity(str.len());
+		self.encode_to(&mut out, str)?;
+		Ok(out)
+	}
+
+	/// Encodes a string, appending it to a vec.
+	///
+	/// On error, `out` is left as it was.
+	pub fn encode_to(&self, out: &mut Vec<u8>, str: &str) -> Result<(), EncodeError> {
+		let start = out.len();
+		let mut trace = Trace::encode(str.len(), self.trace_level());
+		let mut chars = str.char_indices().peekable();
+		while let Some((position, char)) = chars.next() {
+			if let Some(newline) = self.newline.as_str() {
+				if char == '\n' || char == '\r' && chars.next_if(|&(_, c)| c == '\n').is_some() {
+					out.extend(newline.bytes());
+					continue;
+				}
+			}
+			if self.strip_invisible && invisible_name(char).is_some() {
+				continue;
+			}
+			match self.encode_char(char) {
+				Some(enc) => out.extend(enc),
+				None if self.lossy => {
+					trace.replaced_char(position, char, EncodedChar::REPLACEMENT);
+					out.extend(EncodedChar::REPLACEMENT);
+				}
+				None => {
+					out.truncate(start);
+					return Err(EncodeError { position, char });
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Decodes a byte slice into a string.
+	///
+	/// If the codec is lossy, this never fails.
+	pub fn decode(&self, input: &[u8]) -> Result<String, DecodeError> {
+		let mut out = String::with_capacity(input.len());
+		self.decode_to(&mut out, input)?;
+		Ok(out)
+	}
+
+	/// Decodes a byte slice, appending it to a string.
+	///
+	/// On error, `out` is left as it was.
+	pub fn decode_to(&self, out: &mut String, input: &[u8]) -> Result<(), DecodeError> {
+		let start = out.len();
+		let mut trace = Trace::decode(input.len(), self.trace_level());
+		let mut iter = input.iter();
+		while let Some(&b1) = iter.next() {
+			if let Some(newline) = self.newline.as_str() {
+				if b1 == b'\n' || b1 == b'\r' && iter.as_slice().first() == Some(&b'\n') {
+					if b1 == b'\r' {
+						iter.next();
+					}
+					out.push_str(newline);
+					continue;
+				}
+			}
+			let result = match self.spec {
+				Spec::Falcom => decode_char_from(b1, || iter.next().copied()),
+				Spec::Whatwg => whatwg::decode_char(b1, &mut iter),
+			};
+			let enc = match result {
+				Ok(char) => {
+					out.push(char);
+					continue;
+				}
+				Err(enc) => enc,
+			};
+			let (gaiji, unassigned) = match self.spec {
+				Spec::Falcom => (&self.gaiji, self.unassigned),
+				Spec::Whatwg => (&Gaiji::Reject, None),
+			};
+			match (gaiji, enc.two()) {
+				(Gaiji::Pua, Some(code)) if user_code_to_pua(code).is_some() => {
+					out.extend(user_code_to_pua(code))
+				}
+				(Gaiji::Map(map), Some(code)) if map.get(code).is_some() => {
+					out.extend(map.get(code))
+				}
+				(_, Some(_)) if unassigned.is_some() && enc.kuten().is_some() => {
+					out.extend(unassigned)
+				}
+				_ => {
+					let position = input.len() - iter.len() - enc.len();
+					if self.lossy {
+						trace.replaced_bytes(position, &enc, '�');
+						out.push('�');
+					} else {
+						out.truncate(start);
+						return Err(DecodeError::from((position, enc)));
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+#[test]
+fn default_matches_free_functions() {
+	let codec = Codec::new();
+	let lossy = Codec::builder().lossy(true).build();
+	let mut state = 1u32;
+	for len in 0..300 {
+		let input = (0..len)
+			.map(|_| {
+				state = state.wrapping_mul(1103515245).wrapping_add(12345);
+				(state >> 16) as u8
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(
+			codec.decode(&input),
+			crate::decode(&input).map_err(Into::into)
+		);
+		assert_eq!(lossy.decode(&input).unwrap(), crate::decode_lossy(&input));
+		let s = crate::decode_lossy(&input);
+		assert_eq!(
+			codec.encode(&s),
+			crate::encode(&s).map_err(|pos| EncodeError::at(&s, pos))
+		);
+		assert_eq!(lossy.encode(&s).unwrap(), crate::encode_lossy(&s));
+	}
+	let s = "a₂\r\n日\u{E000}";
+	assert_eq!(
+		codec.encode(s),
+		Err(EncodeError {
+			position: 1,
+			char: '₂'
+		})
+	);
+	assert_eq!(lossy.encode(s).unwrap(), crate::encode_lossy(s));
+}
+
+#[test]
+fn options_compose() {
+	let mut map = GaijiMap::new();
+	map.insert([0xF0, 0x40], "{note}");
+	map.insert([0xF0, 0x41], "♫");
+	let codec = Codec::builder()
+		.lossy(true)
+		.vendor(VendorPreference::Ibm)
+		.gaiji(Gaiji::Map(Arc::new(map)))
+		.newline(LineEnding::Lf)
+		.build();
+	assert_eq!(
+		codec
+			.decode(b"a\r\nb\xF0\x40\r\xF0\x41\n\x80\xF0\x42\r\n")
+			.unwrap(),
+		"a\nb{note}\r♫\n��\n"
+	);
+	assert_eq!(
+		codec.encode("ⅰ♫\r\n₂\n").unwrap(),
+		b"\xFA\x40\xF0\x41\n\x81\x45\n"
+	);
+
+	let pua = Codec::builder()
+		.gaiji(Gaiji::Pua)
+		.newline(LineEnding::CrLf)
+		.build();
+	assert_eq!(
+		pua.decode(b"\xF0\x40\n\xF9\xFC\r\n").unwrap(),
+		"\u{E000}\r\n\u{E757}\r\n"
+	);
+	assert_eq!(
+		pua.encode("\u{E000}\n\u{E757}\r\n").unwrap(),
+		b"\xF0\x40\r\n\xF9\xFC\r\n"
+	);
+	assert_eq!(
+		pua.decode(b"ab\r\n\xF0\x7F"),
+		Err(DecodeError {
+			position: 4,
+			bytes: EncodedChar::Two([0xF0, 0x7F])
+		})
+	);
+	assert_eq!(pua.encode("a\n\u{E758}").unwrap_err().position, 2);
+}
+
+#[test]
+fn to_leaves_output_on_error() {
+	let codec = Codec::new();
+	let mut out = b"x".to_vec();
+	assert!(codec.encode_to(&mut out, "ab₂").is_err());
+	assert_eq!(out, b"x");
+	codec.encode_to(&mut out, "日").unwrap();
+	assert_eq!(out, b"x\x93\xFA");
+
+	let mut out = String::from("x");
+	assert!(codec.decode_to(&mut out, b"ab\x80").is_err());
+	assert_eq!(out, "x");
+	codec.decode_to(&mut out, b"\x93\xFA").unwrap();
+	assert_eq!(out, "x日");
+}
+
+#[test]
+fn send_sync() {
+	fn check<T: Send + Sync>() {}
+	check::<Codec>();
+}
+
+#[test]
+fn nbsp() {
+	let s = "A\u{A0}B";
+	assert_eq!(Codec::new().encode(s).unwrap_err().position, 1);
+	assert_eq!(crate::encode(s), Err(1));
+	let ideographic = Codec::builder().nbsp(Nbsp::Ideographic).build();
+	assert_eq!(ideographic.encode(s).unwrap(), b"A\x81\x40B");
+	assert_eq!(ideographic.decode(b"A\x81\x40B").unwrap(), "A\u{3000}B");
+	let ascii = Codec::builder().nbsp(Nbsp::Ascii).lossy(true).build();
+	assert_eq!(ascii.encode("A\u{A0}\u{2007}B").unwrap(), b"A \x81\x45B");
+}
+
+#[test]
+fn unassigned() {
+	let input = b"a\x85\x40b\x81\x7Fc\xF0\x40";
+	let err = Codec::new().decode(input).unwrap_err();
+	assert_eq!(err.position, 1);
+	assert!(err.is_unassigned());
+
+	let geta = Codec::builder().unassigned(Some('〓')).build();
+	let err = geta.decode(input).unwrap_err();
+	assert_eq!(err.position, 4);
+	assert!(!err.is_unassigned());
+	assert_eq!(geta.decode(b"\x85\x40\xF0\x40").unwrap(), "〓〓");
+
+	let lossy = Codec::builder().unassigned(Some('〓')).lossy(true);
+	assert_eq!(lossy.clone().build().decode(input).unwrap(), "a〓b�c〓");
+	let pua = lossy.gaiji(Gaiji::Pua).build();
+	assert_eq!(pua.decode(input).unwrap(), "a〓b�c\u{E000}");
+
+	// The lossy report tells the two apart as well.
+	let (s, cells) = crate::decode_report_unmapped(input);
+	assert_eq!(s, "a�b�c�");
+	assert_eq!(cells, [(9, 1), (95, 1)]);
+	let counts = |s: &str| (s.matches('〓').count(), s.matches('�').count());
+	let lossy = Codec::builder().unassigned(Some('〓')).lossy(true).build();
+	assert_eq!(
+		counts(&lossy.decode(b"\x85\x40\x80\x85\x41").unwrap()),
+		(2, 1)
+	);
+}
+
+#[test]
+fn strip_invisible() {
+	let strip = Codec::builder().strip_invisible(true).build();
+	// Ideographic variation selectors, as in the 葛 of 葛飾.
+	assert_eq!(
+		strip.encode("葛\u{E0100}飾").unwrap(),
+		crate::encode("葛飾").unwrap()
+	);
+	assert_eq!(strip.encode("A\u{200D}B\u{FE0F}").unwrap(), b"AB");
+	assert_eq!(strip.encode("\u{FEFF}あ").unwrap(), b"\x82\xA0");
+
+	let err = Codec::new().encode("\u{FEFF}あ").unwrap_err();
+	assert_eq!(err.position, 0);
+	assert!(err.to_string().contains("U+FEFF (byte order mark)"));
+
+	// Visible characters are never dropped, encodable or not.
+	let s = "\u{3000} \u{A0}\u{AD}";
+	assert_eq!(strip.encode(s), Codec::new().encode(s));
+	let lossy = Codec::builder().strip_invisible(true).lossy(true).build();
+	assert_eq!(lossy.encode("a\u{AD}\u{200B}b").unwrap(), b"a\x81\x45b");
+}
+
+#[test]
+fn presets() {
+	// Each preset on a gaiji code, a NEC row 13 character, a wave dash, a control byte and an
+	// invisible character. Changing a preset should change this test.
+	let battery = |codec: &Codec| {
+		(
+			codec.decode(b"\xF0\x40").ok(),
+			codec.encode("①").ok(),
+			codec.encode("〜").ok(),
+			(codec.decode(b"\x01").ok(), codec.encode("\x01").ok()),
+			codec.encode("葛\u{E0100}").ok(),
+		)
+	};
+	assert_eq!(
+		battery(&Codec::falcom_pc()),
+		(
+			Some("\u{E000}".to_owned()),
+			Some(b"\x87\x40".to_vec()),
+			None,
+			(Some("\x01".to_owned()), Some(b"\x01".to_vec())),
+			Some(b"\x8A\x8B".to_vec()),
+		)
+	);
+	assert_eq!(
+		battery(&Codec::strict_jis()),
+		(
+			None,
+			None,
+			None,
+			(Some("\x01".to_owned()), Some(b"\x01".to_vec())),
+			None,
+		)
+	);
+	assert_eq!(Codec::strict_jis().encode("葛①").unwrap_err().position, 3);
+	assert_eq!(
+		Codec::strict_jis()
+			.encode("ｱ亜腕弌纊")
+			.unwrap_err()
+			.position,
+		12
+	);
+	assert_eq!(Codec::strict_jis().decode(b"\x87\x40").unwrap(), "①");
+
+	let tweaked = Codec::falcom_pc().to_builder().lossy(true).build();
+	assert_eq!(tweaked.encode("〜\u{FEFF}").unwrap(), b"\x81\x45");
+	assert_eq!(
+		Codec::falcom_pc().to_builder().lossy(false).build(),
+		Codec::falcom_pc()
+	);
+}
+
+#[test]
+fn whatwg() {
+	let whatwg = Codec::builder().spec(Spec::Whatwg).build();
+	assert_eq!(
+		whatwg.decode(b"\x80\xF0\x40\x87\x40").unwrap(),
+		"\u{80}\u{E000}①"
+	);
+	assert_eq!(whatwg.encode("¥‾−纊").unwrap(), b"\\~\x81\x7C\xFA\x5C");
+	assert_eq!(
+		whatwg.encode("ab\u{E000}"),
+		Err(EncodeError {
+			position: 2,
+			char: '\u{E000}'
+		})
+	);
+	// Only the lead byte is invalid when the trail byte is ASCII.
+	assert_eq!(
+		whatwg.decode(b"ab\x85\x40"),
+		Err(DecodeError {
+			position: 2,
+			bytes: EncodedChar::One([0x85])
+		})
+	);
+	assert_eq!(
+		whatwg.decode(b"ab\x85\x9F"),
+		Err(DecodeError {
+			position: 2,
+			bytes: EncodedChar::Two([0x85, 0x9F])
+		})
+	);
+
+	// The mapping options are ignored, while the others apply.
+	let tweaked = whatwg
+		.to_builder()
+		.lossy(true)
+		.newline(LineEnding::CrLf)
+		.vendor(VendorPreference::Nec)
+		.gaiji(Gaiji::Reject)
+		.nbsp(Nbsp::Ascii)
+		.unassigned(Some('〓'))
+		.rows(Some(RowFilter::from_rows(1..=8)))
+		.build();
+	assert_eq!(
+		tweaked
+			.decode(b"\xF0\x40\n\x85\x40\x81\x7F\r\n\xFF")
+			.unwrap(),
+		"\u{E000}\r\n�@�\x7F\r\n�"
+	);
+	assert_eq!(
+		tweaked.encode("纊\u{A0}\n\u{E000}").unwrap(),
+		b"\xFA\x5C\x81\x45\r\n\x81\x45"
+	);
+	assert_eq!(Codec::new().decode(b"\x80").unwrap_err().position, 0);
+}
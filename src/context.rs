@@ -0,0 +1,118 @@
+//! Formatting of decode errors with their surrounding bytes.
+
+use std::fmt::Write;
+
+use crate::decode_char_from;
+
+/// Formats the bytes around an invalid sequence at `offset`, for error messages.
+///
+/// The output has two lines. The first has the offset of the first byte shown, followed by about
+/// `window` bytes on either side of the invalid sequence in hex, with the sequence itself
+/// bracketed. The second has the decoded characters aligned below their first byte, with a
+/// replacement character for each invalid byte and `.` for control characters.
+///
+/// Character boundaries are found by decoding from the start of `input`, so the window may be
+/// widened slightly to avoid cutting a character in half. Offsets past the end are clamped.
+pub fn format_error_context(input: &[u8], offset: usize, window: usize) -> String {
+	let offset = offset.min(input.len());
+	let err_end = if offset < input.len() {
+		offset + unit(input, offset).0
+	} else {
+		offset
+	};
+	let lo = offset.saturating_sub(window);
+	let hi = (err_end + window).min(input.len());
+
+	let mut units = Vec::new();
+	let mut pos = 0;
+	while pos < hi {
+		let (len, char) = if pos == offset {
+			(err_end - offset, None)
+		} else {
+			unit(input, pos)
+		};
+		// A character straddling the invalid sequence is treated as invalid.
+		let (len, char) = if pos < offset && pos + len > offset {
+			(offset - pos, None)
+		} else {
+			(len, char)
+		};
+		if pos + len > lo {
+			units.push((pos, len, char));
+		}
+		pos += len;
+	}
+	let start = units.first().map_or(offset, |u| u.0);
+	let end = units.last().map_or(offset, |u| u.0 + u.1);
+
+	let mut hex = format!("{:08X}:", start);
+	for (p, b) in input.iter().enumerate().take(end).skip(start) {
+		let sep = match p {
+			_ if p == offset => '[',
+			_ if p == err_end && err_end != offset => ']',
+			_ => ' ',
+		};
+		write!(hex, "{}{:02X}", sep, b).unwrap();
+	}
+	if end == err_end && err_end != offset {
+		hex.push(']');
+	}
+
+	let mut text = " ".repeat(9);
+	let mut col = 0;
+	let mut put = |text: &mut String, pos: usize, char: char, width: usize| {
+		let target = 3 * (pos - start) + 1;
+		text.extend(std::iter::repeat_n(' ', target.saturating_sub(col)));
+		text.push(char);
+		col = col.max(target) + width;
+	};
+	for (pos, len, char) in units {
+		match char {
+			Some(c) if c.is_control() => put(&mut text, pos, '.', 1),
+			Some(c) => put(&mut text, pos, c, len),
+			None => (pos..pos + len).for_each(|p| put(&mut text, p, '�', 1)),
+		}
+	}
+	hex + "\n" + text.trim_end()
+}
+
+/// Decodes the character at `pos`, returning its length and the character if it is valid.
+fn unit(input: &[u8], pos: usize) -> (usize, Option<char>) {
+	let mut len = 1;
+	let char = decode_char_from(input[pos], || {
+		len = 2;
+		input.get(pos + 1).copied()
+	});
+	(len.min(input.len() - pos), char.ok())
+}
+
+#[test]
+fn context_snapshots() {
+	let input = b"\x93\xFA\x96\x7BA\x01\xEE\xEE\xB1\x83\x40\x81";
+	assert_eq!(
+		format_error_context(input, 6, 4),
+		"00000002: 96 7B 41 01[EE EE]B1 83 40 81\n          本    A  .  �  �  ｱ  ァ    �",
+	);
+	assert_eq!(
+		format_error_context(b"\xEE\xEE\x93\xFA\x41", 0, 3),
+		"00000000:[EE EE]93 FA 41\n          �  �  日    A",
+	);
+	assert_eq!(
+		format_error_context(input, 11, 3),
+		"00000008: B1 83 40[81]\n          ｱ  ァ    �",
+	);
+	assert_eq!(
+		format_error_context(input, 6, 0),
+		"00000006:[EE EE]\n          �  �",
+	);
+	assert_eq!(
+		format_error_context(input, 100, 1),
+		"0000000B: 81\n          �"
+	);
+	assert_eq!(format_error_context(b"", 0, 5), "00000000:\n");
+	// The window is widened so 日 is not cut in half.
+	assert_eq!(
+		format_error_context(b"\x93\xFA\x80", 2, 1),
+		"00000000: 93 FA[80]\n          日    �",
+	);
+}
@@ -0,0 +1,167 @@
+//! Comparison with Microsoft's CP932.
+//!
+//! Windows and tools like `iconv -f CP932` use Microsoft's mapping, which mostly agrees with this
+//! crate's. The reference mapping is embedded from `cp932.txt`, and [`cp932_differences`] lists
+//! every point where the two disagree.
+
+use std::collections::BTreeSet;
+
+use crate::{encode_char, EncodedChar};
+
+static CP932_DECODE: [[u16; 188]; 60] = include!(concat!(env!("OUT_DIR"), "/cp932_decode.rs"));
+static CP932_ENCODE: &[(char, [u8; 2])] = include!(concat!(env!("OUT_DIR"), "/cp932_encode.rs"));
+
+/// A point where this crate and CP932 disagree, from [`cp932_differences`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Difference {
+	/// A code that decodes differently. `None` means the code is not assigned.
+	Decode {
+		code: EncodedChar,
+		ours: Option<char>,
+		cp932: Option<char>,
+	},
+	/// A character that encodes differently. `None` means the character cannot be encoded.
+	Encode {
+		char: char,
+		ours: Option<EncodedChar>,
+		cp932: Option<EncodedChar>,
+	},
+}
+
+pub(crate) fn cp932_decode(enc: EncodedChar) -> Option<char> {
+	match enc {
+		EncodedChar::One([b @ 0x00..=0x7F]) => Some(b as char),
+		EncodedChar::One([b @ 0xA1..=0xDF]) => char::from_u32(0xFF61 + (b - 0xA1) as u32),
+		EncodedChar::One(_) => None,
+		EncodedChar::Two([b1, b2]) => {
+			let a = match b1 {
+				0x81..=0x9F => b1 - 0x81,
+				0xE0..=0xFC => b1 - 0xE0 + 0x1F,
+				_ => return None,
+			};
+			let b = match b2 {
+				0x40..=0x7E => b2 - 0x40,
+				0x80..=0xFC => b2 - 0x80 + 0x3F,
+				_ => return None,
+			};
+			match CP932_DECODE[a as usize][b as usize] {
+				0 => None,
+				c => char::from_u32(c as u32),
+			}
+		}
+	}
+}
+
+pub(crate) fn cp932_encode(char: char) -> Option<EncodedChar> {
+	match char {
+		'\0'..='\x7F' => Some(EncodedChar::One([char as u8])),
+		'｡'..='ﾟ' => Some(EncodedChar::One([(char as u32 - 0xFF61) as u8 + 0xA1])),
+		_ => {
+			let i = CP932_ENCODE.binary_search_by_key(&char, |e| e.0).ok()?;
+			Some(EncodedChar::Two(CP932_ENCODE[i].1))
+		}
+	}
+}
+
+/// Every code and character that this crate maps differently from CP932.
+///
+/// Decoding differences come first, in code order, followed by encoding differences, in char
+/// order. Both include codes or characters that only one side assigns.
+pub fn cp932_differences() -> impl Iterator<Item = Difference> {
+	let singles = (0..=0xFF).map(|b| EncodedChar::One([b]));
+	let b1 = (0x81..=0x9F).chain(0xE0..=0xFC);
+	let doubles = b1.flat_map(|b1| {
+		(0x40..=0x7E)
+			.chain(0x80..=0xFC)
+			.map(move |b2| EncodedChar::Two([b1, b2]))
+	});
+	let codes = singles.chain(doubles).collect::<Vec<_>>();
+
+	let mut chars = BTreeSet::new();
+	let mut out = Vec::new();
+	for &code in &codes {
+		let (ours, cp932) = (code.to_char(), cp932_decode(code));
+		chars.extend(ours);
+		chars.extend(cp932);
+		if ours != cp932 {
+			out.push(Difference::Decode { code, ours, cp932 });
+		}
+	}
+	for char in chars {
+		let (ours, cp932) = (encode_char(char), cp932_encode(char));
+		if ours != cp932 {
+			out.push(Difference::Encode { char, ours, cp932 });
+		}
+	}
+	out.into_iter()
+}
+
+/// Whether a code decodes the same in this crate as in CP932, including both rejecting it.
+pub fn decodes_like_cp932(e: EncodedChar) -> bool {
+	e.to_char() == cp932_decode(e)
+}
+
+#[test]
+fn known_differences() {
+	let diffs = cp932_differences().collect::<Vec<_>>();
+	// The user-defined area is the only decoding difference, as the tables otherwise agree.
+	let decode = diffs
+		.iter()
+		.filter(|d| matches!(d, Difference::Decode { .. }))
+		.count();
+	assert_eq!(decode, 1880);
+	assert!(diffs.contains(&Difference::Decode {
+		code: EncodedChar::Two([0xF0, 0x40]),
+		ours: None,
+		cp932: Some('\u{E000}'),
+	}));
+	assert!(diffs.contains(&Difference::Encode {
+		char: '\u{E000}',
+		ours: None,
+		cp932: Some(EncodedChar::Two([0xF0, 0x40])),
+	}));
+	// Windows prefers a JIS X 0208 code, then NEC row 13, then the IBM extensions, and the
+	// NEC-selected IBM extensions last. This crate prefers the NEC-selected ones to the IBM ones.
+	assert!(diffs.contains(&Difference::Encode {
+		char: '纊',
+		ours: Some(EncodedChar::Two([0xED, 0x40])),
+		cp932: Some(EncodedChar::Two([0xFA, 0x5C])),
+	}));
+	assert!(diffs.contains(&Difference::Encode {
+		char: 'ⅰ',
+		ours: Some(EncodedChar::Two([0xEE, 0xEF])),
+		cp932: Some(EncodedChar::Two([0xFA, 0x40])),
+	}));
+	for d in &diffs {
+		if let Difference::Encode {
+			char,
+			ours: Some(_),
+			cp932,
+		} = *d
+		{
+			assert_eq!(crate::VendorPreference::Ibm.encode_char(char), cp932);
+		}
+	}
+	// With the IBM preference, every character with an IBM code encodes as in Windows, including
+	// ones that also have a JIS X 0208 or NEC row 13 code.
+	for b1 in 0xFA..=0xFC {
+		for b2 in (0x40..=0x7E).chain(0x80..=0xFC) {
+			if let Ok(char) = crate::decode_pair(b1, Some(b2)) {
+				let ibm = crate::VendorPreference::Ibm.encode_char(char);
+				assert_eq!(ibm, cp932_encode(char), "{char}");
+			}
+		}
+	}
+
+	// Ordinary text, including the wave dash family, is unaffected.
+	for c in "亜日本語、ｶﾀｶﾅ～≒∵￢Ⅰ①".chars() {
+		assert!(!diffs
+			.iter()
+			.any(|d| matches!(d, Difference::Encode { char, .. } if *char == c)));
+		let enc = encode_char(c).unwrap();
+		assert!(decodes_like_cp932(enc), "{c}");
+	}
+	assert!(decodes_like_cp932(EncodedChar::One([0x80])));
+	assert!(decodes_like_cp932(EncodedChar::Two([0x85, 0x40])));
+	assert!(!decodes_like_cp932(EncodedChar::Two([0xF9, 0xFC])));
+}
@@ -0,0 +1,126 @@
+//! Encoders and decoders with per-character overrides.
+
+use std::collections::HashMap;
+
+use crate::sink;
+use crate::{decode_char_from, encode_char, EncodedChar};
+
+/// A decoder with custom mappings for specific byte sequences.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Decoder {
+	overrides: HashMap<[u8; 2], char>,
+}
+
+impl Decoder {
+	/// Creates a decoder without any overrides, which behaves like [`crate::decode`].
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Decodes the given two bytes as `ch`, taking precedence over the table.
+	///
+	/// The first byte must be one that starts a two-byte sequence, otherwise the override has no
+	/// effect.
+	pub fn with_override(mut self, bytes: [u8; 2], ch: char) -> Self {
+		self.overrides.insert(bytes, ch);
+		self
+	}
+
+	/// Like [`crate::decode_char_from`], but with the overrides.
+	pub fn decode_char_from(
+		&self,
+		b1: u8,
+		b2: impl FnOnce() -> Option<u8>,
+	) -> Result<char, EncodedChar> {
+		let mut second = None;
+		let result = decode_char_from(b1, || {
+			second = b2();
+			second
+		});
+		match second.and_then(|b2| self.overrides.get(&[b1, b2])) {
+			Some(&ch) => Ok(ch),
+			None => result,
+		}
+	}
+
+	/// Like [`crate::decode`], but with the overrides.
+	pub fn decode(&self, input: &[u8]) -> Result<String, (usize, EncodedChar)> {
+		sink::decode_strict(input, |b1, rest| self.decode_char_from(b1, || rest.next()))
+	}
+
+	/// Like [`crate::decode_lossy`], but with the overrides.
+	pub fn decode_lossy(&self, input: &[u8]) -> String {
+		let mut out = String::new();
+		sink::decode_replacing(&mut out, input, |b1, rest| {
+			self.decode_char_from(b1, || rest.next())
+		});
+		out
+	}
+}
+
+/// An encoder with custom mappings for specific characters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Encoder {
+	overrides: HashMap<char, EncodedChar>,
+}
+
+impl Encoder {
+	/// Creates an encoder without any overrides, which behaves like [`crate::encode`].
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Encodes `ch` as the given bytes, taking precedence over the table.
+	pub fn with_override(mut self, ch: char, bytes: EncodedChar) -> Self {
+		self.overrides.insert(ch, bytes);
+		self
+	}
+
+	/// Like [`crate::encode_char`], but with the overrides.
+	pub fn encode_char(&self, char: char) -> Option<EncodedChar> {
+		self.overrides
+			.get(&char)
+			.copied()
+			.or_else(|| encode_char(char))
+	}
+
+	/// Like [`crate::encode`], but with the overrides.
+	pub fn encode(&self, str: &str) -> Result<Vec<u8>, usize> {
+		crate::sink::encode_strict(str, |char| self.encode_char(char))
+	}
+
+	/// Like [`crate::encode_lossy`], but with the overrides.
+	pub fn encode_lossy(&self, str: &str) -> Vec<u8> {
+		crate::sink::encode_replacing(str, |char| self.encode_char(char))
+	}
+}
+
+#[test]
+fn decoder_override() {
+	let dec = Decoder::new()
+		.with_override([0x87, 0x8A], '♥')
+		.with_override([0xF0, 0x40], '♫');
+	assert_eq!(
+		dec.decode(&[0x87, 0x8A, 0xF0, 0x40, 0x87, 0x8B]).as_deref(),
+		Ok("♥♫㈲")
+	);
+	assert_eq!(crate::decode(&[0x87, 0x8A]).as_deref(), Ok("㈱"));
+	assert_eq!(
+		dec.decode(&[0x41, 0xF0, 0x41]),
+		Err((1, EncodedChar::Two([0xF0, 0x41])))
+	);
+	assert_eq!(dec.decode_lossy(&[0xF0, 0x41, 0xF0, 0x40, 0x87]), "�♫�");
+}
+
+#[test]
+fn encoder_override() {
+	let enc = Encoder::new()
+		.with_override('♥', EncodedChar::Two([0x87, 0x8A]))
+		.with_override('A', EncodedChar::One([0x61]));
+	assert_eq!(
+		enc.encode("♥A㈱").as_deref(),
+		Ok(&[0x87, 0x8A, 0x61, 0x87, 0x8A][..])
+	);
+	assert_eq!(enc.encode("♥₂"), Err(3));
+	assert_eq!(enc.encode_lossy("₂"), [0x81, 0x45]);
+}
@@ -0,0 +1,69 @@
+//! Decoding compressed text, under the `flate2` feature.
+
+use std::io::Read;
+
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+
+use crate::{decode, TranscodeError, TranscodeErrorKind};
+
+/// Inflates and decodes text compressed with gzip, zlib or raw deflate.
+///
+/// The format is recognized by its header: gzip starts with `1F 8B`, and zlib with a two-byte
+/// header whose checksum is valid. Anything else is taken to be raw deflate.
+///
+/// Decoding errors are [`TranscodeErrorKind::Decode`], with the position in the inflated data.
+/// Corrupt compressed data gives [`TranscodeErrorKind::Io`], with the number of bytes inflated
+/// before it.
+pub fn decode_deflated(input: &[u8]) -> Result<String, TranscodeError> {
+	let mut reader: Box<dyn Read + '_> = match input {
+		[0x1F, 0x8B, ..] => Box::new(GzDecoder::new(input)),
+		[cmf, flg, ..] if cmf & 0x0F == 8 && u16::from_be_bytes([*cmf, *flg]) % 31 == 0 => {
+			Box::new(ZlibDecoder::new(input))
+		}
+		_ => Box::new(DeflateDecoder::new(input)),
+	};
+	let mut bytes = Vec::new();
+	if let Err(e) = reader.read_to_end(&mut bytes) {
+		return Err(TranscodeError::at(bytes.len(), TranscodeErrorKind::Io(e)));
+	}
+	decode(&bytes).map_err(|(pos, enc)| TranscodeError::at(pos, TranscodeErrorKind::Decode(enc)))
+}
+
+#[test]
+fn formats() {
+	use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+	use flate2::Compression;
+	use std::io::Write;
+
+	let text = "英雄伝説 空の軌跡\n".repeat(50);
+	let raw = crate::encode(&text).unwrap();
+	let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+	gz.write_all(&raw).unwrap();
+	let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+	zlib.write_all(&raw).unwrap();
+	let mut deflate = DeflateEncoder::new(Vec::new(), Compression::default());
+	deflate.write_all(&raw).unwrap();
+	for compressed in [
+		gz.finish().unwrap(),
+		zlib.finish().unwrap(),
+		deflate.finish().unwrap(),
+	] {
+		assert_eq!(decode_deflated(&compressed).unwrap(), text);
+	}
+}
+
+#[test]
+fn errors() {
+	use flate2::write::GzEncoder;
+	use flate2::Compression;
+	use std::io::Write;
+
+	let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+	gz.write_all(b"abc\x80").unwrap();
+	let e = decode_deflated(&gz.finish().unwrap()).unwrap_err();
+	assert_eq!(e.position, 3);
+	assert!(matches!(e.kind, TranscodeErrorKind::Decode(_)));
+
+	let e = decode_deflated(b"\x1F\x8Bgarbage").unwrap_err();
+	assert!(e.is_io());
+}
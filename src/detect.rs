@@ -0,0 +1,249 @@
+//! Guessing the encoding of a byte buffer.
+
+use crate::iter::decode_units;
+
+/// An encoding that [`detect`] can recognize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+	/// Shift JIS, as decoded by this crate.
+	ShiftJis,
+	/// UTF-8.
+	Utf8,
+	/// EUC-JP, including half-width katakana and JIS X 0212.
+	EucJp,
+	/// None of the above, or the input is ambiguous.
+	Unknown,
+}
+
+/// The result of [`detect`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+	/// The most likely encoding.
+	pub guess: Encoding,
+	/// The difference between the scores of the best and second best guesses, from 0 to 1.
+	pub confidence: f64,
+	/// The score of each encoding from 0 to 1, best first.
+	pub scores: [(Encoding, f64); 3],
+}
+
+/// Guesses whether the input is Shift JIS, UTF-8 or EUC-JP.
+///
+/// Each encoding gets a score as follows:
+/// - If the input is not entirely valid in that encoding, the score is 0. For Shift JIS this means
+///   [`crate::decode`] succeeds, and truncated characters at the end are not accepted.
+/// - Otherwise, the score is 0.5 plus 0.5 times the fraction of non-ASCII characters that are
+///   typical in Japanese text: full-width punctuation, kana and level 1 kanji. Half-width katakana
+///   are not counted as typical, since they are what EUC-JP text tends to look like in Shift JIS.
+///
+/// The guess is the encoding with the highest score, preferring Shift JIS, then UTF-8, then
+/// EUC-JP in case of ties. If all scores are 0, the guess is [`Encoding::Unknown`].
+///
+/// Pure ASCII is valid and identical in all three encodings, so it is reported as
+/// [`Encoding::Unknown`] with a confidence of 0 and all scores 1, including empty input.
+pub fn detect(input: &[u8]) -> Detection {
+	let mut scores = [
+		(Encoding::ShiftJis, score_sjis(input)),
+		(Encoding::Utf8, score_utf8(input)),
+		(Encoding::EucJp, score_euc(input)),
+	];
+	scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+	let guess = if input.is_ascii() || scores[0].1 == 0.0 {
+		Encoding::Unknown
+	} else {
+		scores[0].0
+	};
+	Detection {
+		guess,
+		confidence: scores[0].1 - scores[1].1,
+		scores,
+	}
+}
+
+/// Whether [`detect`] guesses Shift JIS. This is false for pure ASCII.
+pub fn is_probably_sjis(input: &[u8]) -> bool {
+	detect(input).guess == Encoding::ShiftJis
+}
+
+fn score(valid: bool, typical: usize, total: usize) -> f64 {
+	match (valid, total) {
+		(false, _) => 0.0,
+		(true, 0) => 1.0,
+		(true, _) => 0.5 + 0.5 * typical as f64 / total as f64,
+	}
+}
+
+/// Counts the non-ASCII units that are valid, invalid, and valid and typical.
+fn count_sjis(input: &[u8]) -> (usize, usize, usize) {
+	let (mut valid, mut invalid, mut typical) = (0, 0, 0);
+	for (range, char) in decode_units(input) {
+		if input[range.start] < 0x80 && char.is_ok() {
+			continue;
+		}
+		if char.is_err() {
+			invalid += 1;
+			continue;
+		}
+		valid += 1;
+		if range.len() == 2 {
+			// Rows 1, 4, 5 and 16–47.
+			let [b1, b2] = [input[range.start], input[range.start + 1]];
+			typical +=
+				matches!(
+					(b1, b2),
+					(0x81, ..=0x9E)
+						| (0x82, 0x9F..) | (0x83, ..=0x9E)
+						| (0x88, 0x9F..) | (0x89..=0x97, _)
+						| (0x98, ..=0x9E)
+				) as usize;
+		}
+	}
+	(valid, invalid, typical)
+}
+
+/// The fraction of valid sequences among `valid + invalid`, or 1 if there are none.
+pub(crate) fn fraction(valid: usize, invalid: usize) -> f64 {
+	match valid + invalid {
+		0 => 1.0,
+		total => valid as f64 / total as f64,
+	}
+}
+
+/// The fraction of non-ASCII sequences that are valid Shift JIS.
+pub(crate) fn sjis_validity(input: &[u8]) -> f64 {
+	let (valid, invalid, _) = count_sjis(input);
+	fraction(valid, invalid)
+}
+
+fn score_sjis(input: &[u8]) -> f64 {
+	let (valid, invalid, typical) = count_sjis(input);
+	score(invalid == 0, typical, valid)
+}
+
+/// Scores how likely the input is to be Shift JIS, from 0 to 1.
+///
+/// Unlike [`detect`], invalid sequences lower the score rather than ruling Shift JIS out, so this
+/// can be used to rank candidates for damaged files. Only non-ASCII sequences are counted:
+/// - *validity* is the fraction of them that [`crate::decode`] accepts;
+/// - *plausibility* is the fraction of the valid ones that are typical in Japanese text, as
+///   described for [`detect`];
+/// - the score is validity times `0.5 + 0.5 * plausibility`.
+///
+/// Pure ASCII, including empty input, scores 1. The score is the same as that of [`detect`] when
+/// the input is entirely valid.
+pub fn sjis_confidence(input: &[u8]) -> f32 {
+	let (valid, invalid, typical) = count_sjis(input);
+	(fraction(valid, invalid) * score(true, typical, valid)) as f32
+}
+
+fn score_utf8(input: &[u8]) -> f64 {
+	let Ok(s) = std::str::from_utf8(input) else {
+		return 0.0;
+	};
+	let total = s.chars().filter(|c| !c.is_ascii()).count();
+	let typical = s
+		.chars()
+		.filter(
+			|c| matches!(c, '\u{3000}'..='\u{30FF}' | '\u{4E00}'..='\u{9FFF}' | '\u{FF01}'..='\u{FF5E}'),
+		)
+		.count();
+	score(true, typical, total)
+}
+
+fn score_euc(input: &[u8]) -> f64 {
+	let (mut typical, mut total) = (0, 0);
+	let mut iter = input.iter().copied();
+	while let Some(b1) = iter.next() {
+		let valid = match b1 {
+			0x00..=0x7F => continue,
+			0x8E => matches!(iter.next(), Some(0xA1..=0xDF)),
+			0x8F => matches!(
+				(iter.next(), iter.next()),
+				(Some(0xA1..=0xFE), Some(0xA1..=0xFE))
+			),
+			0xA1..=0xFE => {
+				let b2 = iter.next();
+				// Rows 1, 4, 5 and 16–47.
+				typical += matches!(b1, 0xA1 | 0xA4 | 0xA5 | 0xB0..=0xCF) as usize;
+				matches!(b2, Some(0xA1..=0xFE))
+			}
+			_ => false,
+		};
+		if !valid {
+			return 0.0;
+		}
+		total += 1;
+	}
+	score(true, typical, total)
+}
+
+#[cfg(test)]
+const SAMPLE: &str = "日本ファルコムの「英雄伝説」は、とても面白いゲームです。";
+
+#[test]
+fn detect_sjis() {
+	let sjis = crate::encode(SAMPLE).unwrap();
+	let d = detect(&sjis);
+	assert_eq!(d.guess, Encoding::ShiftJis);
+	assert!(d.confidence > 0.5, "{d:?}");
+	assert!(is_probably_sjis(&sjis));
+}
+
+#[test]
+fn detect_utf8() {
+	let d = detect(SAMPLE.as_bytes());
+	assert_eq!(d.guess, Encoding::Utf8);
+	assert!(d.confidence > 0.3, "{d:?}");
+	assert!(!is_probably_sjis(SAMPLE.as_bytes()));
+}
+
+#[test]
+fn detect_euc() {
+	// EUC-JP is row and cell plus 0xA0, which is easiest to compute from Shift JIS.
+	let mut euc = Vec::new();
+	for char in SAMPLE.chars() {
+		let (ku, ten) = crate::encode_char(char).unwrap().kuten().unwrap();
+		euc.extend([ku + 0xA0, ten + 0xA0]);
+	}
+	let d = detect(&euc);
+	assert_eq!(d.guess, Encoding::EucJp);
+	assert!(d.confidence > 0.3, "{d:?}");
+	assert!(!is_probably_sjis(&euc));
+}
+
+#[test]
+fn detect_ascii_and_binary() {
+	let d = detect(b"Hello, world!");
+	assert_eq!(d.guess, Encoding::Unknown);
+	assert_eq!(d.confidence, 0.0);
+	assert!(!is_probably_sjis(b"Hello"));
+	assert_eq!(detect(b"").guess, Encoding::Unknown);
+
+	let mut state = 1u32;
+	let binary = (0..1000)
+		.map(|_| {
+			state = state.wrapping_mul(1103515245).wrapping_add(12345);
+			(state >> 16) as u8
+		})
+		.collect::<Vec<_>>();
+	let d = detect(&binary);
+	assert_eq!(d.guess, Encoding::Unknown);
+	assert_eq!(d.scores.map(|s| s.1), [0.0; 3]);
+}
+
+#[test]
+fn confidence() {
+	let sjis = crate::encode(SAMPLE).unwrap();
+	assert_eq!(sjis_confidence(&sjis), score_sjis(&sjis) as f32);
+	assert!(sjis_confidence(&sjis) > 0.9);
+	assert_eq!(sjis_confidence(b"Hello"), 1.0);
+	assert_eq!(sjis_confidence(b""), 1.0);
+	assert_eq!(sjis_confidence(b"\x80\xA0"), 0.0);
+
+	let mut damaged = sjis.clone();
+	damaged.extend([0x80, 0x80, 0x80]);
+	let c = sjis_confidence(&damaged);
+	assert!(0.0 < c && c < sjis_confidence(&sjis), "{c}");
+	assert!(sjis_confidence(SAMPLE.as_bytes()) < 0.6);
+	// Half-width katakana are valid but not typical.
+	assert_eq!(sjis_confidence(b"\xB1\xB2"), 0.5);
+}
@@ -0,0 +1,187 @@
+//! Character-level comparison of Shift JIS buffers.
+
+use std::ops::Range;
+
+use crate::{decode_lossy, decode_units};
+
+/// A changed region found by [`diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+	/// The byte range in the old buffer. Empty for an insertion.
+	pub a: Range<usize>,
+	/// The byte range in the new buffer. Empty for a deletion.
+	pub b: Range<usize>,
+	/// The old text, decoded lossily.
+	pub old: String,
+	/// The new text, decoded lossily.
+	pub new: String,
+}
+
+/// Compares two buffers character by character.
+///
+/// Both are split into characters as per [`decode_units`], and the hunks are the regions that
+/// differ, in order. Invalid byte sequences are compared by their bytes, so they only match
+/// identical invalid sequences, and do not affect how the surrounding text is aligned.
+pub fn diff(a: &[u8], b: &[u8]) -> Vec<DiffHunk> {
+	let units_a = decode_units(a).collect::<Vec<_>>();
+	let units_b = decode_units(b).collect::<Vec<_>>();
+	let keys_a = units_a.iter().map(|u| u.1).collect::<Vec<_>>();
+	let keys_b = units_b.iter().map(|u| u.1).collect::<Vec<_>>();
+
+	let byte_a = |i: usize| units_a.get(i).map_or(a.len(), |u| u.0.start);
+	let byte_b = |i: usize| units_b.get(i).map_or(b.len(), |u| u.0.start);
+	let mut hunks = Vec::new();
+	let (mut i0, mut j0) = (0, 0);
+	let matches = matches(&keys_a, &keys_b);
+	for (i, j) in matches.into_iter().chain([(keys_a.len(), keys_b.len())]) {
+		if i > i0 || j > j0 {
+			let (ra, rb) = (byte_a(i0)..byte_a(i), byte_b(j0)..byte_b(j));
+			hunks.push(DiffHunk {
+				old: decode_lossy(&a[ra.clone()]),
+				new: decode_lossy(&b[rb.clone()]),
+				a: ra,
+				b: rb,
+			});
+		}
+		(i0, j0) = (i + 1, j + 1);
+	}
+	hunks
+}
+
+/// Finds the pairs of matching indices in a shortest edit script, using Myers' algorithm.
+fn matches<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+	let (n, m) = (a.len() as isize, b.len() as isize);
+	let max = (n + m) as usize;
+	let mut v = vec![0isize; 2 * max + 3];
+	let at = |k: isize| (k + max as isize + 1) as usize;
+	// The frontier before each round, for k in -d..=d.
+	let mut trace = Vec::new();
+	'outer: for d in 0..=max as isize {
+		trace.push(v[at(-d)..=at(d)].to_vec());
+		for k in (-d..=d).step_by(2) {
+			let mut x = if k == -d || k != d && v[at(k - 1)] < v[at(k + 1)] {
+				v[at(k + 1)]
+			} else {
+				v[at(k - 1)] + 1
+			};
+			let mut y = x - k;
+			while x < n && y < m && a[x as usize] == b[y as usize] {
+				x += 1;
+				y += 1;
+			}
+			v[at(k)] = x;
+			if x >= n && y >= m {
+				break 'outer;
+			}
+		}
+	}
+
+	let mut out = Vec::new();
+	let (mut x, mut y) = (n, m);
+	for (d, v) in trace.iter().enumerate().rev() {
+		let d = d as isize;
+		let get = |k: isize| v[(k + d) as usize];
+		let k = x - y;
+		let prev_k = if k == -d || k != d && get(k - 1) < get(k + 1) {
+			k + 1
+		} else {
+			k - 1
+		};
+		let (prev_x, prev_y) = if d == 0 {
+			(0, 0)
+		} else {
+			(get(prev_k), get(prev_k) - prev_k)
+		};
+		while x > prev_x && y > prev_y {
+			x -= 1;
+			y -= 1;
+			out.push((x as usize, y as usize));
+		}
+		(x, y) = (prev_x, prev_y);
+	}
+	out.reverse();
+	out
+}
+
+#[cfg(test)]
+fn hunk(a: Range<usize>, b: Range<usize>, old: &str, new: &str) -> DiffHunk {
+	DiffHunk {
+		a,
+		b,
+		old: old.to_owned(),
+		new: new.to_owned(),
+	}
+}
+
+#[test]
+fn edits() {
+	let enc = |s| crate::encode(s).unwrap();
+	let old = enc("エステルとヨシュア");
+	assert_eq!(diff(&old, &old), []);
+	assert_eq!(diff(b"", b""), []);
+	assert_eq!(
+		diff(&old, &enc("エステルと、ヨシュア")),
+		[hunk(10..10, 10..12, "", "、")]
+	);
+	assert_eq!(
+		diff(&old, &enc("エステルとシュア")),
+		[hunk(10..12, 10..10, "ヨ", "")]
+	);
+	assert_eq!(
+		diff(&enc("英雄伝説"), &enc("英雄伝記")),
+		[hunk(6..8, 6..8, "説", "記")]
+	);
+	assert_eq!(
+		diff(&enc("a日b本c"), &enc("A日b語c!")),
+		[
+			hunk(0..1, 0..1, "a", "A"),
+			hunk(4..6, 4..6, "本", "語"),
+			hunk(7..7, 7..8, "", "!"),
+		]
+	);
+	assert_eq!(diff(b"", b"ab"), [hunk(0..0, 0..2, "", "ab")]);
+	assert_eq!(diff(b"ab", b""), [hunk(0..2, 0..0, "ab", "")]);
+}
+
+#[test]
+fn invalid() {
+	let old = b"\x82\xA0\x80\x82\xA2";
+	assert_eq!(diff(old, old), []);
+	// The invalid byte is a unit of its own, so the kana after it still line up.
+	assert_eq!(diff(old, b"\x82\xA0\x82\xA2"), [hunk(2..3, 2..2, "�", "")]);
+	assert_eq!(
+		diff(b"\x82\xA0\x82\xA2", b"\x82\xA0\x81\x20\x82\xA2"),
+		[hunk(2..2, 2..4, "", "�")]
+	);
+	assert_eq!(diff(b"a\x80b", b"a\xA0b"), [hunk(1..2, 1..2, "�", "�")]);
+}
+
+#[test]
+fn random() {
+	let mut state = 1u32;
+	let mut next = || {
+		state = state.wrapping_mul(1103515245).wrapping_add(12345);
+		(state >> 16) as usize
+	};
+	let chars = ['日', '本', 'a', 'b', 'ｱ'];
+	for _ in 0..200 {
+		let a = (0..next() % 20)
+			.map(|_| chars[next() % 5])
+			.collect::<String>();
+		let b = (0..next() % 20)
+			.map(|_| chars[next() % 5])
+			.collect::<String>();
+		let (ea, eb) = (crate::encode(&a).unwrap(), crate::encode(&b).unwrap());
+		// Applying the hunks to the old buffer gives the new one.
+		let mut out = Vec::new();
+		let mut pos = 0;
+		for h in diff(&ea, &eb) {
+			assert!(h.a.start >= pos);
+			out.extend_from_slice(&ea[pos..h.a.start]);
+			out.extend_from_slice(&eb[h.b.clone()]);
+			pos = h.a.end;
+		}
+		out.extend_from_slice(&ea[pos..]);
+		assert_eq!(out, eb);
+	}
+}
@@ -0,0 +1,356 @@
+//! Error types.
+
+use std::{fmt, io};
+
+use crate::{
+	EncodeUtf16Error, EncodedChar, FixedError, GaijiMapError, PrefixedError, StringTableError,
+	TranscodeError, UnescapeError, WhitelistError,
+};
+
+/// A character that could not be encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EncodeError {
+	/// The UTF-8 offset of the character in the input string.
+	pub position: usize,
+	/// The character itself.
+	pub char: char,
+}
+
+impl EncodeError {
+	/// Creates an error for the character at `position` in `str`, as returned by [`crate::encode`].
+	///
+	/// # Panics
+	/// If `position` is not the start of a character in `str`.
+	pub fn at(str: &str, position: usize) -> Self {
+		let char = str[position..]
+			.chars()
+			.next()
+			.expect("position out of bounds");
+		EncodeError { position, char }
+	}
+}
+
+/// The name of an invisible character, if it is one of those that are often left in text by
+/// editors and input methods, and that [`crate::CodecBuilder::strip_invisible`] drops.
+///
+/// These are the variation selectors U+FE00–U+FE0F and U+E0100–U+E01EF, the zero-width space,
+/// non-joiner and joiner U+200B–U+200D, the word joiner U+2060, and the byte order mark U+FEFF.
+pub(crate) fn invisible_name(char: char) -> Option<&'static str> {
+	Some(match char {
+		'\u{FE00}'..='\u{FE0F}' => "variation selector",
+		'\u{E0100}'..='\u{E01EF}' => "ideographic variation selector",
+		'\u{200B}' => "zero width space",
+		'\u{200C}' => "zero width non-joiner",
+		'\u{200D}' => "zero width joiner",
+		'\u{2060}' => "word joiner",
+		'\u{FEFF}' => "byte order mark",
+		_ => return None,
+	})
+}
+
+/// A byte sequence that could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DecodeError {
+	/// The offset of the first byte of the sequence in the input.
+	pub position: usize,
+	/// The invalid byte sequence.
+	pub bytes: EncodedChar,
+}
+
+impl DecodeError {
+	/// Formats the bytes around the error, as per [`crate::format_error_context`].
+	///
+	/// `input` must be the input that was being decoded.
+	pub fn context(&self, input: &[u8], window: usize) -> String {
+		crate::format_error_context(input, self.position, window)
+	}
+
+	/// Whether the bytes form a well-structured two-byte code that is merely unassigned, such as
+	/// `8540`, rather than not being a character at all.
+	pub fn is_unassigned(&self) -> bool {
+		self.bytes.kuten().is_some()
+	}
+}
+
+impl From<(usize, EncodedChar)> for DecodeError {
+	fn from((position, bytes): (usize, EncodedChar)) -> Self {
+		DecodeError { position, bytes }
+	}
+}
+
+/// A byte sequence that could not be decoded by [`crate::decode_iter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DecodeIterError {
+	/// The number of bytes consumed before the sequence, which for a slice is its offset.
+	pub consumed: usize,
+	/// The invalid byte sequence.
+	pub bytes: EncodedChar,
+}
+
+impl From<DecodeIterError> for DecodeError {
+	fn from(e: DecodeIterError) -> Self {
+		DecodeError {
+			position: e.consumed,
+			bytes: e.bytes,
+		}
+	}
+}
+
+/// More byte sequences could not be decoded by [`crate::decode_tolerant`] than its budget allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TooManyErrors {
+	/// The number of invalid sequences that were allowed.
+	pub max_errors: usize,
+	/// The first invalid sequence past the budget.
+	pub error: DecodeError,
+}
+
+impl fmt::Display for EncodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match invisible_name(self.char) {
+			Some(name) => write!(
+				f,
+				"cannot encode invisible character U+{:04X} ({name}) at position {}",
+				self.char as u32, self.position
+			),
+			None => write!(
+				f,
+				"cannot encode {:?} at position {}",
+				self.char, self.position
+			),
+		}
+	}
+}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"cannot decode {:02X?} at position {}",
+			&*self.bytes, self.position
+		)?;
+		if self.is_unassigned() {
+			write!(f, " (unassigned)")?;
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Display for DecodeIterError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"cannot decode {:02X?} after {} bytes",
+			&*self.bytes, self.consumed
+		)
+	}
+}
+
+impl fmt::Display for TooManyErrors {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"more than {} invalid byte sequences, the next at position {}",
+			self.max_errors, self.error.position
+		)
+	}
+}
+
+impl std::error::Error for EncodeError {}
+/// A decode error is always the innermost cause, so [`source`](std::error::Error::source) is
+/// `None`.
+impl std::error::Error for DecodeError {}
+impl std::error::Error for DecodeIterError {}
+impl std::error::Error for TooManyErrors {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&self.error)
+	}
+}
+
+/// Any error from this crate, for applications that want a single error type.
+///
+/// Every error type in the crate converts into this, so `?` works in a function returning
+/// `Result<T, falcom_sjis::Error>`. The message and [`source`](std::error::Error::source) are
+/// those of the wrapped error.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+	/// A character could not be encoded.
+	Encode(EncodeError),
+	/// A byte sequence could not be decoded.
+	Decode(DecodeError),
+	/// Too many byte sequences could not be decoded, as per [`crate::decode_tolerant`].
+	TooManyErrors(TooManyErrors),
+	/// Reading or writing failed.
+	Io(io::Error),
+	/// Transcoding a stream failed.
+	Transcode(TranscodeError),
+	/// A gaiji map could not be loaded.
+	GaijiMap(GaijiMapError),
+	/// Control code markup could not be encoded.
+	Unescape(UnescapeError),
+	/// A fixed-width field could not be encoded.
+	Fixed(FixedError),
+	/// A length-prefixed string could not be encoded.
+	Prefixed(PrefixedError),
+	/// A string table could not be decoded.
+	StringTable(StringTableError),
+	/// A UTF-16 string could not be encoded, as per [`crate::encode_utf16`].
+	EncodeUtf16(EncodeUtf16Error),
+	/// Text contained characters outside a whitelist, as per [`crate::Whitelist::encode_checked`].
+	Whitelist(WhitelistError),
+}
+
+macro_rules! error_from {
+	($($variant:ident($ty:ty),)*) => {
+		$(impl From<$ty> for Error {
+			fn from(e: $ty) -> Self {
+				Error::$variant(e)
+			}
+		})*
+
+		impl Error {
+			fn inner(&self) -> &(dyn std::error::Error + 'static) {
+				match self {
+					$(Error::$variant(e) => e,)*
+				}
+			}
+		}
+	};
+}
+
+error_from! {
+	Encode(EncodeError),
+	Decode(DecodeError),
+	TooManyErrors(TooManyErrors),
+	Io(io::Error),
+	Transcode(TranscodeError),
+	GaijiMap(GaijiMapError),
+	Unescape(UnescapeError),
+	Fixed(FixedError),
+	Prefixed(PrefixedError),
+	StringTable(StringTableError),
+	EncodeUtf16(EncodeUtf16Error),
+	Whitelist(WhitelistError),
+}
+
+impl From<DecodeIterError> for Error {
+	fn from(e: DecodeIterError) -> Self {
+		Error::Decode(e.into())
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self.inner(), f)
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		self.inner().source()
+	}
+}
+
+#[test]
+fn invisible_display() {
+	let e = EncodeError::at("葛\u{FE0F}", 3);
+	assert_eq!(
+		e.to_string(),
+		"cannot encode invisible character U+FE0F (variation selector) at position 3"
+	);
+	let e = EncodeError::at("\u{E0100}", 0);
+	assert_eq!(
+		e.to_string(),
+		"cannot encode invisible character U+E0100 (ideographic variation selector) at position 0"
+	);
+	assert_eq!(
+		EncodeError::at("a€", 1).to_string(),
+		"cannot encode '€' at position 1"
+	);
+}
+
+#[test]
+fn decode_error_chain() {
+	use std::error::Error;
+
+	#[derive(Debug)]
+	struct Context(DecodeError);
+	impl fmt::Display for Context {
+		fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+			write!(f, "in scena.bin")
+		}
+	}
+	impl Error for Context {
+		fn source(&self) -> Option<&(dyn Error + 'static)> {
+			Some(&self.0)
+		}
+	}
+
+	fn load(input: &[u8]) -> Result<String, Box<dyn Error + Send + Sync>> {
+		Ok(crate::try_decode(input)?)
+	}
+	assert_eq!(load(b"\x93\xFA").unwrap(), "日");
+	let e = load(b"ab\x85\x40").unwrap_err();
+	assert_eq!(
+		e.to_string(),
+		"cannot decode [85, 40] at position 2 (unassigned)"
+	);
+	assert!(e.source().is_none());
+	assert_eq!(
+		e.downcast_ref::<DecodeError>(),
+		Some(&DecodeError {
+			position: 2,
+			bytes: EncodedChar::Two([0x85, 0x40])
+		})
+	);
+
+	let e = Context(crate::try_decode(b"a\x80").unwrap_err());
+	let chain = std::iter::successors(Some(&e as &dyn Error), |&e| e.source())
+		.map(|e| e.to_string())
+		.collect::<Vec<_>>();
+	assert_eq!(chain, ["in scena.bin", "cannot decode [80] at position 1"]);
+}
+
+#[test]
+fn unified_error() {
+	use std::error::Error as _;
+
+	fn tool(text: &str, sjis: &[u8], map: &str) -> Result<(), Error> {
+		crate::try_decode(sjis)?;
+		crate::Codec::new().encode(text)?;
+		crate::load_gaiji_map(map.as_bytes())?;
+		let mut out = Vec::new();
+		crate::transcode_sjis_to_utf8(sjis, &mut out, false)?;
+		Ok(())
+	}
+	assert!(tool("日本", b"\x93\xFA", "F040 = a").is_ok());
+
+	let e = tool("a₂", b"", "").unwrap_err();
+	assert!(matches!(e, Error::Encode(EncodeError { position: 1, .. })));
+	assert_eq!(e.to_string(), "cannot encode '₂' at position 1");
+	assert!(e.source().is_none());
+
+	let e = tool("", b"a\x80", "").unwrap_err();
+	assert!(matches!(e, Error::Decode(DecodeError { position: 1, .. })));
+	assert_eq!(e.to_string(), "cannot decode [80] at position 1");
+
+	let e = tool("", b"", "F040 = a\nE000 = b").unwrap_err();
+	assert!(matches!(e, Error::GaijiMap(_)));
+	assert_eq!(e.to_string(), "line 2: [E0, 00] is not a gaiji code");
+
+	let e = Error::from(crate::decode_iter(*b"ab\x81").unwrap_err());
+	assert!(matches!(e, Error::Decode(DecodeError { position: 2, .. })));
+
+	// A nested cause is reached through the wrapped error's source.
+	let io = io::Error::new(io::ErrorKind::UnexpectedEof, "truncated");
+	let e = Error::from(TranscodeError::at(7, crate::TranscodeErrorKind::Io(io)));
+	assert_eq!(e.to_string(), "truncated at position 7");
+	let source = e.source().unwrap();
+	assert_eq!(source.to_string(), "truncated");
+	assert!(source.downcast_ref::<io::Error>().is_some());
+
+	let e = Error::from(io::Error::other("disk on fire"));
+	assert_eq!(e.to_string(), "disk on fire");
+	assert!(matches!(e, Error::Io(_)));
+}
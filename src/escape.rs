@@ -0,0 +1,177 @@
+//! Reversible escaped representations of Shift JIS text.
+
+use crate::{decode_char_from, encode_char};
+
+/// Decodes a byte slice into a string, escaping anything that cannot be decoded.
+///
+/// Invalid byte sequences are written as `\xNN`, one escape per byte, and literal backslashes are
+/// written as `\\`. The duplicate codes, which would encode to a different byte sequence, are also
+/// escaped. The output is thus lossless, and is turned back into the original bytes by
+/// [`encode_git_friendly`].
+pub fn decode_git_friendly(input: &[u8]) -> String {
+	let mut out = String::new();
+	let mut iter = input.iter().copied();
+	while let Some(b1) = iter.next() {
+		let start = input.len() - iter.len() - 1;
+		let result = decode_char_from(b1, || iter.next());
+		let bytes = &input[start..input.len() - iter.len()];
+		match result {
+			Ok('\\') => out.push_str("\\\\"),
+			Ok(char) if encode_char(char).as_deref() == Some(bytes) => out.push(char),
+			_ => {
+				for b in bytes {
+					out.push_str(&format!("\\x{:02X}", b));
+				}
+			}
+		}
+	}
+	out
+}
+
+/// Encodes a string produced by [`decode_git_friendly`].
+///
+/// Returns `Err(position)` if a codepoint cannot be represented in Shift JIS or a backslash does not
+/// start a valid escape, where `position` is the UTF-8 offset of the offending codepoint.
+pub fn encode_git_friendly(str: &str) -> Result<Vec<u8>, usize> {
+	let mut out = Vec::new();
+	let mut pos = 0;
+	while let Some(char) = str[pos..].chars().next() {
+		let rest = &str[pos + char.len_utf8()..];
+		if char != '\\' {
+			out.extend(encode_char(char).ok_or(pos)?);
+			pos += char.len_utf8();
+		} else if rest.starts_with('\\') {
+			out.push(b'\\');
+			pos += 2;
+		} else if let Some(hex) = rest
+			.strip_prefix('x')
+			.and_then(|r| r.get(..2))
+			.filter(|h| is_hex(h))
+		{
+			out.push(u8::from_str_radix(hex, 16).unwrap());
+			pos += 4;
+		} else {
+			return Err(pos);
+		}
+	}
+	Ok(out)
+}
+
+fn is_hex(s: &str) -> bool {
+	s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[test]
+fn git_friendly_roundtrip() {
+	let input = [0x93, 0xFA, 0x5C, 0x81, 0x20, 0xF0, 0x40, 0x80, 0x41, 0x83];
+	let dec = decode_git_friendly(&input);
+	assert_eq!(dec, r"日\\\x81\x20\xF0\x40\x80A\x83");
+	assert_eq!(encode_git_friendly(&dec).as_deref(), Ok(&input[..]));
+	assert_eq!(
+		decode_git_friendly(&[0x87, 0x90, 0x81, 0xE0]),
+		"\\x87\\x90≒"
+	);
+	for array in (0..=0xFFFF).map(u16::to_le_bytes) {
+		assert_eq!(
+			encode_git_friendly(&decode_git_friendly(&array)).as_deref(),
+			Ok(&array[..])
+		);
+	}
+}
+
+#[test]
+fn git_friendly_errors() {
+	assert_eq!(encode_git_friendly(r"a\x4"), Err(1));
+	assert_eq!(encode_git_friendly(r"a\x4G"), Err(1));
+	assert_eq!(encode_git_friendly(r"日\n"), Err(3));
+	assert_eq!(encode_git_friendly(r"\"), Err(0));
+	assert_eq!(encode_git_friendly("₂"), Err(0));
+	assert_eq!(
+		encode_git_friendly(r"\x8Fあ").as_deref(),
+		Ok(&[0x8F, 0x82, 0xA0][..])
+	);
+}
+
+/// Encodes a string into a byte vec, escaping characters that cannot be encoded.
+///
+/// Characters that cannot be represented in Shift JIS are written as `\u{XXXX}`, and literal
+/// backslashes as `\\`. Since the escapes are pure ASCII, this never fails. After decoding, the
+/// original string is recovered with [`unescape_unicode`].
+pub fn encode_escape_unicode(str: &str) -> Vec<u8> {
+	let mut out = Vec::new();
+	for char in str.chars() {
+		if char == '\\' {
+			out.extend(b"\\\\");
+		} else if let Some(enc) = encode_char(char) {
+			out.extend(enc);
+		} else {
+			out.extend(format!("\\u{{{:X}}}", char as u32).bytes());
+		}
+	}
+	out
+}
+
+/// Resolves the escapes written by [`encode_escape_unicode`].
+///
+/// Backslashes that do not start a valid escape are kept as is.
+pub fn unescape_unicode(str: &str) -> String {
+	let mut out = String::new();
+	let mut rest = str;
+	while let Some(i) = rest.find('\\') {
+		out.push_str(&rest[..i]);
+		rest = &rest[i..];
+		if let Some(r) = rest.strip_prefix("\\\\") {
+			out.push('\\');
+			rest = r;
+		} else if let Some((char, r)) = parse_unicode_escape(rest) {
+			out.push(char);
+			rest = r;
+		} else {
+			out.push('\\');
+			rest = &rest[1..];
+		}
+	}
+	out.push_str(rest);
+	out
+}
+
+fn parse_unicode_escape(s: &str) -> Option<(char, &str)> {
+	let (hex, rest) = s.strip_prefix("\\u{")?.split_once('}')?;
+	if hex.is_empty() || hex.len() > 6 || !is_hex(hex) {
+		return None;
+	}
+	Some((char::from_u32(u32::from_str_radix(hex, 16).ok()?)?, rest))
+}
+
+#[test]
+fn escape_unicode() {
+	let enc = encode_escape_unicode("日₂\\u{41}😀");
+	assert_eq!(enc, b"\x93\xFA\\u{2082}\\\\u{41}\\u{1F600}");
+	assert_eq!(
+		unescape_unicode(&crate::decode(&enc).unwrap()),
+		"日₂\\u{41}😀"
+	);
+	assert_eq!(
+		unescape_unicode("\\u{D800}\\u{}\\u{41\\x\\"),
+		"\\u{D800}\\u{}\\u{41\\x\\"
+	);
+	assert_eq!(unescape_unicode("\\u{3042}\\\\"), "あ\\");
+}
+
+#[test]
+fn escape_unicode_roundtrip() {
+	let pool = [
+		'\\', 'u', '{', '}', '1', 'F', 'a', '日', '₂', '😀', '\u{FFFF}', '\0', '¥',
+	];
+	let mut state = 1u32;
+	for len in 0..500 {
+		let str = (0..len % 20)
+			.map(|_| {
+				state = state.wrapping_mul(1103515245).wrapping_add(12345);
+				pool[(state >> 16) as usize % pool.len()]
+			})
+			.collect::<String>();
+		let dec = crate::decode(&encode_escape_unicode(&str)).unwrap();
+		assert_eq!(unescape_unicode(&dec), str);
+	}
+}
@@ -0,0 +1,116 @@
+//! Conversion between Shift JIS and EUC-JP.
+//!
+//! Both encode the same JIS X 0208 rows and cells, so this is done arithmetically for each
+//! character, without going through Unicode. Cells are converted whether or not they are assigned.
+
+use crate::iter::is_lead;
+use crate::{EncodedChar, TranscodeError, TranscodeErrorKind};
+
+/// Converts the EUC-JP character at the start of the input to Shift JIS, returning it along with
+/// its length in EUC-JP.
+pub(crate) fn from_euc_jp(input: &[u8]) -> Option<(EncodedChar, usize)> {
+	match *input {
+		[b @ 0x00..=0x7F, ..] => Some((EncodedChar::One([b]), 1)),
+		[0x8E, b @ 0xA1..=0xDF, ..] => Some((EncodedChar::One([b]), 2)),
+		[b1 @ 0xA1..=0xFE, b2 @ 0xA1..=0xFE, ..] => {
+			Some((EncodedChar::from_kuten(b1 - 0xA0, b2 - 0xA0)?, 2))
+		}
+		_ => None,
+	}
+}
+
+/// Converts Shift JIS to EUC-JP, as per [`EncodedChar::to_euc_jp`].
+///
+/// Invalid byte sequences are [`TranscodeErrorKind::Decode`] errors, and the user-defined and IBM
+/// extension areas (lead bytes `F0`–`FC`) are [`TranscodeErrorKind::Unrepresentable`].
+pub fn sjis_to_euc_jp(input: &[u8]) -> Result<Vec<u8>, TranscodeError> {
+	let mut out = Vec::with_capacity(input.len());
+	let mut pos = 0;
+	while let Some(&b1) = input.get(pos) {
+		let enc = match input.get(pos + 1) {
+			Some(&b2) if is_lead(b1) => EncodedChar::Two([b1, b2]),
+			_ => EncodedChar::One([b1]),
+		};
+		let Some(euc) = enc.to_euc_jp() else {
+			let kind = if enc.kuten().is_some() {
+				TranscodeErrorKind::Unrepresentable(enc)
+			} else {
+				TranscodeErrorKind::Decode(enc)
+			};
+			return Err(TranscodeError::at(pos, kind));
+		};
+		out.extend(euc);
+		pos += enc.len();
+	}
+	Ok(out)
+}
+
+/// Converts EUC-JP to Shift JIS.
+///
+/// JIS X 0212 characters, prefixed with `8F`, have no Shift JIS equivalent and are rejected along
+/// with other invalid sequences as [`TranscodeErrorKind::Malformed`].
+pub fn euc_jp_to_sjis(input: &[u8]) -> Result<Vec<u8>, TranscodeError> {
+	let mut out = Vec::with_capacity(input.len());
+	let mut pos = 0;
+	while pos < input.len() {
+		let (enc, len) = from_euc_jp(&input[pos..])
+			.ok_or(TranscodeError::at(pos, TranscodeErrorKind::Malformed))?;
+		out.extend(enc);
+		pos += len;
+	}
+	Ok(out)
+}
+
+#[test]
+fn corpus() {
+	let s = "日本ファルコムの「英雄伝説 空の軌跡」、ｶﾀｶﾅ①㈱！ ABC\n";
+	let sjis = crate::encode(s).unwrap();
+	let mut euc = Vec::new();
+	for c in s.chars() {
+		euc.extend(crate::encode_char(c).unwrap().to_euc_jp().unwrap());
+	}
+	assert_eq!(sjis_to_euc_jp(&sjis).unwrap(), euc);
+	assert_eq!(euc_jp_to_sjis(&euc).unwrap(), sjis);
+	assert_eq!(
+		crate::decode_auto(&euc),
+		(s.to_owned(), crate::Encoding::EucJp)
+	);
+}
+
+#[test]
+fn all_cells() {
+	for ku in 1..=94 {
+		for ten in 1..=94 {
+			let sjis = EncodedChar::from_kuten(ku, ten).unwrap();
+			let euc = sjis_to_euc_jp(&sjis).unwrap();
+			assert_eq!(euc, [ku + 0xA0, ten + 0xA0]);
+			assert_eq!(euc_jp_to_sjis(&euc).unwrap(), &*sjis);
+		}
+	}
+}
+
+#[test]
+fn malformed() {
+	let err = |r: Result<Vec<u8>, TranscodeError>| {
+		let e = r.unwrap_err();
+		(e.position, e.to_string())
+	};
+	assert_eq!(
+		err(sjis_to_euc_jp(b"ab\x81\x7F")),
+		(2, "cannot decode [81, 7F] at position 2".to_owned())
+	);
+	assert_eq!(err(sjis_to_euc_jp(b"a\x80")).0, 1);
+	assert_eq!(err(sjis_to_euc_jp(b"a\x88")).0, 1);
+	assert_eq!(
+		err(sjis_to_euc_jp(b"a\xF0\x40")),
+		(1, "cannot represent [F0, 40] at position 1".to_owned())
+	);
+	assert_eq!(
+		err(euc_jp_to_sjis(b"a\xA4\xA2\x8F\xB0\xA1")),
+		(3, "malformed input at position 3".to_owned())
+	);
+	assert_eq!(err(euc_jp_to_sjis(b"\x8E\xE0")).0, 0);
+	assert_eq!(err(euc_jp_to_sjis(b"\xA4\x41")).0, 0);
+	assert_eq!(err(euc_jp_to_sjis(b"a\xA4")).0, 1);
+	assert_eq!(err(euc_jp_to_sjis(b"\x80")).0, 0);
+}
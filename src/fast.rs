@@ -0,0 +1,53 @@
+//! Faster implementations using unsafe code, enabled with the `unsafe-fast` feature.
+
+use crate::{decode_char_from, EncodedChar};
+
+/// Like [`crate::decode_checked`], but builds the output as bytes.
+///
+/// The output buffer is allocated up front with room for the worst case, so that each character
+/// can be written as a fixed four-byte store without checking the capacity, of which only the
+/// length of its UTF-8 encoding is kept.
+pub(crate) fn decode(input: &[u8]) -> Result<String, (usize, EncodedChar)> {
+	// Each input byte gives at most three bytes of output: single-byte characters are at most
+	// U+FFFF, and two-byte ones at most U+10FFFF. The extra four bytes are for the last store.
+	let cap = input.len() * 3 + 4;
+	let mut out = Vec::<u8>::with_capacity(cap);
+	let mut iter = input.iter().copied();
+	while let Some(b1) = iter.next() {
+		let char = match decode_char_from(b1, || iter.next()) {
+			Ok(char) => char,
+			Err(enc) => return Err((input.len() - iter.len() - enc.len(), enc)),
+		};
+		let mut buf = [0; 4];
+		let len = char.encode_utf8(&mut buf).len();
+		debug_assert!(out.len() + 4 <= cap);
+		// SAFETY: By the above bound, there are at least four bytes of spare capacity.
+		unsafe {
+			let end = out.as_mut_ptr().add(out.len());
+			end.cast::<[u8; 4]>().write_unaligned(buf);
+			out.set_len(out.len() + len);
+		}
+	}
+	debug_assert!(std::str::from_utf8(&out).is_ok());
+	// SAFETY: Only complete UTF-8 encodings of chars have been appended, and any concatenation of
+	// those is valid UTF-8.
+	Ok(unsafe { String::from_utf8_unchecked(out) })
+}
+
+#[test]
+fn same_as_checked() {
+	let mut state = 1u32;
+	let mut next = || {
+		state = state.wrapping_mul(1103515245).wrapping_add(12345);
+		(state >> 16) as u8
+	};
+	for len in 0..500 {
+		let input = (0..len).map(|_| next()).collect::<Vec<_>>();
+		assert_eq!(decode(&input), crate::decode_checked(&input));
+		// Mostly valid input, with runs of ASCII.
+		let text = crate::decode_lossy(&input).replace(['\u{FFFD}', '\u{7F}'], "ab");
+		let valid = crate::encode_lossy(&text);
+		assert_eq!(decode(&valid), crate::decode_checked(&valid));
+		assert_eq!(decode(&valid).unwrap(), text);
+	}
+}
@@ -0,0 +1,361 @@
+//! Gaiji (custom glyph) mappings for the user-defined area.
+//!
+//! Games and mods frequently put custom glyphs in the user-defined area, lead bytes `F0`–`F9`,
+//! which this crate otherwise rejects. Some variants use the unassigned rows `EB`–`EC` instead. A
+//! [`GaijiMap`] assigns text to codes in either area, and can be loaded from a simple text format
+//! with [`load_gaiji_map`], or built from `(code, text)` pairs.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead};
+
+use crate::class::is_trail_byte;
+use crate::sink::{self, DecodeSinkError};
+use crate::trace::{Trace, DEFAULT_LEVEL};
+use crate::{decode_char_from, encode_char, EncodedChar};
+
+/// The range of lead bytes making up the user-defined area.
+pub(crate) const USER_AREA: std::ops::RangeInclusive<u8> = 0xF0..=0xF9;
+
+/// The range of lead bytes that some variants use for gaiji instead, which are unassigned in every
+/// table.
+const EXTRA_AREA: std::ops::RangeInclusive<u8> = 0xEB..=0xEC;
+
+/// A mapping from user-defined codes to text.
+///
+/// It can be collected from an iterator of `(code, text)` pairs, which panics like
+/// [`insert`](Self::insert) on invalid codes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GaijiMap {
+	decode: HashMap<[u8; 2], String>,
+	encode: HashMap<char, [u8; 2]>,
+}
+
+impl GaijiMap {
+	/// Creates an empty map.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Assigns `text` to the code, returning the previous assignment, if any.
+	///
+	/// If `text` is a single character that cannot otherwise be encoded, that character will also
+	/// be encoded to this code.
+	///
+	/// # Panics
+	/// If the code is not in the user-defined area `F040`–`F9FC`, or in `EB40`–`ECFC`.
+	pub fn insert(&mut self, code: [u8; 2], text: impl Into<String>) -> Option<String> {
+		assert!(is_gaiji_code(code), "{:02X?} is not a gaiji code", code);
+		let text = text.into();
+		self.encode.retain(|_, v| *v != code);
+		let mut chars = text.chars();
+		if let (Some(char), None) = (chars.next(), chars.next()) {
+			if encode_char(char).is_none() {
+				self.encode.entry(char).or_insert(code);
+			}
+		}
+		self.decode.insert(code, text)
+	}
+
+	/// Gets the text assigned to a code.
+	pub fn get(&self, code: [u8; 2]) -> Option<&str> {
+		self.decode.get(&code).map(String::as_str)
+	}
+
+	/// The number of assigned codes.
+	pub fn len(&self) -> usize {
+		self.decode.len()
+	}
+
+	/// Whether there are no assigned codes.
+	pub fn is_empty(&self) -> bool {
+		self.decode.is_empty()
+	}
+
+	/// Encodes a single character, falling back to the gaiji assignments.
+	pub fn encode_char(&self, char: char) -> Option<EncodedChar> {
+		encode_char(char).or_else(|| self.encode.get(&char).map(|&c| EncodedChar::Two(c)))
+	}
+
+	/// Like [`crate::encode`], but with the gaiji assignments.
+	pub fn encode(&self, str: &str) -> Result<Vec<u8>, usize> {
+		crate::sink::encode_strict(str, |char| self.encode_char(char))
+	}
+
+	/// Like [`crate::decode`], but with the gaiji assignments.
+	pub fn decode(&self, input: &[u8]) -> Result<String, (usize, EncodedChar)> {
+		let mut out = String::new();
+		let result = sink::decode_into(
+			&mut out,
+			input,
+			|b1, rest| decode_char_from(b1, || rest.next()),
+			|out, _, enc| {
+				match enc.two().and_then(|c| self.get(c)) {
+					Some(text) => out.push_str(text),
+					None => return false,
+				}
+				true
+			},
+		);
+		match result {
+			Ok(()) => Ok(out),
+			Err(DecodeSinkError::Undecodable(pos, enc)) => Err((pos, enc)),
+			Err(DecodeSinkError::Sink(e)) => match e {},
+		}
+	}
+
+	/// Like [`crate::decode_lossy`], but with the gaiji assignments.
+	pub fn decode_lossy(&self, input: &[u8]) -> String {
+		let mut out = String::new();
+		let mut trace = Trace::decode(input.len(), DEFAULT_LEVEL);
+		match sink::decode_into(
+			&mut out,
+			input,
+			|b1, rest| decode_char_from(b1, || rest.next()),
+			|out, pos, enc| {
+				match enc.two().and_then(|c| self.get(c)) {
+					Some(text) => out.push_str(text),
+					None => {
+						trace.replaced_bytes(pos, &enc, '�');
+						out.push('�');
+					}
+				}
+				true
+			},
+		) {
+			Ok(()) => out,
+			Err(DecodeSinkError::Undecodable(..)) => unreachable!(),
+			Err(DecodeSinkError::Sink(e)) => match e {},
+		}
+	}
+}
+
+fn is_user_code([b1, b2]: [u8; 2]) -> bool {
+	USER_AREA.contains(&b1) && is_trail_byte(b2)
+}
+
+fn is_gaiji_code([b1, b2]: [u8; 2]) -> bool {
+	is_user_code([b1, b2]) || EXTRA_AREA.contains(&b1) && is_trail_byte(b2)
+}
+
+impl<T: Into<String>> Extend<([u8; 2], T)> for GaijiMap {
+	fn extend<I: IntoIterator<Item = ([u8; 2], T)>>(&mut self, iter: I) {
+		for (code, text) in iter {
+			self.insert(code, text);
+		}
+	}
+}
+
+impl<T: Into<String>> FromIterator<([u8; 2], T)> for GaijiMap {
+	fn from_iter<I: IntoIterator<Item = ([u8; 2], T)>>(iter: I) -> Self {
+		let mut map = GaijiMap::new();
+		map.extend(iter);
+		map
+	}
+}
+
+/// Maps a user-defined code to the private use area, as Windows does: `F040` is U+E000, and the
+/// 1880 codes up to `F9FC` follow in order.
+pub(crate) fn user_code_to_pua(code: [u8; 2]) -> Option<char> {
+	if !is_user_code(code) {
+		return None;
+	}
+	let [b1, b2] = code;
+	let cell = b2 - 0x40 - (b2 > 0x7F) as u8;
+	char::from_u32(0xE000 + (b1 - 0xF0) as u32 * 188 + cell as u32)
+}
+
+/// The inverse of [`user_code_to_pua`].
+pub(crate) fn pua_to_user_code(char: char) -> Option<[u8; 2]> {
+	let index = (char as u32).checked_sub(0xE000).filter(|&i| i < 1880)?;
+	let (b1, cell) = ((index / 188) as u8 + 0xF0, (index % 188) as u8);
+	Some([b1, cell + 0x40 + (cell >= 0x3F) as u8])
+}
+
+/// Loads a gaiji map from a text file.
+///
+/// Each line has the form `F040 = ♪`, assigning the text after the `=` to the code before it.
+/// Surrounding whitespace is trimmed from both parts, and the text may be several characters long,
+/// as in `F041 = {note}`. Blank lines and lines starting with `#` are ignored.
+///
+/// Codes that [`GaijiMap::insert`] does not accept, as well as codes assigned more than once, are
+/// rejected.
+pub fn load_gaiji_map(reader: impl io::Read) -> Result<GaijiMap, GaijiMapError> {
+	let mut map = GaijiMap::new();
+	for (i, line) in io::BufReader::new(reader).lines().enumerate() {
+		let line = line?;
+		let error = |kind| GaijiMapError::Line { line: i + 1, kind };
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		let (code, text) = line.split_once('=').ok_or(error(GaijiLineError::Syntax))?;
+		let (code, text) = (code.trim(), text.trim());
+		if text.is_empty() {
+			return Err(error(GaijiLineError::Syntax));
+		}
+		if code.len() != 4 || !code.bytes().all(|b| b.is_ascii_hexdigit()) {
+			return Err(error(GaijiLineError::InvalidCode));
+		}
+		let code = u16::from_str_radix(code, 16).unwrap().to_be_bytes();
+		if !is_gaiji_code(code) {
+			return Err(error(GaijiLineError::OutsideUserArea(code)));
+		}
+		if map.get(code).is_some() {
+			return Err(error(GaijiLineError::Duplicate(code)));
+		}
+		map.insert(code, text);
+	}
+	Ok(map)
+}
+
+/// An error from [`load_gaiji_map`].
+#[derive(Debug)]
+pub enum GaijiMapError {
+	/// Reading the input failed.
+	Io(io::Error),
+	/// A line was invalid. Line numbers start at 1.
+	Line { line: usize, kind: GaijiLineError },
+}
+
+/// The reason a line in a gaiji map was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GaijiLineError {
+	/// The line was not of the form `code = text`.
+	Syntax,
+	/// The code was not four hex digits.
+	InvalidCode,
+	/// The code was not in the user-defined area, nor in the other gaiji area `EB40`–`ECFC`.
+	OutsideUserArea([u8; 2]),
+	/// The code was already assigned on an earlier line.
+	Duplicate([u8; 2]),
+}
+
+impl From<io::Error> for GaijiMapError {
+	fn from(e: io::Error) -> Self {
+		GaijiMapError::Io(e)
+	}
+}
+
+impl fmt::Display for GaijiMapError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			GaijiMapError::Io(e) => e.fmt(f),
+			GaijiMapError::Line { line, kind } => write!(f, "line {line}: {kind}"),
+		}
+	}
+}
+
+impl fmt::Display for GaijiLineError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			GaijiLineError::Syntax => write!(f, "expected `code = text`"),
+			GaijiLineError::InvalidCode => write!(f, "code must be four hex digits"),
+			GaijiLineError::OutsideUserArea(c) => {
+				write!(f, "{:02X?} is not a gaiji code", c)
+			}
+			GaijiLineError::Duplicate(c) => write!(f, "{:02X?} is already assigned", c),
+		}
+	}
+}
+
+impl std::error::Error for GaijiMapError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			GaijiMapError::Io(e) => Some(e),
+			GaijiMapError::Line { .. } => None,
+		}
+	}
+}
+
+#[test]
+fn load_valid() {
+	let map = load_gaiji_map(&b"# music\nF040 = \xE2\x99\xAA\n\n  F041={note}  \n"[..]).unwrap();
+	assert_eq!(map.len(), 2);
+	assert_eq!(map.get([0xF0, 0x40]), Some("♪"));
+	assert_eq!(map.get([0xF0, 0x41]), Some("{note}"));
+	assert_eq!(map.get([0xF0, 0x42]), None);
+}
+
+#[test]
+fn load_errors() {
+	fn err(input: &str) -> (usize, GaijiLineError) {
+		match load_gaiji_map(input.as_bytes()) {
+			Err(GaijiMapError::Line { line, kind }) => (line, kind),
+			other => panic!("{:?}", other),
+		}
+	}
+	assert_eq!(err("F040 = a\nF041"), (2, GaijiLineError::Syntax));
+	assert_eq!(err("F040 ="), (1, GaijiLineError::Syntax));
+	assert_eq!(err("\nF04 = a"), (2, GaijiLineError::InvalidCode));
+	assert_eq!(err("F04G = a"), (1, GaijiLineError::InvalidCode));
+	assert_eq!(err("+F04 = a"), (1, GaijiLineError::InvalidCode));
+	assert_eq!(
+		err("889F = a"),
+		(1, GaijiLineError::OutsideUserArea([0x88, 0x9F]))
+	);
+	assert_eq!(
+		err("F07F = a"),
+		(1, GaijiLineError::OutsideUserArea([0xF0, 0x7F]))
+	);
+	assert_eq!(
+		err("F040 = a\nf040 = b"),
+		(2, GaijiLineError::Duplicate([0xF0, 0x40]))
+	);
+	assert!(matches!(
+		load_gaiji_map(&b"F040 = \xFF"[..]),
+		Err(GaijiMapError::Io(_))
+	));
+}
+
+#[test]
+fn gaiji_decode_encode() {
+	let map = load_gaiji_map("F040 = ♫\nF041 = {note}\nF042 = 日".as_bytes()).unwrap();
+	let input = [0x93, 0xFA, 0xF0, 0x40, 0xF0, 0x41, 0xF0, 0x42, 0x41];
+	assert_eq!(map.decode(&input).as_deref(), Ok("日♫{note}日A"));
+	assert_eq!(map.decode_lossy(&[0xF0, 0x43, 0xF0, 0x40]), "�♫");
+	assert_eq!(
+		map.decode(&[0x41, 0xF0, 0x43]),
+		Err((1, EncodedChar::Two([0xF0, 0x43])))
+	);
+	assert_eq!(
+		map.encode("日♫A").as_deref(),
+		Ok(&[0x93, 0xFA, 0xF0, 0x40, 0x41][..])
+	);
+	assert_eq!(map.encode("♬"), Err(0));
+	assert_eq!(map.encode("{note}"), crate::encode("{note}"));
+}
+
+#[test]
+fn pua() {
+	assert_eq!(user_code_to_pua([0xF0, 0x40]), Some('\u{E000}'));
+	assert_eq!(user_code_to_pua([0xF0, 0x80]), Some('\u{E03F}'));
+	assert_eq!(user_code_to_pua([0xF1, 0x40]), Some('\u{E0BC}'));
+	assert_eq!(user_code_to_pua([0xF9, 0xFC]), Some('\u{E757}'));
+	assert_eq!(user_code_to_pua([0xF0, 0x7F]), None);
+	assert_eq!(user_code_to_pua([0xEF, 0x40]), None);
+	assert_eq!(pua_to_user_code('\u{E758}'), None);
+	for i in 0xE000..0xE758 {
+		let char = char::from_u32(i).unwrap();
+		let code = pua_to_user_code(char).unwrap();
+		assert_eq!(user_code_to_pua(code), Some(char));
+	}
+}
+
+#[test]
+fn extra_area() {
+	let map = [([0xEB, 0x40], '♬'), ([0xF0, 0x40], '♫')]
+		.into_iter()
+		.collect::<GaijiMap>();
+	let input = [0xEB, 0x40, 0x41, 0xF0, 0x40];
+	assert_eq!(map.decode(&input).as_deref(), Ok("♬A♫"));
+	assert_eq!(map.encode("♬A♫").as_deref(), Ok(&input[..]));
+	assert_eq!(
+		map.decode(&[0xEC, 0xFC]),
+		Err((0, EncodedChar::Two([0xEC, 0xFC])))
+	);
+	let map = load_gaiji_map("ECFC = {end}".as_bytes()).unwrap();
+	assert_eq!(map.decode(&[0xEC, 0xFC]).as_deref(), Ok("{end}"));
+	// The extra area is not part of the Windows private use mapping.
+	assert_eq!(user_code_to_pua([0xEB, 0x40]), None);
+}
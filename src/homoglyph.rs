@@ -0,0 +1,71 @@
+//! Characters that look alike but encode differently.
+
+use crate::encode;
+
+/// Pairs of a character and the look-alike that is usually meant in Japanese text, sorted by the
+/// first.
+///
+/// Some of the first characters cannot be encoded at all, but are easily typed by mistake, such as
+/// the wave dash `〜` for `～`. The others encode to different bytes that are rendered differently
+/// by the games' fonts, such as `~` which is an overline in JIS X 0201, or half-width punctuation.
+static HOMOGLYPHS: &[(char, char)] = &[
+	('\\', '＼'),
+	('~', '～'),
+	('¢', '￠'),
+	('£', '￡'),
+	('¬', '￢'),
+	('—', '―'),
+	('‖', '∥'),
+	('−', '－'),
+	('〜', '～'),
+	('｡', '。'),
+	('｢', '「'),
+	('｣', '」'),
+	('､', '、'),
+	('･', '・'),
+	('ｰ', 'ー'),
+];
+
+/// The look-alike of `char` that is usually meant, if it has one.
+fn canonical(char: char) -> Option<char> {
+	let i = HOMOGLYPHS.binary_search_by_key(&char, |e| e.0).ok()?;
+	Some(HOMOGLYPHS[i].1)
+}
+
+/// Encodes a string, also listing characters that have a look-alike which encodes differently.
+///
+/// Each warning is the UTF-8 offset of the character, the character itself, and the look-alike
+/// that was probably meant. The table of look-alikes is curated: it covers characters like the
+/// wave dash and minus sign that cannot be encoded, and ASCII or half-width characters that the
+/// games render differently from their full-width forms.
+#[allow(clippy::type_complexity)]
+pub fn encode_warn_homoglyphs(s: &str) -> (Result<Vec<u8>, usize>, Vec<(usize, char, char)>) {
+	let warnings = s
+		.char_indices()
+		.filter_map(|(pos, char)| Some((pos, char, canonical(char)?)))
+		.collect();
+	(encode(s), warnings)
+}
+
+#[test]
+fn table() {
+	assert!(HOMOGLYPHS.windows(2).all(|w| w[0].0 < w[1].0));
+	for &(char, canonical) in HOMOGLYPHS {
+		let enc = crate::encode_char(canonical);
+		assert!(enc.is_some_and(|e| e.len() == 2), "{canonical:?}");
+		assert_ne!(crate::encode_char(char), enc, "{char:?}");
+	}
+}
+
+#[test]
+fn warnings() {
+	let (result, warnings) = encode_warn_homoglyphs("「はい〜」~ｰ");
+	assert_eq!(result, Err(9));
+	assert_eq!(
+		warnings,
+		[(9, '〜', '～'), (15, '~', '～'), (16, 'ｰ', 'ー')]
+	);
+	let (result, warnings) = encode_warn_homoglyphs("はい～");
+	assert_eq!(result, encode("はい～"));
+	assert_eq!(warnings, []);
+}
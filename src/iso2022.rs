@@ -0,0 +1,165 @@
+//! Conversion between Shift JIS and ISO-2022-JP.
+//!
+//! ISO-2022-JP switches between character sets with escape sequences:
+//! - `ESC ( B` for ASCII, also accepting `ESC ( J` for JIS X 0201 Roman;
+//! - `ESC $ B` for JIS X 0208, also accepting `ESC $ @` for its 1978 edition;
+//! - `ESC ( I` for JIS X 0201 half-width katakana. This is not part of ISO-2022-JP proper, but is
+//!   the most common way to represent half-width katakana in it.
+//!
+//! As with EUC-JP, the two-byte characters are converted arithmetically.
+
+use crate::iter::is_lead;
+use crate::{EncodedChar, TranscodeError, TranscodeErrorKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+	Ascii,
+	Jis,
+	Katakana,
+}
+
+impl Mode {
+	fn escape(self) -> [u8; 3] {
+		match self {
+			Mode::Ascii => *b"\x1B(B",
+			Mode::Jis => *b"\x1B$B",
+			Mode::Katakana => *b"\x1B(I",
+		}
+	}
+}
+
+/// Converts Shift JIS to ISO-2022-JP.
+///
+/// Half-width katakana are written with `ESC ( I`. The output always ends in ASCII mode.
+///
+/// Invalid byte sequences are [`TranscodeErrorKind::Decode`] errors. Characters in rows past 94,
+/// and the bytes `ESC`, `SO` and `SI`, which would be misread as shifts, are
+/// [`TranscodeErrorKind::Unrepresentable`].
+pub fn sjis_to_iso2022jp(input: &[u8]) -> Result<Vec<u8>, TranscodeError> {
+	let mut out = Vec::with_capacity(input.len());
+	let mut mode = Mode::Ascii;
+	let mut pos = 0;
+	while let Some(&b1) = input.get(pos) {
+		let enc = match input.get(pos + 1) {
+			Some(&b2) if is_lead(b1) => EncodedChar::Two([b1, b2]),
+			_ => EncodedChar::One([b1]),
+		};
+		let (new_mode, bytes) = match enc {
+			EncodedChar::One([0x1B | 0x0E | 0x0F]) => {
+				let kind = TranscodeErrorKind::Unrepresentable(enc);
+				return Err(TranscodeError::at(pos, kind));
+			}
+			EncodedChar::One([b @ 0x00..=0x7F]) => (Mode::Ascii, EncodedChar::One([b])),
+			EncodedChar::One([b @ 0xA1..=0xDF]) => (Mode::Katakana, EncodedChar::One([b - 0x80])),
+			_ => match enc.kuten() {
+				Some((ku @ 1..=94, ten)) => (Mode::Jis, EncodedChar::Two([ku + 0x20, ten + 0x20])),
+				Some(_) => {
+					let kind = TranscodeErrorKind::Unrepresentable(enc);
+					return Err(TranscodeError::at(pos, kind));
+				}
+				None => return Err(TranscodeError::at(pos, TranscodeErrorKind::Decode(enc))),
+			},
+		};
+		if new_mode != mode {
+			out.extend(new_mode.escape());
+			mode = new_mode;
+		}
+		out.extend(bytes);
+		pos += enc.len();
+	}
+	if mode != Mode::Ascii {
+		out.extend(Mode::Ascii.escape());
+	}
+	Ok(out)
+}
+
+/// Converts ISO-2022-JP to Shift JIS.
+///
+/// The input starts in ASCII mode. It does not need to end in ASCII mode; a final unterminated
+/// JIS X 0208 or katakana segment is accepted.
+///
+/// Unknown escape sequences, bytes outside the range of the current mode, and a two-byte
+/// character cut off by the end of the input are [`TranscodeErrorKind::Malformed`] errors.
+pub fn iso2022jp_to_sjis(input: &[u8]) -> Result<Vec<u8>, TranscodeError> {
+	let mut out = Vec::with_capacity(input.len());
+	let mut mode = Mode::Ascii;
+	let mut pos = 0;
+	let malformed = |pos| TranscodeError::at(pos, TranscodeErrorKind::Malformed);
+	while let Some(&b1) = input.get(pos) {
+		if b1 == 0x1B {
+			mode = match input.get(pos + 1..pos + 3) {
+				Some(b"(B" | b"(J") => Mode::Ascii,
+				Some(b"$B" | b"$@") => Mode::Jis,
+				Some(b"(I") => Mode::Katakana,
+				_ => return Err(malformed(pos)),
+			};
+			pos += 3;
+			continue;
+		}
+		match mode {
+			Mode::Ascii if b1 < 0x80 && b1 != 0x0E && b1 != 0x0F => {
+				out.push(b1);
+				pos += 1;
+			}
+			Mode::Katakana if (0x21..=0x5F).contains(&b1) => {
+				out.push(b1 + 0x80);
+				pos += 1;
+			}
+			Mode::Jis => {
+				let enc = match input.get(pos..pos + 2) {
+					Some(&[ku @ 0x21..=0x7E, ten @ 0x21..=0x7E]) => {
+						EncodedChar::from_kuten(ku - 0x20, ten - 0x20).unwrap()
+					}
+					_ => return Err(malformed(pos)),
+				};
+				out.extend(enc);
+				pos += 2;
+			}
+			_ => return Err(malformed(pos)),
+		}
+	}
+	Ok(out)
+}
+
+#[test]
+fn roundtrip() {
+	let s = "Falcom「英雄伝説」\nｶﾀｶﾅ and ①, ok.";
+	let sjis = crate::encode(s).unwrap();
+	let jis = sjis_to_iso2022jp(&sjis).unwrap();
+	assert_eq!(
+		jis,
+		b"Falcom\x1B$B!V1QM:EA@b!W\x1B(B\n\x1B(I6@6E\x1B(B and \x1B$B-!\x1B(B, ok."
+	);
+	assert_eq!(iso2022jp_to_sjis(&jis).unwrap(), sjis);
+	assert_eq!(sjis_to_iso2022jp(b"").unwrap(), b"");
+	assert_eq!(sjis_to_iso2022jp(b"abc").unwrap(), b"abc");
+}
+
+#[test]
+fn segments_at_ends() {
+	let sjis = crate::encode("日本語").unwrap();
+	let jis = sjis_to_iso2022jp(&sjis).unwrap();
+	assert_eq!(jis, b"\x1B$BF|K\\8l\x1B(B");
+	assert_eq!(iso2022jp_to_sjis(&jis).unwrap(), sjis);
+	assert_eq!(iso2022jp_to_sjis(&jis[..jis.len() - 3]).unwrap(), sjis);
+	assert_eq!(iso2022jp_to_sjis(b"\x1B$@F|\x1B(JA").unwrap(), b"\x93\xFAA");
+	assert_eq!(iso2022jp_to_sjis(b"\x1B(I6").unwrap(), b"\xB6");
+}
+
+#[test]
+fn errors() {
+	let err = |r: Result<Vec<u8>, TranscodeError>| r.unwrap_err().position;
+	assert_eq!(err(iso2022jp_to_sjis(b"ab\x1B$Cxx")), 2);
+	assert_eq!(err(iso2022jp_to_sjis(b"ab\x1B$")), 2);
+	assert_eq!(err(iso2022jp_to_sjis(b"\x1B$BF|K")), 5);
+	assert_eq!(err(iso2022jp_to_sjis(b"\x1B$BF|\n")), 5);
+	assert_eq!(err(iso2022jp_to_sjis(b"a\xB6")), 1);
+	assert_eq!(err(iso2022jp_to_sjis(b"\x1B(Ia")), 3);
+	assert_eq!(err(sjis_to_iso2022jp(b"a\x1B")), 1);
+	assert_eq!(err(sjis_to_iso2022jp(b"ab\x81\x7F")), 2);
+	let e = sjis_to_iso2022jp(b"\xF0\x40").unwrap_err();
+	assert!(matches!(
+		e.kind,
+		TranscodeErrorKind::Unrepresentable(EncodedChar::Two([0xF0, 0x40]))
+	));
+}
@@ -0,0 +1,418 @@
+//! Iterators over decoded characters and encoded bytes.
+
+use std::io;
+use std::ops::Range;
+use std::str::CharIndices;
+
+use crate::{
+	decode_char, decode_char_from, encode_char, DecodeIterError, EncodeError, EncodedChar,
+	TranscodeError, TranscodeErrorKind,
+};
+
+/// Whether a byte always starts a two-byte sequence when at the start of a character.
+pub(crate) fn is_lead(b: u8) -> bool {
+	crate::class::LEAD[b as usize].is_double()
+}
+
+/// An iterator over the characters in a byte slice, created by [`decode_units`].
+#[derive(Debug, Clone)]
+pub struct DecodeUnits<'a> {
+	input: &'a [u8],
+	front: usize,
+	back: usize,
+}
+
+/// Iterates over the characters in a byte slice, along with their byte ranges.
+///
+/// Each item is the result of [`decode_char_from`] at that position. The iterator can also be
+/// iterated from the back, yielding the same items as from the front.
+pub fn decode_units(input: &[u8]) -> DecodeUnits<'_> {
+	DecodeUnits {
+		input,
+		front: 0,
+		back: input.len(),
+	}
+}
+
+impl DecodeUnits<'_> {
+	fn decode(&self, range: Range<usize>) -> (Range<usize>, Result<char, EncodedChar>) {
+		let b2 = self.input.get(range.start + 1).filter(|_| range.len() == 2);
+		let result = decode_char_from(self.input[range.start], || b2.copied());
+		(range, result)
+	}
+}
+
+impl Iterator for DecodeUnits<'_> {
+	type Item = (Range<usize>, Result<char, EncodedChar>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.front == self.back {
+			return None;
+		}
+		let start = self.front;
+		let len = if is_lead(self.input[start]) { 2 } else { 1 };
+		self.front = (start + len).min(self.back);
+		Some(self.decode(start..self.front))
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let len = self.back - self.front;
+		(len.div_ceil(2), Some(len))
+	}
+}
+
+impl DoubleEndedIterator for DecodeUnits<'_> {
+	fn next_back(&mut self) -> Option<Self::Item> {
+		if self.front == self.back {
+			return None;
+		}
+		let end = self.back;
+		// A byte that is not a lead byte always ends a character, so the characters after the last
+		// such byte are all two bytes long, except possibly a truncated one at the very end.
+		let run = self.input[self.front..end - 1]
+			.iter()
+			.rev()
+			.take_while(|&&b| is_lead(b))
+			.count();
+		self.back = if run % 2 == 1 { end - 2 } else { end - 1 };
+		Some(self.decode(self.back..end))
+	}
+}
+
+impl std::iter::FusedIterator for DecodeUnits<'_> {}
+
+/// Decodes bytes from any iterator, such as a chain of slices or a drained `VecDeque`.
+///
+/// This gives the same result as [`crate::decode`] would on the bytes collected into a slice,
+/// without the copy. For readers, see [`decode_io_bytes`].
+pub fn decode_iter<I: IntoIterator<Item = u8>>(iter: I) -> Result<String, DecodeIterError> {
+	let mut consumed = 0;
+	let mut iter = iter.into_iter().inspect(|_| consumed += 1);
+	let mut out = String::with_capacity(iter.size_hint().0);
+	while let Some(result) = decode_char(&mut iter) {
+		match result {
+			Ok(char) => out.push(char),
+			Err(bytes) => {
+				drop(iter);
+				let consumed = consumed - bytes.len();
+				return Err(DecodeIterError { consumed, bytes });
+			}
+		}
+	}
+	Ok(out)
+}
+
+/// Decodes bytes from any iterator lossily, as per [`crate::decode_lossy`].
+pub fn decode_iter_lossy<I: IntoIterator<Item = u8>>(iter: I) -> String {
+	let mut iter = iter.into_iter();
+	let mut out = String::with_capacity(iter.size_hint().0);
+	while let Some(result) = decode_char(&mut iter) {
+		out.push(result.unwrap_or('�'));
+	}
+	out
+}
+
+/// Decodes bytes from an iterator of I/O results, such as [`Read::bytes`](io::Read::bytes).
+///
+/// This is like [`decode_iter`], but stops at the first I/O error, whose position is the number of
+/// bytes read before it. Since `Read::bytes` reads one byte at a time, the reader should usually be
+/// buffered.
+///
+/// ```
+/// use std::io::{BufReader, Read};
+///
+/// let file = BufReader::new(&b"\x93\xFA\x96\x7B"[..]);
+/// assert_eq!(falcom_sjis::decode_io_bytes(file.bytes()).unwrap(), "日本");
+/// ```
+pub fn decode_io_bytes<I: IntoIterator<Item = io::Result<u8>>>(
+	iter: I,
+) -> Result<String, TranscodeError> {
+	let mut iter = iter.into_iter();
+	let mut out = String::new();
+	let mut consumed = 0;
+	let mut error = None;
+	loop {
+		let b1 = match iter.next() {
+			None => return Ok(out),
+			Some(Ok(b1)) => b1,
+			Some(Err(e)) => return Err(TranscodeError::at(consumed, TranscodeErrorKind::Io(e))),
+		};
+		consumed += 1;
+		let result = decode_char_from(b1, || match iter.next()? {
+			Ok(b2) => {
+				consumed += 1;
+				Some(b2)
+			}
+			Err(e) => {
+				error = Some(e);
+				None
+			}
+		});
+		if let Some(e) = error {
+			return Err(TranscodeError::at(consumed, TranscodeErrorKind::Io(e)));
+		}
+		match result {
+			Ok(char) => out.push(char),
+			Err(enc) => {
+				let position = consumed - enc.len();
+				return Err(TranscodeError::at(
+					position,
+					TranscodeErrorKind::Decode(enc),
+				));
+			}
+		}
+	}
+}
+
+/// An iterator over the encoded bytes of a string, created by [`encode_bytes`].
+#[derive(Debug, Clone)]
+pub struct EncodeBytes<'a> {
+	chars: CharIndices<'a>,
+	trail: Option<u8>,
+	failed: bool,
+}
+
+/// Encodes a string lazily, one byte at a time.
+///
+/// The bytes are the same as from [`crate::encode`]. A character that cannot be encoded yields an
+/// error, after which the iterator ends.
+pub fn encode_bytes(s: &str) -> EncodeBytes<'_> {
+	EncodeBytes {
+		chars: s.char_indices(),
+		trail: None,
+		failed: false,
+	}
+}
+
+impl Iterator for EncodeBytes<'_> {
+	type Item = Result<u8, EncodeError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if let Some(b) = self.trail.take() {
+			return Some(Ok(b));
+		}
+		if self.failed {
+			return None;
+		}
+		let (position, char) = self.chars.next()?;
+		match encode_char(char) {
+			Some(EncodedChar::One([b])) => Some(Ok(b)),
+			Some(EncodedChar::Two([b1, b2])) => {
+				self.trail = Some(b2);
+				Some(Ok(b1))
+			}
+			None => {
+				self.failed = true;
+				Some(Err(EncodeError { position, char }))
+			}
+		}
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let trail = self.trail.is_some() as usize;
+		if self.failed {
+			return (trail, Some(trail));
+		}
+		let (lower, upper) = self.chars.size_hint();
+		(trail + lower.min(1), upper.map(|n| trail + 2 * n))
+	}
+}
+
+impl std::iter::FusedIterator for EncodeBytes<'_> {}
+
+/// An iterator over the encoded bytes of a string, created by [`encode_bytes_lossy`].
+#[derive(Debug, Clone)]
+pub struct EncodeBytesLossy<'a> {
+	chars: std::str::Chars<'a>,
+	trail: Option<u8>,
+}
+
+/// Encodes a string lazily and lossily, one byte at a time.
+///
+/// The bytes are the same as from [`crate::encode_lossy`].
+pub fn encode_bytes_lossy(s: &str) -> EncodeBytesLossy<'_> {
+	EncodeBytesLossy {
+		chars: s.chars(),
+		trail: None,
+	}
+}
+
+impl Iterator for EncodeBytesLossy<'_> {
+	type Item = u8;
+
+	fn next(&mut self) -> Option<u8> {
+		if let Some(b) = self.trail.take() {
+			return Some(b);
+		}
+		let char = self.chars.next()?;
+		match encode_char(char).unwrap_or(EncodedChar::REPLACEMENT) {
+			EncodedChar::One([b]) => Some(b),
+			EncodedChar::Two([b1, b2]) => {
+				self.trail = Some(b2);
+				Some(b1)
+			}
+		}
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let trail = self.trail.is_some() as usize;
+		let (lower, upper) = self.chars.size_hint();
+		(trail + lower, upper.map(|n| trail + 2 * n))
+	}
+}
+
+impl std::iter::FusedIterator for EncodeBytesLossy<'_> {}
+
+#[test]
+fn iter_matches_slice() {
+	let mut state = 1u32;
+	for len in 0..300 {
+		let input = (0..len)
+			.map(|_| {
+				state = state.wrapping_mul(1103515245).wrapping_add(12345);
+				(state >> 16) as u8
+			})
+			.collect::<Vec<_>>();
+		let expected = crate::decode(&input);
+		let result = decode_iter(input.iter().copied());
+		assert_eq!(result.map_err(|e| (e.consumed, e.bytes)), expected);
+		assert_eq!(
+			decode_iter_lossy(input.iter().copied()),
+			crate::decode_lossy(&input)
+		);
+		let result = decode_io_bytes(input.iter().map(|&b| Ok(b)));
+		match (result, expected) {
+			(Ok(a), Ok(b)) => assert_eq!(a, b),
+			(Err(e), Err((pos, bytes))) => {
+				assert_eq!(e.position, pos as u64);
+				assert!(matches!(e.kind, TranscodeErrorKind::Decode(b) if b == bytes));
+			}
+			(a, b) => panic!("{a:?} vs {b:?}"),
+		}
+	}
+}
+
+#[test]
+fn iter_sources() {
+	let (a, b) = (&b"\x93"[..], &b"\xFA\x96\x7B"[..]);
+	assert_eq!(
+		decode_iter(a.iter().chain(b).copied()).as_deref(),
+		Ok("日本")
+	);
+	let mut deque = std::collections::VecDeque::from(b"A\x80".to_vec());
+	assert_eq!(
+		decode_iter(deque.drain(..)),
+		Err(DecodeIterError {
+			consumed: 1,
+			bytes: EncodedChar::One([0x80])
+		})
+	);
+
+	// An I/O error in the middle of a character is reported after the bytes read so far.
+	let input = [Ok(b'a'), Ok(0x93), Err(io::Error::other("broken"))];
+	let e = decode_io_bytes(input).unwrap_err();
+	assert!(e.is_io());
+	assert_eq!(e.position, 2);
+}
+
+#[test]
+fn units_forward() {
+	let units = decode_units(b"\x93\xFAA\x81\x20\x80\x93").collect::<Vec<_>>();
+	assert_eq!(
+		units,
+		[
+			(0..2, Ok('日')),
+			(2..3, Ok('A')),
+			(3..5, Err(EncodedChar::Two([0x81, 0x20]))),
+			(5..6, Err(EncodedChar::One([0x80]))),
+			(6..7, Err(EncodedChar::One([0x93]))),
+		],
+	);
+	assert_eq!(
+		decode_units(b"\x93\xFAA").next_back(),
+		Some((2..3, Ok('A')))
+	);
+}
+
+#[test]
+fn units_backward() {
+	let bytes = [
+		0x00, 0x41, 0x40, 0x80, 0x81, 0x9F, 0xA0, 0xB1, 0xE0, 0xF0, 0xFC, 0xFF,
+	];
+	let mut buf = Vec::new();
+	for n in 0..=5u32 {
+		for mut i in 0..bytes.len().pow(n) {
+			buf.clear();
+			for _ in 0..n {
+				buf.push(bytes[i % bytes.len()]);
+				i /= bytes.len();
+			}
+			let fwd = decode_units(&buf).collect::<Vec<_>>();
+			let mut bwd = decode_units(&buf).rev().collect::<Vec<_>>();
+			bwd.reverse();
+			assert_eq!(fwd, bwd, "{:02X?}", buf);
+
+			let mut it = decode_units(&buf);
+			let mut mixed = Vec::new();
+			let mut tail = Vec::new();
+			while let Some(u) = it.next() {
+				mixed.push(u);
+				tail.extend(it.next_back());
+			}
+			mixed.extend(tail.into_iter().rev());
+			assert_eq!(fwd, mixed, "{:02X?}", buf);
+		}
+	}
+}
+
+#[test]
+fn encode_bytes_matches_encode() {
+	for s in [
+		"",
+		"abc",
+		"日本ｱ語",
+		"a₂b",
+		"₂",
+		"日₂本",
+		"英雄伝説\n空の軌跡",
+	] {
+		let strict = encode_bytes(s).collect::<Result<Vec<_>, _>>();
+		assert_eq!(
+			strict,
+			crate::encode(s).map_err(|pos| EncodeError::at(s, pos))
+		);
+		assert_eq!(
+			encode_bytes_lossy(s).collect::<Vec<_>>(),
+			crate::encode_lossy(s)
+		);
+
+		// The size hints hold at every step, including for a clone partway through.
+		let mut iter = encode_bytes_lossy(s);
+		let mut strict = encode_bytes(s);
+		loop {
+			let rest = iter.clone().count();
+			let (lower, upper) = iter.size_hint();
+			assert!(lower <= rest && rest <= upper.unwrap(), "{s:?}");
+			let rest = strict.clone().count();
+			let (lower, upper) = strict.size_hint();
+			assert!(lower <= rest && rest <= upper.unwrap(), "{s:?}");
+			strict.next();
+			if iter.next().is_none() {
+				break;
+			}
+		}
+	}
+
+	let mut iter = encode_bytes("日₂本");
+	assert_eq!(iter.next(), Some(Ok(0x93)));
+	assert_eq!(iter.next(), Some(Ok(0xFA)));
+	assert_eq!(
+		iter.next(),
+		Some(Err(EncodeError {
+			position: 3,
+			char: '₂'
+		}))
+	);
+	assert_eq!(iter.next(), None);
+	assert_eq!(iter.next(), None);
+}
@@ -0,0 +1,311 @@
+//! Classification of characters by JIS level.
+
+use crate::{decode_char_from, encode_char, EncodedChar};
+
+/// The part of the character set a character belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum JisClass {
+	/// A single-byte ASCII character.
+	Ascii,
+	/// A single-byte half-width katakana.
+	HalfwidthKatakana,
+	/// Rows 1–8 of JIS X 0208: symbols, kana, and the Latin, Greek and Cyrillic alphabets.
+	NonKanji,
+	/// Rows 16–47 of JIS X 0208.
+	Level1Kanji,
+	/// Rows 48–84 of JIS X 0208.
+	Level2Kanji,
+	/// Any other row, including NEC and IBM extensions and the user-defined area.
+	Extension,
+}
+
+/// Classifies a character, or returns `None` if it cannot be encoded.
+pub fn classify(c: char) -> Option<JisClass> {
+	classify_encoded(encode_char(c)?)
+}
+
+/// Classifies an encoded character.
+///
+/// Two-byte characters are classified by their row as per [`EncodedChar::kuten`], without checking
+/// whether the cell is assigned. Returns `None` for invalid byte sequences.
+pub fn classify_encoded(e: EncodedChar) -> Option<JisClass> {
+	match e {
+		EncodedChar::One([0x00..=0x7F]) => Some(JisClass::Ascii),
+		EncodedChar::One([0xA1..=0xDF]) => Some(JisClass::HalfwidthKatakana),
+		EncodedChar::One(_) => None,
+		EncodedChar::Two(_) => match e.kuten()?.0 {
+			1..=8 => Some(JisClass::NonKanji),
+			16..=47 => Some(JisClass::Level1Kanji),
+			48..=84 => Some(JisClass::Level2Kanji),
+			_ => Some(JisClass::Extension),
+		},
+	}
+}
+
+/// Classifies each character in a string.
+pub fn classify_str(s: &str) -> impl Iterator<Item = (char, Option<JisClass>)> + '_ {
+	s.chars().map(|c| (c, classify(c)))
+}
+
+/// Converts a JIS row and cell (*ku* and *ten*) to Shift JIS.
+///
+/// This is pure arithmetic, and does not check whether the cell is assigned. Rows 95–120 give the
+/// lead bytes `F0`–`FC`, as per [`EncodedChar::kuten`]. For a 7-bit JIS code such as `0x2422`, the
+/// row and cell are the high and low bytes minus `0x20`.
+pub fn jis_to_sjis(ku: u8, ten: u8) -> Option<EncodedChar> {
+	EncodedChar::from_kuten(ku, ten)
+}
+
+/// Converts Shift JIS to a JIS row and cell. This is the same as [`EncodedChar::kuten`].
+pub fn sjis_to_jis(enc: EncodedChar) -> Option<(u8, u8)> {
+	enc.kuten()
+}
+
+/// A set of allowed JIS rows, for restricting encoding to what a font supports.
+///
+/// Characters in other rows are treated as unencodable. Valid single-byte characters are always
+/// allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RowFilter {
+	rows: u128,
+}
+
+impl RowFilter {
+	/// Allows the given rows, numbered from 1 as per [`EncodedChar::kuten`].
+	pub fn from_rows(rows: impl IntoIterator<Item = u8>) -> Self {
+		let mut filter = RowFilter { rows: 0 };
+		for row in rows {
+			filter.rows |= 1u128.checked_shl(row as u32).unwrap_or(0);
+		}
+		filter
+	}
+
+	/// Allows only the non-kanji rows and level 1 kanji.
+	pub fn level1_only() -> Self {
+		Self::from_rows((1..=8).chain(16..=47))
+	}
+
+	/// Whether an encoded character is in an allowed row.
+	pub fn allows(&self, e: EncodedChar) -> bool {
+		match e.kuten() {
+			Some((row, _)) => self.rows & 1u128.checked_shl(row as u32).unwrap_or(0) != 0,
+			None => classify_encoded(e).is_some(),
+		}
+	}
+
+	/// Like [`crate::encode_char`], but returns `None` for disallowed characters.
+	pub fn encode_char(&self, char: char) -> Option<EncodedChar> {
+		encode_char(char).filter(|&e| self.allows(e))
+	}
+
+	/// Like [`crate::encode`], but disallowed characters are errors.
+	pub fn encode(&self, str: &str) -> Result<Vec<u8>, usize> {
+		crate::sink::encode_strict(str, |char| self.encode_char(char))
+	}
+
+	/// Like [`crate::encode_lossy`], but disallowed characters are replaced.
+	pub fn encode_lossy(&self, str: &str) -> Vec<u8> {
+		crate::sink::encode_replacing(str, |char| self.encode_char(char))
+	}
+}
+
+/// A vendor extension area, which fonts frequently lack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ExtensionArea {
+	/// Row 13, NEC special characters such as `①` and `㈱`.
+	NecRow13,
+	/// Rows 89–92, the NEC-selected IBM extensions.
+	NecSelectedIbm,
+	/// Lead bytes `FA`–`FC`, the IBM extensions.
+	IbmExtension,
+}
+
+impl ExtensionArea {
+	/// Gets the extension area an encoded character is in, if any.
+	pub fn of(e: EncodedChar) -> Option<Self> {
+		match e.kuten()?.0 {
+			13 => Some(ExtensionArea::NecRow13),
+			89..=92 => Some(ExtensionArea::NecSelectedIbm),
+			115..=120 => Some(ExtensionArea::IbmExtension),
+			_ => None,
+		}
+	}
+}
+
+/// A character found by [`extension_usage`] or [`extension_usage_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExtensionUse {
+	/// The character.
+	pub char: char,
+	/// The offset of the character in the input, in bytes.
+	pub position: usize,
+	/// The area the character's encoding is in.
+	pub area: ExtensionArea,
+}
+
+/// Finds the characters in a string that would be encoded in a vendor extension area.
+///
+/// Positions are UTF-8 offsets. Characters that cannot be encoded are ignored.
+pub fn extension_usage(s: &str) -> Vec<ExtensionUse> {
+	let mut out = Vec::new();
+	for (position, char) in s.char_indices() {
+		if let Some(area) = encode_char(char).and_then(ExtensionArea::of) {
+			out.push(ExtensionUse {
+				char,
+				position,
+				area,
+			});
+		}
+	}
+	out
+}
+
+/// Finds the characters in already-encoded data that are in a vendor extension area.
+///
+/// Positions are offsets into the input. Invalid byte sequences are ignored.
+pub fn extension_usage_bytes(input: &[u8]) -> Vec<ExtensionUse> {
+	let mut out = Vec::new();
+	let mut iter = input.iter().copied();
+	while let Some(b1) = iter.next() {
+		let position = input.len() - iter.len() - 1;
+		let mut b2 = None;
+		if let Ok(char) = decode_char_from(b1, || {
+			b2 = iter.next();
+			b2
+		}) {
+			let enc = match b2 {
+				Some(b2) => EncodedChar::Two([b1, b2]),
+				None => EncodedChar::One([b1]),
+			};
+			if let Some(area) = ExtensionArea::of(enc) {
+				out.push(ExtensionUse {
+					char,
+					position,
+					area,
+				});
+			}
+		}
+	}
+	out
+}
+
+#[test]
+fn test_classify() {
+	assert_eq!(classify('A'), Some(JisClass::Ascii));
+	assert_eq!(classify('ｱ'), Some(JisClass::HalfwidthKatakana));
+	assert_eq!(classify('あ'), Some(JisClass::NonKanji));
+	assert_eq!(classify('亜'), Some(JisClass::Level1Kanji));
+	assert_eq!(classify('腕'), Some(JisClass::Level1Kanji));
+	assert_eq!(classify('弌'), Some(JisClass::Level2Kanji));
+	assert_eq!(classify('①'), Some(JisClass::Extension));
+	assert_eq!(classify('₂'), None);
+	assert_eq!(encode_char('腕'), Some(EncodedChar::Two([0x98, 0x72])));
+	assert_eq!(encode_char('弌'), Some(EncodedChar::Two([0x98, 0x9F])));
+	assert_eq!(
+		classify_encoded(EncodedChar::Two([0xF0, 0x40])),
+		Some(JisClass::Extension)
+	);
+	assert_eq!(classify_encoded(EncodedChar::One([0x80])), None);
+	assert_eq!(classify_encoded(EncodedChar::Two([0x81, 0x7F])), None);
+	assert_eq!(
+		classify_str("a腕₂").collect::<Vec<_>>(),
+		[
+			('a', Some(JisClass::Ascii)),
+			('腕', Some(JisClass::Level1Kanji)),
+			('₂', None)
+		],
+	);
+}
+
+#[test]
+fn row_filter() {
+	let filter = RowFilter::level1_only();
+	assert_eq!(
+		crate::encode("亜弌").as_deref(),
+		Ok(&[0x88, 0x9F, 0x98, 0x9F][..])
+	);
+	assert_eq!(filter.encode("亜弌"), Err(3));
+	assert_eq!(filter.encode("ｱ1①"), Err(4));
+	assert_eq!(crate::decode_lossy(&filter.encode_lossy("亜弌①")), "亜・・");
+
+	let map = crate::load_gaiji_map("F040 = ♫".as_bytes()).unwrap();
+	assert_eq!(map.encode_char('♫').filter(|&e| filter.allows(e)), None);
+	let filter = RowFilter::from_rows([1, 95]);
+	assert_eq!(
+		map.encode_char('♫').filter(|&e| filter.allows(e)),
+		Some(EncodedChar::Two([0xF0, 0x40]))
+	);
+	assert!(filter.allows(EncodedChar::REPLACEMENT));
+	assert!(!filter.allows(EncodedChar::One([0x80])));
+	assert!(!RowFilter::from_rows([200]).allows(EncodedChar::REPLACEMENT));
+}
+
+#[test]
+fn test_extension_usage() {
+	use ExtensionArea::*;
+	assert_eq!(extension_usage("亜あ日本A"), []);
+	assert_eq!(
+		extension_usage("①a髙ⅰ"),
+		[
+			ExtensionUse {
+				char: '①',
+				position: 0,
+				area: NecRow13
+			},
+			ExtensionUse {
+				char: '髙',
+				position: 4,
+				area: NecSelectedIbm
+			},
+			ExtensionUse {
+				char: 'ⅰ',
+				position: 7,
+				area: NecSelectedIbm
+			},
+		],
+	);
+	let enc = crate::VendorPreference::Ibm.encode("①a髙").unwrap();
+	assert_eq!(
+		extension_usage_bytes(&enc),
+		[
+			ExtensionUse {
+				char: '①',
+				position: 0,
+				area: NecRow13
+			},
+			ExtensionUse {
+				char: '髙',
+				position: 3,
+				area: IbmExtension
+			},
+		],
+	);
+	let enc = crate::VendorPreference::Nec.encode("①a髙").unwrap();
+	assert_eq!(extension_usage_bytes(&enc)[1].area, NecSelectedIbm);
+	assert_eq!(
+		extension_usage_bytes(&[0x87, 0x20, 0x87, 0x40]),
+		[ExtensionUse {
+			char: '①',
+			position: 2,
+			area: NecRow13
+		}]
+	);
+}
+
+#[test]
+fn jis_conversion() {
+	assert_eq!(jis_to_sjis(0x24 - 0x20, 0x22 - 0x20), encode_char('あ'));
+	assert_eq!(jis_to_sjis(16, 1), Some(EncodedChar::Two([0x88, 0x9F])));
+	assert_eq!(jis_to_sjis(84, 6), encode_char('熙'));
+	assert_eq!(jis_to_sjis(0, 1), None);
+	assert_eq!(jis_to_sjis(1, 0), None);
+	assert_eq!(jis_to_sjis(1, 95), None);
+	assert_eq!(sjis_to_jis(encode_char('亜').unwrap()), Some((16, 1)));
+	assert_eq!(sjis_to_jis(EncodedChar::One([0x41])), None);
+	for ku in 1..=120 {
+		for ten in 1..=94 {
+			let enc = jis_to_sjis(ku, ten).unwrap();
+			assert_eq!(sjis_to_jis(enc), Some((ku, ten)));
+		}
+	}
+}
@@ -1,7 +1,119 @@
+#![cfg_attr(not(feature = "unsafe-fast"), forbid(unsafe_code))]
+
+use std::borrow::Cow;
 use std::cell::Cell;
 
-static UTF8_SJIS: phf::Map<char, [u8; 2]> = include!(concat!(env!("OUT_DIR"), "/utf8sjis.rs"));
-static SJIS_UTF8: [[char; 188]; 47] = include!(concat!(env!("OUT_DIR"), "/sjisutf8.rs"));
+use class::{Lead, KANA, LEAD, TRAIL};
+use sink::DecodeSinkError;
+
+mod analyze;
+mod auto;
+mod buffer;
+#[cfg(feature = "decode-cache")]
+mod cache;
+mod channel;
+mod charset;
+mod class;
+mod codec;
+mod context;
+mod cp932;
+mod custom;
+#[cfg(feature = "flate2")]
+mod deflate;
+mod detect;
+mod diff;
+mod error;
+mod escape;
+mod euc;
+#[cfg(feature = "unsafe-fast")]
+mod fast;
+mod gaiji;
+mod homoglyph;
+mod iso2022;
+mod iter;
+mod jis;
+mod limits;
+mod markup;
+mod os;
+mod record;
+mod segment;
+mod sink;
+mod sjis_table;
+mod string;
+mod tables;
+mod trace;
+mod transcode;
+mod utf16;
+mod vendor;
+mod whatwg;
+mod width;
+mod xml;
+pub use analyze::{analyze, has_ascii_hazard, Analysis, Count};
+pub use auto::{decode_auto, decode_auto_strict};
+pub use buffer::SjisBuffer;
+#[cfg(feature = "decode-cache")]
+pub use cache::{decode_cached, DECODE_CACHE_SIZE};
+pub use channel::decode_to_channel;
+pub use charset::{collect_charset, collect_charset_bytes, SjisCharset, Whitelist, WhitelistError};
+pub use codec::{Codec, CodecBuilder, Gaiji, LineEnding, Nbsp, Spec};
+pub use context::format_error_context;
+pub use cp932::{cp932_differences, decodes_like_cp932, Difference};
+pub use custom::{Decoder, Encoder};
+#[cfg(feature = "flate2")]
+pub use deflate::decode_deflated;
+pub use detect::{detect, is_probably_sjis, sjis_confidence, Detection, Encoding};
+pub use diff::{diff, DiffHunk};
+pub use error::{DecodeError, DecodeIterError, EncodeError, Error, TooManyErrors};
+pub use escape::{
+	decode_git_friendly, encode_escape_unicode, encode_git_friendly, unescape_unicode,
+};
+pub use euc::{euc_jp_to_sjis, sjis_to_euc_jp};
+pub use gaiji::{load_gaiji_map, GaijiLineError, GaijiMap, GaijiMapError};
+pub use homoglyph::encode_warn_homoglyphs;
+pub use iso2022::{iso2022jp_to_sjis, sjis_to_iso2022jp};
+pub use iter::{
+	decode_io_bytes, decode_iter, decode_iter_lossy, decode_units, encode_bytes,
+	encode_bytes_lossy, DecodeUnits, EncodeBytes, EncodeBytesLossy,
+};
+pub use jis::{
+	classify, classify_encoded, classify_str, extension_usage, extension_usage_bytes, jis_to_sjis,
+	sjis_to_jis, ExtensionArea, ExtensionUse, JisClass, RowFilter,
+};
+pub use limits::{check_limits, LimitKind, LimitViolation, TextLimits};
+pub use markup::{
+	decode_with_controls, escape_controls, unescape_controls, ControlTable, TextToken,
+	UnescapeError, UnescapeErrorKind,
+};
+pub use os::{decode_os_string, decode_os_string_lossy, encode_os_str, encode_os_str_lossy};
+pub use record::{
+	decode_prefixed, decode_string_table, decode_string_table_lossy, encode_fixed,
+	encode_fixed_truncate, encode_prefixed, encode_string_table, read_length_prefixed,
+	write_length_prefixed, FixedError, PrefixWidth, PrefixedError, StringTableError, Unterminated,
+};
+pub use segment::{
+	decode_split, encode_join, extract_strings, segments, segments_with, split_sentences,
+	split_sentences_str, split_sentences_str_with, split_sentences_with, Segment,
+};
+pub use sink::{encode_partial, encode_partial_to, encode_to, encode_to_slice, encode_to_writer};
+pub use sjis_table::{
+	verify_table, verify_tables, BuiltinTable, SjisTable, TableInconsistency, TABLE_VERSION,
+};
+pub use string::{SjisStr, SjisString};
+pub use transcode::{
+	transcode_sjis_to_utf8, transcode_utf8_to_sjis, TranscodeError, TranscodeErrorKind,
+	TranscodeStats,
+};
+pub use utf16::{
+	decode_to_utf16, decode_to_utf16be, decode_to_utf16be_into, decode_to_utf16le,
+	decode_to_utf16le_into, encode_utf16, encode_utf16_lossy, EncodeUtf16Error,
+};
+pub use vendor::VendorPreference;
+pub use width::{
+	decode_halfwidth_ascii, display_width, encode_fullwidth, encode_fullwidth_with,
+	fold_fullwidth_ascii, to_fullwidth, to_fullwidth_with, to_halfwidth_ascii,
+	to_halfwidth_ascii_with, wrap_columns,
+};
+pub use xml::{decode_xml_entities, encode_xml, encode_xml_with, NcrRadix, XmlEscape, XmlOptions};
 
 /// An encoded character in Shift JIS encoding.
 ///
@@ -15,6 +127,95 @@ pub enum EncodedChar {
 impl EncodedChar {
 	/// The replacement character used with [`encode_lossy`], namely `・`.
 	pub const REPLACEMENT: EncodedChar = EncodedChar::Two([0x81, 0x45]);
+
+	/// The encoded bytes, one or two of them.
+	pub fn as_bytes(&self) -> &[u8] {
+		self
+	}
+
+	/// The bytes padded to two with zeros, and the number of valid bytes.
+	///
+	/// This is useful for storing the bytes inline, to be sliced as `&bytes[..len]` later.
+	pub fn to_inline(&self) -> ([u8; 2], usize) {
+		match *self {
+			EncodedChar::One([a]) => ([a, 0], 1),
+			EncodedChar::Two(a) => (a, 2),
+		}
+	}
+
+	/// The byte, if this is a single-byte character.
+	pub fn one(&self) -> Option<u8> {
+		match *self {
+			EncodedChar::One([a]) => Some(a),
+			EncodedChar::Two(_) => None,
+		}
+	}
+
+	/// The bytes, if this is a two-byte character.
+	pub fn two(&self) -> Option<[u8; 2]> {
+		match *self {
+			EncodedChar::One(_) => None,
+			EncodedChar::Two(a) => Some(a),
+		}
+	}
+
+	/// The JIS row and cell (*ku* and *ten*) of a two-byte character, both starting at 1.
+	///
+	/// Lead bytes past `EF` give rows past 94, such as the user-defined area. Returns `None` for
+	/// single-byte characters and invalid byte sequences.
+	pub fn kuten(&self) -> Option<(u8, u8)> {
+		let [b1, b2] = self.two()?;
+		let row = match b1 {
+			0x81..=0x9F => b1 - 0x81,
+			0xE0..=0xFC => b1 - 0xC1,
+			_ => return None,
+		};
+		match b2 {
+			0x40..=0x7E => Some((row * 2 + 1, b2 - 0x3F)),
+			0x80..=0x9E => Some((row * 2 + 1, b2 - 0x40)),
+			0x9F..=0xFC => Some((row * 2 + 2, b2 - 0x9E)),
+			_ => None,
+		}
+	}
+
+	/// The inverse of [`kuten`](Self::kuten), for rows 1–120 and cells 1–94.
+	pub(crate) fn from_kuten(ku: u8, ten: u8) -> Option<EncodedChar> {
+		if !(1..=120).contains(&ku) || !(1..=94).contains(&ten) {
+			return None;
+		}
+		let b1 = ku.div_ceil(2) + if ku <= 62 { 0x80 } else { 0xC0 };
+		let b2 = match (ku % 2, ten) {
+			(1, ..=63) => ten + 0x3F,
+			(1, _) => ten + 0x40,
+			_ => ten + 0x9E,
+		};
+		Some(EncodedChar::Two([b1, b2]))
+	}
+
+	/// The character this decodes to, if it is a single valid character with no extra bytes.
+	pub(crate) fn to_char(self) -> Option<char> {
+		match decode_one(&self)? {
+			(Ok(char), len) if len == self.len() => Some(char),
+			_ => None,
+		}
+	}
+
+	/// The EUC-JP encoding of this character.
+	///
+	/// This is computed from the bytes, without checking whether the character is assigned. The
+	/// result is not Shift JIS; this type is merely used as a container for one or two bytes.
+	/// Returns `None` for invalid byte sequences and for rows past 94, which EUC-JP lacks.
+	pub fn to_euc_jp(&self) -> Option<EncodedChar> {
+		match *self {
+			EncodedChar::One([b @ 0x00..=0x7F]) => Some(EncodedChar::One([b])),
+			EncodedChar::One([b @ 0xA1..=0xDF]) => Some(EncodedChar::Two([0x8E, b])),
+			EncodedChar::One(_) => None,
+			EncodedChar::Two(_) => match self.kuten()? {
+				(ku @ 1..=94, ten) => Some(EncodedChar::Two([ku + 0xA0, ten + 0xA0])),
+				_ => None,
+			},
+		}
+	}
 }
 
 impl std::ops::Deref for EncodedChar {
@@ -53,21 +254,26 @@ impl IntoIterator for EncodedChar {
 }
 
 /// Encodes a single character, yielding either an error or one or two bytes.
+///
+/// Of the Latin-1 supplement, U+00A0–U+00FF, only the symbols in JIS X 0208 are encodable:
+/// `§¨°±´¶×÷`. In particular `¢£¬` are not, since Shift JIS has only their full-width forms
+/// `￠￡￢`. Some CP932 encoders map them to those codes anyway, but as that does not round-trip,
+/// this crate leaves it to the caller; [`encode_warn_homoglyphs`] points them out.
+#[inline]
 pub fn encode_char(char: char) -> Option<EncodedChar> {
 	if char.is_ascii() {
 		Some(EncodedChar::One([char as u8]))
 	} else if ('｡'..='ﾟ').contains(&char) {
 		Some(EncodedChar::One([(char as u32 - '｡' as u32) as u8 + 0xA1]))
-	} else if let Some(&[k1, k2]) = UTF8_SJIS.get(&char) {
-		Some(EncodedChar::Two([k1, k2]))
 	} else {
-		None
+		tables::encode(char).map(EncodedChar::Two)
 	}
 }
 
 /// Decodes a single character from the input.
 ///
 /// Consumes one or two bytes from the iterator and returns the decoding result as per [`encode_char_from`], or `None` if the iterator is empty.
+#[inline]
 pub fn decode_char(iter: &mut impl Iterator<Item = u8>) -> Option<Result<char, EncodedChar>> {
 	iter.next().map(|b1| decode_char_from(b1, || iter.next()))
 }
@@ -75,37 +281,304 @@ pub fn decode_char(iter: &mut impl Iterator<Item = u8>) -> Option<Result<char, E
 /// Decodes a single character from an already-read input.
 ///
 /// It will call the `b2` closure if necessary to complete a two-byte sequence.
+#[inline]
 pub fn decode_char_from(b1: u8, b2: impl FnOnce() -> Option<u8>) -> Result<char, EncodedChar> {
-	let enc = Cell::new(EncodedChar::One([b1]));
-	let b2 = || {
-		let b2 = b2().ok_or(enc.get())?;
-		enc.set(EncodedChar::Two([b1, b2]));
-		Ok(b2)
-	};
+	// Tests in order of frequency rather than a `match`, which compiles to a jump table. The
+	// ASCII test is on the byte itself, so that the caller knows the char is a single byte.
+	if b1 < 0x80 {
+		return Ok(char::from(b1));
+	}
+	let lead = LEAD[b1 as usize];
+	if let Lead::Row(a) = lead {
+		let Some(b2) = b2() else {
+			return Err(EncodedChar::One([b1]));
+		};
+		let enc = EncodedChar::Two([b1, b2]);
+		let b = TRAIL[b2 as usize].ok_or(enc)?;
+		return tables::decode(a as usize, b as usize).ok_or(enc);
+	}
+	if let Lead::Kana(k) = lead {
+		return Ok(KANA[k as usize]);
+	}
+	Err(invalid_lead(b1, b2))
+}
 
-	let a = match b1 {
-		a @ 0x00..=0x7F => return Ok(char::from(a)),
-		a @ 0xA1..=0xDF => return Ok(char::from_u32('｡' as u32 + (a - 0xA1) as u32).unwrap()),
-		a @ 0x81..=0x9F => a - 0x81,
-		a @ 0xE0..=0xEF => a - 0xE0 + 0x1F,
-		0x80 | 0xA0 => return Err(enc.get()),
-		0xF0.. => {
-			b2()?;
-			return Err(enc.get());
-		}
-	} as usize;
+/// The error for a lead byte that does not start a character, including its trail byte if it
+/// takes one.
+#[cold]
+fn invalid_lead(b1: u8, b2: impl FnOnce() -> Option<u8>) -> EncodedChar {
+	match LEAD[b1 as usize].is_double().then(b2).flatten() {
+		Some(b2) => EncodedChar::Two([b1, b2]),
+		None => EncodedChar::One([b1]),
+	}
+}
+
+/// Decodes a single character from bytes already in hand.
+///
+/// Like [`decode_char_from`], but takes the second byte directly. It is ignored if the first byte
+/// is a single-byte character.
+pub fn decode_pair(b1: u8, b2: Option<u8>) -> Result<char, EncodedChar> {
+	decode_char_from(b1, || b2)
+}
 
-	let b = match b2()? {
-		b @ 0x40..=0x7E => b - 0x40,
-		b @ 0x80..=0xFC => b - 0x80 + 0x3F,
-		..=0x3F | 0x7F | 0xFD.. => return Err(enc.get()),
-	} as usize;
+/// Decodes the first character of a byte slice, also returning the number of bytes consumed.
+///
+/// Bytes `81`–`9F` and `E0`–`FF` consume two bytes, whether or not the result is valid, unless the
+/// slice has only one byte. Every other byte consumes one. In particular, a lead byte at the end of
+/// the slice consumes one byte and gives an error.
+///
+/// Calling this repeatedly, advancing by the returned count each time, gives the same results as
+/// [`decode`] and [`decode_lossy`]. Returns `None` only if the slice is empty.
+pub fn decode_one(input: &[u8]) -> Option<(Result<char, EncodedChar>, usize)> {
+	let (&b1, rest) = input.split_first()?;
+	let mut len = 1;
+	let result = decode_char_from(b1, || {
+		let b2 = rest.first().copied();
+		len += b2.is_some() as usize;
+		b2
+	});
+	Some((result, len))
+}
 
-	let ch = SJIS_UTF8[a][b];
-	if ch == '�' {
-		return Err(enc.get());
+/// Decodes the next character of an iterator without advancing it, also returning the number of
+/// bytes that [`decode_char`] would consume.
+///
+/// The count follows the same rules as [`decode_one`]: an invalid trail byte is counted as part of
+/// the invalid sequence. A tokenizer that wants to keep such a byte can advance by one instead
+/// whenever the result is an error.
+pub fn decode_char_peek<I: Iterator<Item = u8> + Clone>(
+	iter: &I,
+) -> Option<(Result<char, EncodedChar>, usize)> {
+	let mut iter = iter.clone();
+	let b1 = iter.next()?;
+	let mut len = 1;
+	let result = decode_char_from(b1, || {
+		let b2 = iter.next();
+		len += b2.is_some() as usize;
+		b2
+	});
+	Some((result, len))
+}
+
+/// Decodes the next character of a peekable iterator, consuming the second byte only if it
+/// completes a valid character.
+///
+/// Unlike [`decode_char`], an invalid sequence only consumes its first byte, so a lead byte
+/// followed by something like a quote leaves the quote to be read next. The error still contains
+/// every byte that was examined.
+pub fn decode_char_peekable<I: Iterator<Item = u8>>(
+	iter: &mut std::iter::Peekable<I>,
+) -> Option<Result<char, EncodedChar>> {
+	let b1 = iter.next()?;
+	let mut used_b2 = false;
+	let result = decode_char_from(b1, || {
+		used_b2 = true;
+		iter.peek().copied()
+	});
+	if used_b2 && result.is_ok() {
+		iter.next();
+	}
+	Some(result)
+}
+
+/// Compares two encoded characters by the characters they decode to.
+///
+/// Unlike the derived [`Ord`], which compares the bytes, this puts characters in Unicode order,
+/// which is usually what is wanted when sorting text. Units that do not decode to exactly one
+/// character, such as unassigned codes or lone lead bytes, sort after every valid character, in
+/// byte order among themselves.
+pub fn decoded_cmp(a: &EncodedChar, b: &EncodedChar) -> std::cmp::Ordering {
+	match (a.to_char(), b.to_char()) {
+		(Some(a), Some(b)) => a.cmp(&b),
+		(Some(_), None) => std::cmp::Ordering::Less,
+		(None, Some(_)) => std::cmp::Ordering::Greater,
+		(None, None) => a.cmp(b),
+	}
+}
+
+#[test]
+fn test_decoded_cmp() {
+	let mut chars = "ｱ日あA本ア"
+		.chars()
+		.map(|c| encode_char(c).unwrap())
+		.collect::<Vec<_>>();
+	chars.push(EncodedChar::Two([0xF0, 0x40]));
+	chars.push(EncodedChar::One([0x81]));
+	chars.push(EncodedChar::Two([0x41, 0x42]));
+	chars.sort_by(decoded_cmp);
+	let expected = [
+		EncodedChar::One([b'A']),
+		encode_char('あ').unwrap(),
+		encode_char('ア').unwrap(),
+		encode_char('日').unwrap(),
+		encode_char('本').unwrap(),
+		encode_char('ｱ').unwrap(),
+		EncodedChar::One([0x81]),
+		EncodedChar::Two([0x41, 0x42]),
+		EncodedChar::Two([0xF0, 0x40]),
+	];
+	assert_eq!(chars, expected);
+	// Duplicate encodings compare equal.
+	let (nec, ibm) = (
+		EncodedChar::Two([0xED, 0x40]),
+		EncodedChar::Two([0xFA, 0x5C]),
+	);
+	assert_eq!(decoded_cmp(&nec, &ibm), std::cmp::Ordering::Equal);
+}
+
+/// An encoded character that compares and hashes by the character it decodes to, for use as a map
+/// key.
+///
+/// Duplicate encodings of a character, such as the NEC and IBM codes of `∵`, are thus the same
+/// key. Units that do not decode to a character are compared by their bytes. The ordering is that
+/// of [`decoded_cmp`].
+#[derive(Debug, Clone, Copy)]
+pub struct ByDecodedChar(pub EncodedChar);
+
+impl ByDecodedChar {
+	fn key(&self) -> Result<char, EncodedChar> {
+		self.0.to_char().ok_or(self.0)
+	}
+}
+
+impl PartialEq for ByDecodedChar {
+	fn eq(&self, other: &Self) -> bool {
+		self.key() == other.key()
+	}
+}
+
+impl Eq for ByDecodedChar {}
+
+impl std::hash::Hash for ByDecodedChar {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		self.key().hash(state)
+	}
+}
+
+impl PartialOrd for ByDecodedChar {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for ByDecodedChar {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		decoded_cmp(&self.0, &other.0)
+	}
+}
+
+#[test]
+fn by_decoded_char() {
+	use std::collections::HashMap;
+	// ∵ three ways: NEC row 2, NEC row 13, and IBM.
+	let input = b"\x81\xE6\x87\x9A\xFA\x5B\x93\xFA\x81\x80\xF0\x40\xF0\x40";
+	let mut counts = HashMap::<ByDecodedChar, usize>::new();
+	for (range, _) in decode_units(input) {
+		let bytes = &input[range];
+		let enc = EncodedChar::Two([bytes[0], bytes[1]]);
+		*counts.entry(ByDecodedChar(enc)).or_default() += 1;
+	}
+	assert_eq!(counts.len(), 4);
+	assert_eq!(counts[&ByDecodedChar(encode_char('∵').unwrap())], 3);
+	assert_eq!(counts[&ByDecodedChar(encode_char('日').unwrap())], 1);
+	assert_eq!(counts[&ByDecodedChar(EncodedChar::Two([0xF0, 0x40]))], 2);
+	assert_ne!(
+		ByDecodedChar(EncodedChar::Two([0xF0, 0x40])),
+		ByDecodedChar(EncodedChar::Two([0xF0, 0x41]))
+	);
+	let mut keys = counts.into_keys().collect::<Vec<_>>();
+	keys.sort();
+	let chars = keys.iter().map(|k| k.0.to_char()).collect::<Vec<_>>();
+	assert_eq!(chars, [Some('÷'), Some('∵'), Some('日'), None]);
+}
+
+#[test]
+fn test_decode_char_peek() {
+	let input = b"\x93\xFA\x81\"\x88";
+	let mut iter = input.iter().copied();
+	assert_eq!(decode_char_peek(&iter), Some((Ok('日'), 2)));
+	assert_eq!(iter.len(), 5);
+	iter.nth(1);
+	assert_eq!(
+		decode_char_peek(&iter),
+		Some((Err(EncodedChar::Two([0x81, b'"'])), 2))
+	);
+	iter.next();
+	assert_eq!(decode_char_peek(&iter), Some((Ok('"'), 1)));
+	iter.next();
+	assert_eq!(
+		decode_char_peek(&iter),
+		Some((Err(EncodedChar::One([0x88])), 1))
+	);
+	assert_eq!(iter.len(), 1);
+	iter.next();
+	assert_eq!(decode_char_peek(&iter), None);
+
+	let mut iter = input.iter().copied().peekable();
+	assert_eq!(decode_char_peekable(&mut iter), Some(Ok('日')));
+	assert_eq!(iter.peek(), Some(&0x81));
+	assert_eq!(
+		decode_char_peekable(&mut iter),
+		Some(Err(EncodedChar::Two([0x81, b'"'])))
+	);
+	assert_eq!(iter.peek(), Some(&b'"'));
+	assert_eq!(decode_char_peekable(&mut iter), Some(Ok('"')));
+	assert_eq!(
+		decode_char_peekable(&mut iter),
+		Some(Err(EncodedChar::One([0x88])))
+	);
+	assert_eq!(iter.peek(), None);
+	assert_eq!(decode_char_peekable(&mut iter), None);
+}
+
+#[test]
+fn test_decode_one() {
+	assert_eq!(decode_one(b""), None);
+	assert_eq!(decode_one(b"ab"), Some((Ok('a'), 1)));
+	assert_eq!(decode_one(b"\x93\xFAa"), Some((Ok('日'), 2)));
+	assert_eq!(
+		decode_one(b"\x93"),
+		Some((Err(EncodedChar::One([0x93])), 1))
+	);
+	assert_eq!(
+		decode_one(b"\x81\x20"),
+		Some((Err(EncodedChar::Two([0x81, 0x20])), 2))
+	);
+	assert_eq!(
+		decode_one(b"\xF0\x40"),
+		Some((Err(EncodedChar::Two([0xF0, 0x40])), 2))
+	);
+	assert_eq!(
+		decode_one(b"\x80\x40"),
+		Some((Err(EncodedChar::One([0x80])), 1))
+	);
+
+	let mut state = 1u32;
+	for len in 0..500 {
+		let input = (0..len)
+			.map(|_| {
+				state = state.wrapping_mul(1103515245).wrapping_add(12345);
+				(state >> 16) as u8
+			})
+			.collect::<Vec<_>>();
+		let mut lossy = String::new();
+		let mut strict = Ok(());
+		let mut pos = 0;
+		while let Some((result, n)) = decode_one(&input[pos..]) {
+			assert!(n == 1 || n == 2);
+			if let Err(enc) = result {
+				assert_eq!(enc.len(), n);
+				if strict.is_ok() {
+					strict = Err((pos, enc));
+				}
+			}
+			lossy.push(result.unwrap_or('�'));
+			pos += n;
+		}
+		assert_eq!(pos, input.len());
+		assert_eq!(lossy, decode_lossy(&input));
+		assert_eq!(strict, decode(&input).map(|_| ()));
 	}
-	Ok(ch)
 }
 
 #[test]
@@ -113,6 +586,81 @@ fn encode_replacement() {
 	assert_eq!(EncodedChar::REPLACEMENT, encode_char('・').unwrap())
 }
 
+#[test]
+fn encoded_char_accessors() {
+	let one = encode_char('A').unwrap();
+	assert_eq!(
+		(one.one(), one.two(), one.as_bytes()),
+		(Some(b'A'), None, &b"A"[..])
+	);
+	let two = EncodedChar::REPLACEMENT;
+	assert_eq!(
+		(two.one(), two.two(), two.as_bytes()),
+		(None, Some([0x81, 0x45]), &[0x81, 0x45][..])
+	);
+	assert_eq!(one.to_inline(), ([b'A', 0], 1));
+	assert_eq!(two.to_inline(), ([0x81, 0x45], 2));
+	let (bytes, len) = one.to_inline();
+	assert_eq!(&bytes[..len], one.as_bytes());
+}
+
+#[test]
+fn test_decode_pair() {
+	assert_eq!(decode_pair(0x93, Some(0xFA)), Ok('日'));
+	assert_eq!(decode_pair(0x41, Some(0xFA)), Ok('A'));
+	assert_eq!(decode_pair(0x93, None), Err(EncodedChar::One([0x93])));
+	assert_eq!(
+		decode_pair(0x93, Some(0x20)),
+		Err(EncodedChar::Two([0x93, 0x20]))
+	);
+}
+
+#[test]
+fn kuten() {
+	assert_eq!(EncodedChar::Two([0x81, 0x40]).kuten(), Some((1, 1)));
+	assert_eq!(EncodedChar::Two([0x88, 0x9F]).kuten(), Some((16, 1)));
+	assert_eq!(EncodedChar::Two([0x98, 0x9E]).kuten(), Some((47, 94)));
+	assert_eq!(EncodedChar::Two([0xE0, 0x40]).kuten(), Some((63, 1)));
+	assert_eq!(EncodedChar::Two([0xEF, 0xFC]).kuten(), Some((94, 94)));
+	assert_eq!(EncodedChar::Two([0xF0, 0x40]).kuten(), Some((95, 1)));
+	assert_eq!(EncodedChar::Two([0x81, 0x7F]).kuten(), None);
+	assert_eq!(EncodedChar::Two([0xA0, 0x40]).kuten(), None);
+	assert_eq!(EncodedChar::One([0x41]).kuten(), None);
+	for b1 in 0..=0xFF {
+		for b2 in 0..=0xFF {
+			let enc = EncodedChar::Two([b1, b2]);
+			if let Some((ku, ten)) = enc.kuten() {
+				assert_eq!(EncodedChar::from_kuten(ku, ten), Some(enc));
+			}
+		}
+	}
+	assert_eq!(EncodedChar::from_kuten(121, 1), None);
+	assert_eq!(EncodedChar::from_kuten(1, 95), None);
+}
+
+#[test]
+fn latin1_symbols() {
+	let symbols = [
+		('§', [0x81, 0x98]),
+		('¨', [0x81, 0x4E]),
+		('°', [0x81, 0x8B]),
+		('±', [0x81, 0x7D]),
+		('´', [0x81, 0x4C]),
+		('¶', [0x81, 0xF7]),
+		('×', [0x81, 0x7E]),
+		('÷', [0x81, 0x80]),
+	];
+	for (char, code) in symbols {
+		assert_eq!(encode_char(char), Some(EncodedChar::Two(code)), "{char:?}");
+		assert_eq!(decode_pair(code[0], Some(code[1])), Ok(char));
+	}
+	for char in '\u{A0}'..='\u{FF}' {
+		let listed = symbols.iter().any(|s| s.0 == char);
+		assert_eq!(encode_char(char).is_some(), listed, "{char:?}");
+	}
+	assert_eq!(encode("3×4=12°"), Ok(b"3\x81\x7E4=12\x81\x8B".to_vec()));
+}
+
 #[test]
 fn encode_then_decode() {
 	for char in (0..=0xFFFF).filter_map(char::from_u32) {
@@ -140,9 +688,16 @@ fn decode_then_encode() {
 		let mut it = array.into_iter();
 		if let Some(Ok(dec)) = decode_char(&mut it) {
 			let consumed = &array[..2 - it.as_slice().len()];
-			let enc = encode_char(dec).unwrap();
+			let enc = if (0xFA..=0xFC).contains(&array[0]) {
+				VendorPreference::Ibm.encode_char(dec).unwrap()
+			} else {
+				encode_char(dec).unwrap()
+			};
 			let enc = enc.into_iter().collect::<Vec<u8>>();
-			if enc != consumed && !duplicates.contains(&array) {
+			// IBM codes that duplicate a JIS X 0208 or NEC row 13 code encode to that code.
+			let ibm_duplicate = (0xFA..=0xFC).contains(&array[0])
+				&& !matches!(encode_char(dec), Some(EncodedChar::Two([0xED | 0xEE, _])));
+			if enc != consumed && !duplicates.contains(&array) && !ibm_duplicate {
 				panic!("{:02X?} {:?} {:02X?}", consumed, dec, enc);
 			};
 		}
@@ -153,31 +708,293 @@ fn decode_then_encode() {
 ///
 /// Returns `Err(position)` if a codepoint cannot be represented in Shift JIS, where `position` is
 /// the UTF-8 offset of the offending codepoint in the input string.
+///
+/// The output consists only of complete characters, so it never contains a lead byte without its
+/// trail byte, and can always be decoded again with [`decode`]. An empty string gives `Ok` with an
+/// empty vec; see [`encode_nonempty`] to tell that case apart.
 pub fn encode(str: &str) -> Result<Vec<u8>, usize> {
-	let mut out = Vec::new();
-	for (pos, char) in str.char_indices() {
-		if let Some(char) = encode_char(char) {
-			out.extend(char)
-		} else {
-			return Err(pos);
-		}
+	BuiltinTable.encode(str)
+}
+
+/// Like [`encode`], but returns `Ok(None)` for an empty string.
+pub fn encode_nonempty(str: &str) -> Result<Option<Vec<u8>>, usize> {
+	if str.is_empty() {
+		Ok(None)
+	} else {
+		encode(str).map(Some)
+	}
+}
+
+/// Encodes a string, borrowing it if it is pure ASCII.
+///
+/// ASCII is encoded as itself, so this avoids allocating in that case. Otherwise this is the same
+/// as [`encode`].
+pub fn encode_cow(str: &str) -> Result<Cow<'_, [u8]>, usize> {
+	if str.is_ascii() {
+		Ok(Cow::Borrowed(str.as_bytes()))
+	} else {
+		encode(str).map(Cow::Owned)
 	}
-	Ok(out)
+}
+
+#[test]
+fn test_encode_cow() {
+	assert!(matches!(encode_cow("abc\0"), Ok(Cow::Borrowed(b"abc\0"))));
+	assert!(matches!(
+		encode_cow("a日").as_deref(),
+		Ok([0x61, 0x93, 0xFA])
+	));
+	assert!(matches!(encode_cow("a日"), Ok(Cow::Owned(_))));
+	assert_eq!(encode_cow("日本2=₂"), Err("日本2=".len()));
+}
+
+/// Encodes a string into a per-thread scratch buffer, and passes the result to a closure.
+///
+/// This avoids allocating when encoding many strings in a row. The bytes are only borrowed for the
+/// duration of the closure, since the buffer is reused by the next call on the same thread; copy
+/// them if they are needed afterwards. Calling this from inside the closure works, but uses a
+/// separate buffer.
+///
+/// The result is the same as from [`encode`].
+pub fn encode_scratch<R>(str: &str, f: impl FnOnce(Result<&[u8], usize>) -> R) -> R {
+	thread_local! {
+		static SCRATCH: Cell<Vec<u8>> = const { Cell::new(Vec::new()) };
+	}
+	let mut buf = SCRATCH.take();
+	buf.clear();
+	let result = match sink::encode_into(&mut buf, str, encode_char, |_, _| None) {
+		Ok(()) => Ok(&buf[..]),
+		Err(sink::SinkError::Unencodable(pos)) => Err(pos),
+		Err(sink::SinkError::Sink(_, e)) => match e {},
+	};
+	let value = f(result);
+	SCRATCH.set(buf);
+	value
+}
+
+#[test]
+fn test_encode_scratch() {
+	assert_eq!(
+		encode_scratch("日本", |r| r.map(<[u8]>::to_vec)),
+		encode("日本")
+	);
+	assert_eq!(encode_scratch("a₂", |r| r.map(<[u8]>::to_vec)), Err(1));
+	let ptr = encode_scratch("abc", |r| r.unwrap().as_ptr());
+	assert_eq!(encode_scratch("x", |r| r.unwrap().as_ptr()), ptr);
+	encode_scratch("outer", |outer| {
+		encode_scratch("inner", |inner| assert_eq!(inner, Ok(&b"inner"[..])));
+		assert_eq!(outer, Ok(&b"outer"[..]));
+	});
 }
 
 /// Encodes a string into a byte vec, lossily.
 ///
 /// Characters that cannot be encoded in Shift-JIS are substituted with [`EncodedChar::REPLACEMENT`].
 pub fn encode_lossy(str: &str) -> Vec<u8> {
-	let mut out = Vec::new();
-	for char in str.chars() {
-		if let Some(char) = encode_char(char) {
-			out.extend(char)
-		} else {
-			out.extend(EncodedChar::REPLACEMENT)
+	sink::encode_replacing(str, encode_char)
+}
+
+/// Like [`encode_lossy`], but also returns the UTF-8 offsets of the characters that were replaced.
+///
+/// This tells replaced characters apart from actual occurrences of `・`.
+pub fn encode_lossy_tracked(str: &str) -> (Vec<u8>, Vec<usize>) {
+	let mut out = Vec::with_capacity(str.len());
+	let mut replaced = Vec::new();
+	let mut trace = trace::Trace::encode(str.len(), trace::DEFAULT_LEVEL);
+	let result = sink::encode_into(&mut out, str, encode_char, |pos, char| {
+		trace.replaced_char(pos, char, EncodedChar::REPLACEMENT);
+		replaced.push(pos);
+		Some(EncodedChar::REPLACEMENT)
+	});
+	let Ok(()) = result else { unreachable!() };
+	(out, replaced)
+}
+
+#[test]
+fn test_encode_lossy_tracked() {
+	let s = "a₂・日₃";
+	let (bytes, replaced) = encode_lossy_tracked(s);
+	assert_eq!(bytes, encode_lossy(s));
+	assert_eq!(replaced, [1, 10]);
+	assert_eq!(decode(&bytes).unwrap(), "a・・日・");
+	assert_eq!(encode_lossy_tracked(""), (vec![], vec![]));
+}
+
+/// Like [`encode`], but reports every character that cannot be encoded rather than only the first.
+///
+/// If any character fails, no bytes are returned, only the errors in order.
+pub fn encode_all_errors(str: &str) -> Result<Vec<u8>, Vec<EncodeError>> {
+	let mut out = Vec::with_capacity(str.len());
+	let mut errors = Vec::new();
+	let result = sink::encode_into(&mut out, str, encode_char, |position, char| {
+		errors.push(EncodeError { position, char });
+		Some(EncodedChar::REPLACEMENT)
+	});
+	let Ok(()) = result else { unreachable!() };
+	if errors.is_empty() {
+		Ok(out)
+	} else {
+		Err(errors)
+	}
+}
+
+#[test]
+fn test_encode_all_errors() {
+	assert_eq!(encode_all_errors("a日"), encode("a日").map_err(|_| vec![]));
+	assert_eq!(encode_all_errors(""), Ok(vec![]));
+	let errors = encode_all_errors("₂a日₃₄").unwrap_err();
+	let errors = errors
+		.iter()
+		.map(|e| (e.position, e.char))
+		.collect::<Vec<_>>();
+	assert_eq!(errors, [(0, '₂'), (7, '₃'), (10, '₄')]);
+}
+
+/// Like [`encode`], but also fails on ASCII control characters other than those in `allowed`.
+///
+/// Control characters are `00`–`1F` and `7F`. This catches stray ones, such as a tab pasted into
+/// dialogue, where only a few like `\n` or a form feed for page breaks are meaningful.
+pub fn encode_checked_controls(str: &str, allowed: &[u8]) -> Result<Vec<u8>, EncodeError> {
+	sink::encode_strict(str, |char| {
+		let allowed = !char.is_ascii_control() || allowed.contains(&(char as u8));
+		encode_char(char).filter(|_| allowed)
+	})
+	.map_err(|pos| EncodeError::at(str, pos))
+}
+
+#[test]
+fn test_encode_checked_controls() {
+	let s = "はい。\x0Cいいえ\n";
+	assert_eq!(
+		encode_checked_controls(s, b"\n\x0C"),
+		encode(s).map_err(|_| unreachable!())
+	);
+	assert_eq!(
+		encode_checked_controls(s, b"\n"),
+		Err(EncodeError {
+			position: 9,
+			char: '\x0C'
+		})
+	);
+	assert_eq!(
+		encode_checked_controls("a\tb\x7F", b"\t").unwrap_err().char,
+		'\x7F'
+	);
+	assert_eq!(
+		encode_checked_controls("a₂\0", b"").unwrap_err().position,
+		1
+	);
+	assert_eq!(encode_checked_controls("", b"").unwrap(), b"");
+}
+
+/// Encodes a string into a byte vec, with yen/backslash unification.
+///
+/// Some fonts render `0x5C` as `¥`, so in this mode both `¥` and `\` encode to `0x5C`. Other than
+/// that, this is the same as [`encode`], under which `¥` is unencodable.
+pub fn encode_yen_unified(str: &str) -> Result<Vec<u8>, usize> {
+	sink::encode_strict(str, |char| match char {
+		'¥' => Some(EncodedChar::One([b'\\'])),
+		_ => encode_char(char),
+	})
+}
+
+#[test]
+fn test_encode_yen_unified() {
+	assert_eq!(encode("¥100"), Err(0));
+	assert_eq!(encode_yen_unified("¥100\\").as_deref(), Ok(&b"\\100\\"[..]));
+	assert_eq!(encode_yen_unified("¥₂"), Err(2));
+}
+
+/// Encodes a string into a byte vec, with extra mappings for characters missing from the table.
+///
+/// `extra` is only consulted for characters that [`encode_char`] cannot encode; the error
+/// position is that of the first character neither can encode.
+pub fn encode_with_extra(
+	str: &str,
+	extra: impl Fn(char) -> Option<EncodedChar>,
+) -> Result<Vec<u8>, usize> {
+	sink::encode_strict(str, |char| encode_char(char).or_else(|| extra(char)))
+}
+
+#[test]
+fn test_encode_with_extra() {
+	let extra = |c| match c {
+		'₂' => Some(EncodedChar::Two([0xF0, 0x40])),
+		'ā' => Some(EncodedChar::One([b'a'])),
+		// Never consulted, since the table has it.
+		'日' => Some(EncodedChar::One([b'x'])),
+		_ => None,
+	};
+	assert_eq!(
+		encode_with_extra("H₂O日ā", extra).as_deref(),
+		Ok(&b"H\xF0\x40O\x93\xFAa"[..])
+	);
+	assert_eq!(encode_with_extra("H₂O₃", extra), Err(5));
+	assert_eq!(encode_with_extra("H₂", |_| None), encode("H₂"));
+}
+
+/// Encodes a string into a byte vec, also returning what each character was encoded to.
+///
+/// The result is the same as from [`encode`]. The trace covers every character in the input,
+/// including any after the first error, with `None` for those that cannot be encoded.
+#[allow(clippy::type_complexity)]
+pub fn encode_trace(str: &str) -> (Result<Vec<u8>, usize>, Vec<(char, Option<EncodedChar>)>) {
+	let mut out = Ok(Vec::new());
+	let mut trace = Vec::new();
+	for (pos, char) in str.char_indices() {
+		let enc = encode_char(char);
+		match (&mut out, enc) {
+			(Ok(out), Some(enc)) => out.extend(enc),
+			(Ok(_), None) => out = Err(pos),
+			(Err(_), _) => {}
 		}
+		trace.push((char, enc));
 	}
-	out
+	(out, trace)
+}
+
+#[test]
+fn test_encode_trace() {
+	assert_eq!(
+		encode_trace("A₂日₃"),
+		(
+			Err(1),
+			vec![
+				('A', Some(EncodedChar::One([0x41]))),
+				('₂', None),
+				('日', Some(EncodedChar::Two([0x93, 0xFA]))),
+				('₃', None),
+			]
+		),
+	);
+	assert_eq!(encode_trace("日本").0, encode("日本"));
+}
+
+#[test]
+fn encode_no_lone_lead_byte() {
+	let all = (0..=0xFFFF).filter_map(char::from_u32).collect::<String>();
+	let encodable = all
+		.chars()
+		.filter(|&c| encode_char(c).is_some())
+		.collect::<String>();
+	let enc = encode(&encodable).unwrap();
+	assert_eq!(
+		decode(&enc).map(|s| s.chars().count()),
+		Ok(encodable.chars().count())
+	);
+	let enc = encode_lossy(&all);
+	assert_eq!(
+		decode(&enc).map(|s| s.chars().count()),
+		Ok(all.chars().count())
+	);
+}
+
+#[test]
+fn test_encode_empty() {
+	assert_eq!(encode(""), Ok(vec![]));
+	assert_eq!(encode_nonempty(""), Ok(None));
+	assert_eq!(encode_nonempty("a"), Ok(Some(vec![b'a'])));
+	assert_eq!(encode_nonempty("a₂"), Err(1));
 }
 
 #[rustfmt::skip]
@@ -196,31 +1013,372 @@ fn test_encode() {
 /// Returns `Err(position)` on encountering an invalid byte sequence, where `position` is the
 /// offset of the first byte of the sequence.
 pub fn decode(input: &[u8]) -> Result<String, (usize, EncodedChar)> {
-	let mut out = String::new();
-	let mut pos = 0;
-	let mut iter = input.iter().copied().inspect(|_| pos += 1);
-	while let Some(b1) = iter.next() {
-		match decode_char_from(b1, || iter.next()) {
-			Ok(char) => out.push(char),
-			Err(enc) => return Err((pos - enc.len(), enc)),
-		}
+	BuiltinTable.decode(input)
+}
+
+/// Decodes a byte slice into a string, like [`decode`], but with a [`DecodeError`].
+///
+/// The error implements [`std::error::Error`], so this works with `?` in functions returning
+/// `Box<dyn Error>` or similar.
+pub fn try_decode(input: &[u8]) -> Result<String, DecodeError> {
+	decode(input).map_err(DecodeError::from)
+}
+
+/// Decodes as much of a byte slice as possible, returning the string decoded before the first
+/// invalid sequence, and the error for that sequence.
+///
+/// The error is the same as from [`decode`], and is `None` if the whole input is valid.
+pub fn decode_partial(input: &[u8]) -> (String, Option<(usize, EncodedChar)>) {
+	let mut out = String::with_capacity(input.len());
+	match sink::decode_into(
+		&mut out,
+		input,
+		|b1, rest| decode_char_from(b1, || rest.next()),
+		|_, _, _| false,
+	) {
+		Ok(()) => (out, None),
+		Err(DecodeSinkError::Undecodable(pos, enc)) => (out, Some((pos, enc))),
+		Err(DecodeSinkError::Sink(e)) => match e {},
 	}
-	Ok(out)
+}
+
+#[test]
+fn test_decode_partial() {
+	assert_eq!(decode_partial(b"a\x93\xFA"), ("a日".to_owned(), None));
+	assert_eq!(decode_partial(b""), (String::new(), None));
+	let input = b"a\x93\xFA\x85\x40b\x80";
+	let (s, error) = decode_partial(input);
+	assert_eq!(s, "a日");
+	assert_eq!(error, decode(input).err());
+	assert_eq!(error, Some((3, EncodedChar::Two([0x85, 0x40]))));
+	assert_eq!(
+		decode_partial(b"ab\x81").1,
+		Some((2, EncodedChar::One([0x81])))
+	);
+}
+
+/// The safe implementation of [`decode`].
+#[cfg_attr(feature = "unsafe-fast", allow(dead_code))]
+fn decode_checked(input: &[u8]) -> Result<String, (usize, EncodedChar)> {
+	sink::decode_strict(input, |b1, rest| decode_char_from(b1, || rest.next()))
+}
+
+/// Decodes a byte slice into a string, ignoring trailing padding.
+///
+/// Bytes in `pad`, commonly `0x00` or `0x20`, are stripped from the end of the input before
+/// decoding. Note that if `pad` contains bytes in the trail byte range `40`–`FC`, the second half
+/// of a final two-byte character may be stripped as well.
+pub fn decode_trim_padding(input: &[u8], pad: &[u8]) -> Result<String, DecodeError> {
+	let len = input
+		.iter()
+		.rposition(|b| !pad.contains(b))
+		.map_or(0, |i| i + 1);
+	Ok(decode(&input[..len])?)
+}
+
+#[test]
+fn test_decode_trim_padding() {
+	assert_eq!(
+		decode_trim_padding(b"\x93\xFA\0\0\0", &[0]).as_deref(),
+		Ok("日")
+	);
+	assert_eq!(
+		decode_trim_padding(b"a b \0 \0", &[0x20, 0]).as_deref(),
+		Ok("a b")
+	);
+	assert_eq!(decode_trim_padding(b"\0\0", &[0]).as_deref(), Ok(""));
+	assert_eq!(decode_trim_padding(b"", &[0]).as_deref(), Ok(""));
+	assert_eq!(decode_trim_padding(b"a\0", &[]).as_deref(), Ok("a\0"));
+	assert_eq!(
+		decode_trim_padding(b"a\x80\xFF\xFF", &[0xFF]),
+		Err(DecodeError {
+			position: 1,
+			bytes: EncodedChar::One([0x80])
+		})
+	);
 }
 
 /// Decodes a byte slice into a string, lossily.
 ///
-/// Invalid bytes are replaced with the unicode replacement character, one per byte.
+/// Invalid byte sequences are replaced with the unicode replacement character, one per sequence.
+/// See [`decode_lossy_aligned`] for one per byte.
 pub fn decode_lossy(input: &[u8]) -> String {
 	let mut out = String::new();
-	let mut iter = input.iter().copied();
-	while let Some(b1) = iter.next() {
-		match decode_char_from(b1, || iter.next()) {
-			Ok(char) => out.push(char),
-			Err(_) => out.push('�'),
+	sink::decode_replacing(&mut out, input, |b1, rest| {
+		decode_char_from(b1, || rest.next())
+	});
+	out
+}
+
+/// Decodes a byte slice into a string, replacing up to `max_errors` invalid byte sequences.
+///
+/// The replacements are as per [`decode_lossy`]. If there are more invalid sequences than that, the
+/// input is probably not Shift JIS at all, and the error holds the first sequence past the budget.
+/// With a budget of 0 this accepts the same input as [`decode`].
+pub fn decode_tolerant(input: &[u8], max_errors: usize) -> Result<String, TooManyErrors> {
+	let mut out = String::new();
+	let mut errors = 0;
+	let result = sink::decode_into(
+		&mut out,
+		input,
+		|b1, rest| decode_char_from(b1, || rest.next()),
+		|out, _, _| {
+			if errors == max_errors {
+				return false;
+			}
+			errors += 1;
+			out.push('�');
+			true
+		},
+	);
+	match result {
+		Ok(()) => Ok(out),
+		Err(DecodeSinkError::Undecodable(pos, enc)) => Err(TooManyErrors {
+			max_errors,
+			error: DecodeError::from((pos, enc)),
+		}),
+		Err(DecodeSinkError::Sink(e)) => match e {},
+	}
+}
+
+#[test]
+fn test_decode_tolerant() {
+	let input = b"a\x80b\x81\x20c\xFF";
+	assert_eq!(decode_tolerant(input, 3).as_deref(), Ok("a�b�c�"));
+	assert_eq!(decode_tolerant(input, 100), Ok(decode_lossy(input)));
+	let err = decode_tolerant(input, 2).unwrap_err();
+	let error = DecodeError {
+		position: 6,
+		bytes: EncodedChar::One([0xFF]),
+	};
+	assert_eq!(
+		err,
+		TooManyErrors {
+			max_errors: 2,
+			error
 		}
+	);
+	assert_eq!(
+		err.to_string(),
+		"more than 2 invalid byte sequences, the next at position 6"
+	);
+	let err = decode_tolerant(input, 0).unwrap_err();
+	assert_eq!(err.error, decode(input).unwrap_err().into());
+	assert_eq!(decode_tolerant(b"", 0).as_deref(), Ok(""));
+}
+
+/// Decodes a byte slice into a string, with extra mappings for cells missing from the table.
+///
+/// `extra` is only consulted for two-byte sequences that are well-formed but unassigned, such as
+/// the user-defined area; the error is the first sequence neither can decode. This is the inverse
+/// of [`encode_with_extra`].
+pub fn decode_with_extra(
+	input: &[u8],
+	extra: impl Fn([u8; 2]) -> Option<char>,
+) -> Result<String, DecodeError> {
+	let mut out = String::new();
+	let result = sink::decode_into(
+		&mut out,
+		input,
+		|b1, rest| decode_char_from(b1, || rest.next()),
+		|out, _, enc| {
+			match enc.two().filter(|_| enc.kuten().is_some()).and_then(&extra) {
+				Some(char) => out.push(char),
+				None => return false,
+			}
+			true
+		},
+	);
+	match result {
+		Ok(()) => Ok(out),
+		Err(DecodeSinkError::Undecodable(pos, enc)) => Err(DecodeError::from((pos, enc))),
+		Err(DecodeSinkError::Sink(e)) => match e {},
 	}
-	out
+}
+
+#[test]
+fn test_decode_with_extra() {
+	let extra = |code: [u8; 2]| match code {
+		[0xF0, 0x40] => Some('₂'),
+		[0x81, 0x7F] => Some('?'),
+		// Never consulted, since the table has it.
+		[0x93, 0xFA] => Some('x'),
+		_ => None,
+	};
+	assert_eq!(
+		decode_with_extra(b"H\xF0\x40O\x93\xFA", extra).as_deref(),
+		Ok("H₂O日")
+	);
+	assert_eq!(
+		decode_with_extra(b"H\xF0\x40\xF0\x41", extra),
+		Err(DecodeError {
+			position: 3,
+			bytes: EncodedChar::Two([0xF0, 0x41])
+		})
+	);
+	assert_eq!(
+		decode_with_extra(b"a\x81\x7F", extra).unwrap_err().position,
+		1
+	);
+	assert_eq!(
+		decode_with_extra(b"a\x80", |_| Some('x'))
+			.unwrap_err()
+			.position,
+		1
+	);
+	let s = "H₂O";
+	let enc = encode_with_extra(s, |c| (c == '₂').then_some(EncodedChar::Two([0xF0, 0x40])));
+	assert_eq!(decode_with_extra(&enc.unwrap(), extra).as_deref(), Ok(s));
+}
+
+/// Decodes a byte slice into a string, lossily, keeping alignment with the input bytes.
+///
+/// Each invalid byte sequence is replaced with as many unicode replacement characters as it has
+/// bytes. Since two-byte characters are full-width, every byte of input thus corresponds to one
+/// column of output.
+pub fn decode_lossy_aligned(input: &[u8]) -> String {
+	let mut out = String::new();
+	let mut trace = trace::Trace::decode(input.len(), trace::DEFAULT_LEVEL);
+	match sink::decode_into(
+		&mut out,
+		input,
+		|b1, rest| decode_char_from(b1, || rest.next()),
+		|out, pos, enc| {
+			trace.replaced_bytes(pos, &enc, '�');
+			out.extend(std::iter::repeat_n('�', enc.len()));
+			true
+		},
+	) {
+		Ok(()) => out,
+		Err(DecodeSinkError::Undecodable(..)) => unreachable!(),
+		Err(DecodeSinkError::Sink(e)) => match e {},
+	}
+}
+
+#[test]
+fn test_decode_lossy_aligned() {
+	let input = [0x41, 0x81, 0x20, 0xF0, 0x40, 0x80, 0x93, 0xFA, 0x93];
+	assert_eq!(decode_lossy_aligned(&input), "A�����日�");
+}
+
+/// Decodes a byte slice into a string lossily, also reporting which unmapped cells were encountered.
+///
+/// The string is the same as from [`decode_lossy`]. The cells are given as `(ku, ten)` as per
+/// [`EncodedChar::kuten`], sorted and without duplicates. Byte sequences that do not form a cell at
+/// all, such as invalid trail bytes, are not included.
+pub fn decode_report_unmapped(input: &[u8]) -> (String, Vec<(u8, u8)>) {
+	let mut out = String::new();
+	let mut cells = Vec::new();
+	match sink::decode_into(
+		&mut out,
+		input,
+		|b1, rest| decode_char_from(b1, || rest.next()),
+		|out, _, enc| {
+			out.push('�');
+			cells.extend(enc.kuten());
+			true
+		},
+	) {
+		Ok(()) => {}
+		Err(DecodeSinkError::Undecodable(..)) => unreachable!(),
+		Err(DecodeSinkError::Sink(e)) => match e {},
+	}
+	cells.sort();
+	cells.dedup();
+	(out, cells)
+}
+
+#[test]
+fn test_decode_report_unmapped() {
+	let input = [
+		0xF0, 0x40, 0x93, 0xFA, 0x85, 0x9F, 0x81, 0x20, 0xF0, 0x40, 0x80,
+	];
+	let cells = vec![(10, 1), (95, 1)];
+	assert_eq!(
+		decode_report_unmapped(&input),
+		("�日����".to_owned(), cells)
+	);
+}
+
+/// Decodes a byte slice, pairing each character with the bytes it was decoded from.
+///
+/// A few characters have more than one encoding, such as `≒` which is both `81E0` and `8790`, and
+/// [`encode_char`] only returns one of them. Writing out the stored bytes instead of re-encoding
+/// reproduces the input exactly.
+pub fn decode_with_source(input: &[u8]) -> Result<Vec<(char, EncodedChar)>, DecodeError> {
+	let mut out = Vec::new();
+	let decode_char = |b1, rest: &mut sink::Rest| {
+		let mut b2 = None;
+		let char = decode_char_from(b1, || {
+			b2 = rest.next();
+			b2
+		})?;
+		let enc = match b2 {
+			Some(b2) => EncodedChar::Two([b1, b2]),
+			None => EncodedChar::One([b1]),
+		};
+		Ok((char, enc))
+	};
+	match sink::decode_into(&mut out, input, decode_char, |_, _, _| false) {
+		Ok(()) => Ok(out),
+		Err(DecodeSinkError::Undecodable(pos, enc)) => Err(DecodeError::from((pos, enc))),
+		Err(DecodeSinkError::Sink(e)) => match e {},
+	}
+}
+
+#[test]
+fn test_decode_with_source() {
+	let input = [0x41, 0x87, 0x90, 0x81, 0xE0, 0xB1, 0x93, 0xFA];
+	let decoded = decode_with_source(&input).unwrap();
+	assert_eq!(
+		decoded,
+		[
+			('A', EncodedChar::One([0x41])),
+			('≒', EncodedChar::Two([0x87, 0x90])),
+			('≒', EncodedChar::Two([0x81, 0xE0])),
+			('ｱ', EncodedChar::One([0xB1])),
+			('日', EncodedChar::Two([0x93, 0xFA])),
+		]
+	);
+	assert_eq!(decoded.iter().flat_map(|e| e.1).collect::<Vec<_>>(), input);
+	assert_ne!(encode("A≒≒ｱ日").unwrap(), input);
+	assert_eq!(
+		decode_with_source(&[0x41, 0x81, 0x20]),
+		Err(DecodeError {
+			position: 1,
+			bytes: EncodedChar::Two([0x81, 0x20])
+		})
+	);
+	assert_eq!(decode_with_source(&[0x93]).unwrap_err().position, 0);
+}
+
+/// Decodes a byte slice into its characters, along with the JIS row and cell of each two-byte
+/// character, as per [`EncodedChar::kuten`].
+///
+/// Single-byte characters have `None`. This is useful for locating glyphs in a font laid out by
+/// row and cell.
+#[allow(clippy::type_complexity)]
+pub fn decode_with_kuten(input: &[u8]) -> Result<Vec<(char, Option<(u8, u8)>)>, DecodeError> {
+	Ok(decode_with_source(input)?
+		.into_iter()
+		.map(|(char, enc)| (char, enc.kuten()))
+		.collect())
+}
+
+#[test]
+fn test_decode_with_kuten() {
+	assert_eq!(
+		decode_with_kuten(b"A\x88\x9F\xB1\x87\x40\xFA\x40").unwrap(),
+		[
+			('A', None),
+			('亜', Some((16, 1))),
+			('ｱ', None),
+			('①', Some((13, 1))),
+			('ⅰ', Some((115, 1))),
+		]
+	);
+	assert_eq!(decode_with_kuten(b"").unwrap(), []);
+	assert_eq!(decode_with_kuten(b"a\x81\x20").unwrap_err().position, 1);
 }
 
 #[rustfmt::skip]
@@ -0,0 +1,227 @@
+//! Checking text against the length limits of a game's text boxes.
+
+use std::ops::Range;
+
+use crate::display_width;
+use crate::width::char_width;
+
+/// Length limits for a message, as used by [`check_limits`]. Each limit is optional.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TextLimits {
+	/// The most encoded bytes on a line, not counting the line ending.
+	pub line_bytes: Option<usize>,
+	/// The most columns on a line, as per [`display_width`].
+	pub line_columns: Option<usize>,
+	/// The most lines in the message.
+	pub lines: Option<usize>,
+	/// The most encoded bytes in the whole message, including line endings.
+	pub total_bytes: Option<usize>,
+}
+
+/// Which of the [`TextLimits`] was exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitKind {
+	/// [`TextLimits::line_bytes`].
+	LineBytes,
+	/// [`TextLimits::line_columns`].
+	LineColumns,
+	/// [`TextLimits::lines`].
+	Lines,
+	/// [`TextLimits::total_bytes`].
+	TotalBytes,
+}
+
+/// A limit exceeded by a message, as found by [`check_limits`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LimitViolation {
+	/// Which limit was exceeded.
+	pub kind: LimitKind,
+	/// The line the overflow starts on, starting at 1.
+	pub line: usize,
+	/// The measured length.
+	pub measured: usize,
+	/// The limit.
+	pub limit: usize,
+	/// The UTF-8 byte range of the text past the limit: the rest of the line for the per-line
+	/// limits, and the rest of the message otherwise.
+	pub range: Range<usize>,
+}
+
+/// Checks a message against a set of length limits, returning every limit it exceeds.
+///
+/// Lengths are measured in Shift JIS: each character counts as many bytes as it encodes to, with
+/// unencodable characters counting as two like the `・` that [`crate::encode_lossy`] writes. Since
+/// half-width characters are one byte and full-width ones two, bytes and columns coincide; both
+/// limits exist so that they can be given as the engine states them.
+///
+/// Lines are separated by `\n`, and a trailing `\n` does not start a new line. A `\r` before it
+/// counts towards the total but not the line. Violations are ordered by line, with the total last.
+pub fn check_limits(s: &str, limits: &TextLimits) -> Vec<LimitViolation> {
+	let mut violations = Vec::new();
+	let mut start = 0;
+	let mut lines = 0;
+	for line in s.split_inclusive('\n') {
+		lines += 1;
+		let text = line.strip_suffix('\n').unwrap_or(line);
+		let text = text.strip_suffix('\r').unwrap_or(text);
+		let per_line = [
+			(LimitKind::LineBytes, limits.line_bytes),
+			(LimitKind::LineColumns, limits.line_columns),
+		];
+		for (kind, limit) in per_line {
+			let Some(limit) = limit else { continue };
+			if let Some(over) = overflow(text, limit) {
+				violations.push(LimitViolation {
+					kind,
+					line: lines,
+					measured: display_width(text),
+					limit,
+					range: start + over..start + text.len(),
+				});
+			}
+		}
+		if limits.lines == Some(lines - 1) {
+			violations.push(LimitViolation {
+				kind: LimitKind::Lines,
+				line: lines,
+				measured: s.split_inclusive('\n').count(),
+				limit: lines - 1,
+				range: start..s.len(),
+			});
+		}
+		start += line.len();
+	}
+	if let Some(limit) = limits.total_bytes {
+		if let Some(over) = overflow(s, limit) {
+			violations.push(LimitViolation {
+				kind: LimitKind::TotalBytes,
+				line: s[..over].matches('\n').count() + 1,
+				measured: display_width(s),
+				limit,
+				range: over..s.len(),
+			});
+		}
+	}
+	violations
+}
+
+/// The offset of the first character that goes past `limit` columns, if any.
+fn overflow(s: &str, limit: usize) -> Option<usize> {
+	let mut width = 0;
+	s.char_indices().find_map(|(i, c)| {
+		width += char_width(c);
+		(width > limit).then_some(i)
+	})
+}
+
+#[test]
+fn compliant() {
+	let limits = TextLimits {
+		line_bytes: Some(12),
+		line_columns: Some(12),
+		lines: Some(3),
+		total_bytes: Some(40),
+	};
+	assert_eq!(check_limits("", &limits), []);
+	assert_eq!(check_limits("おはよう！\nHP 100/100\n", &limits), []);
+	assert_eq!(check_limits("一二三四五六\n\r\nabc", &limits), []);
+}
+
+#[test]
+fn each_limit() {
+	let s = "「おはよう」\nEstelle: Hi!\nｴｽﾃﾙ";
+	let check = |limits| check_limits(s, &limits);
+
+	let v = check(TextLimits {
+		line_bytes: Some(10),
+		..Default::default()
+	});
+	assert_eq!(
+		v,
+		[
+			LimitViolation {
+				kind: LimitKind::LineBytes,
+				line: 1,
+				measured: 12,
+				limit: 10,
+				range: 15..18,
+			},
+			LimitViolation {
+				kind: LimitKind::LineBytes,
+				line: 2,
+				measured: 12,
+				limit: 10,
+				range: 29..31,
+			},
+		]
+	);
+	assert_eq!(&s[v[0].range.clone()], "」");
+	assert_eq!(&s[v[1].range.clone()], "i!");
+
+	let v = check(TextLimits {
+		line_columns: Some(11),
+		..Default::default()
+	});
+	assert_eq!(v.len(), 2);
+	assert_eq!(v[0].kind, LimitKind::LineColumns);
+	assert_eq!(&s[v[0].range.clone()], "」");
+	assert_eq!(&s[v[1].range.clone()], "!");
+
+	let v = check(TextLimits {
+		lines: Some(2),
+		..Default::default()
+	});
+	assert_eq!(
+		v,
+		[LimitViolation {
+			kind: LimitKind::Lines,
+			line: 3,
+			measured: 3,
+			limit: 2,
+			range: 32..s.len(),
+		}]
+	);
+	assert_eq!(&s[v[0].range.clone()], "ｴｽﾃﾙ");
+
+	let v = check(TextLimits {
+		total_bytes: Some(20),
+		..Default::default()
+	});
+	assert_eq!(
+		v,
+		[LimitViolation {
+			kind: LimitKind::TotalBytes,
+			line: 2,
+			measured: 30,
+			limit: 20,
+			range: 26..s.len(),
+		}]
+	);
+	assert_eq!(&s[v[0].range.clone()], ": Hi!\nｴｽﾃﾙ");
+}
+
+#[test]
+fn several_limits() {
+	// The wave dash is unencodable, but still counts as two bytes.
+	let s = "あ〜あ\nいいいい\r\nう\nえ";
+	let limits = TextLimits {
+		line_bytes: Some(6),
+		line_columns: None,
+		lines: Some(3),
+		total_bytes: Some(18),
+	};
+	let v = check_limits(s, &limits);
+	let kinds = v.iter().map(|v| (v.kind, v.line)).collect::<Vec<_>>();
+	assert_eq!(
+		kinds,
+		[
+			(LimitKind::LineBytes, 2),
+			(LimitKind::Lines, 4),
+			(LimitKind::TotalBytes, 3),
+		]
+	);
+	assert_eq!(&s[v[0].range.clone()], "い");
+	assert_eq!(&s[v[1].range.clone()], "え");
+	assert_eq!(v[2].measured, 22);
+	assert_eq!(&s[v[2].range.clone()], "う\nえ");
+}
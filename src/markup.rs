@@ -0,0 +1,414 @@
+//! Readable markup for control codes in script text.
+//!
+//! Falcom's script strings interleave text with control codes, some of which take arguments. The
+//! [`escape_controls`] function renders these as tags such as `{page}`, `{color 2}` or
+//! `{item 0x01F3}`, and [`unescape_controls`] turns the markup back into the original bytes.
+//!
+//! Bytes that are neither known control codes nor valid text are written as raw byte tags, such as
+//! `{0x05}`, so the markup is always lossless. A literal `{` is written as `{{`.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::{decode_char_from, decode_one, encode_char, EncodedChar};
+
+/// A set of named control codes, used with [`escape_controls`] and [`unescape_controls`].
+///
+/// Each control code is a single byte followed by a fixed number of argument bytes, which are
+/// shown as a single little-endian integer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlTable {
+	by_byte: HashMap<u8, (String, usize)>,
+	by_name: HashMap<String, u8>,
+}
+
+impl ControlTable {
+	/// Creates a table without any control codes.
+	pub fn new() -> Self {
+		ControlTable {
+			by_byte: HashMap::new(),
+			by_name: HashMap::new(),
+		}
+	}
+
+	/// Adds or replaces a control code.
+	///
+	/// # Panics
+	/// If the name is not alphanumeric (underscores allowed), starts with `0x`, or `args` is more
+	/// than 8.
+	pub fn insert(&mut self, byte: u8, name: &str, args: usize) {
+		assert!(
+			!name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_'),
+			"invalid control code name {name:?}",
+		);
+		assert!(
+			!name.starts_with("0x"),
+			"control code name {name:?} looks like a raw byte"
+		);
+		assert!(args <= 8, "too many argument bytes");
+		if let Some((old, _)) = self.by_byte.remove(&byte) {
+			self.by_name.remove(&old);
+		}
+		if let Some(old) = self.by_name.remove(name) {
+			self.by_byte.remove(&old);
+		}
+		self.by_byte.insert(byte, (name.to_owned(), args));
+		self.by_name.insert(name.to_owned(), byte);
+	}
+
+	/// Adds or replaces a control code, builder style.
+	pub fn with(mut self, byte: u8, name: &str, args: usize) -> Self {
+		self.insert(byte, name, args);
+		self
+	}
+}
+
+/// The common control codes of the *Trails in the Sky* games.
+impl Default for ControlTable {
+	fn default() -> Self {
+		ControlTable::new()
+			.with(0x01, "line", 0)
+			.with(0x02, "wait", 0)
+			.with(0x03, "page", 0)
+			.with(0x07, "color", 1)
+			.with(0x1F, "item", 2)
+	}
+}
+
+/// Decodes script text into markup.
+///
+/// Control codes in the table are written as tags, other bytes below `20` and anything that
+/// cannot be decoded (or would encode differently) as raw byte tags. A control code whose
+/// arguments are cut off by the end of the input is written as raw bytes.
+pub fn escape_controls(input: &[u8], table: &ControlTable) -> String {
+	let mut out = String::new();
+	let mut pos = 0;
+	while let Some(&b) = input.get(pos) {
+		if let Some((name, args)) = table.by_byte.get(&b) {
+			if let Some(arg) = input.get(pos + 1..pos + 1 + args) {
+				out.push('{');
+				out.push_str(name);
+				match args {
+					0 => {}
+					1 => write!(out, " {}", arg[0]).unwrap(),
+					_ => {
+						let mut v = [0; 8];
+						v[..*args].copy_from_slice(arg);
+						write!(
+							out,
+							" {:#0width$X}",
+							u64::from_le_bytes(v),
+							width = 2 + 2 * args
+						)
+						.unwrap();
+					}
+				}
+				out.push('}');
+				pos += 1 + args;
+				continue;
+			}
+		}
+		let mut len = 1;
+		let char = decode_char_from(b, || {
+			len = 2;
+			input.get(pos + 1).copied()
+		});
+		// Invalid sequences are resynchronized after the first byte, since the second one might be
+		// a control code.
+		if let Ok(c) = char {
+			let bytes = &input[pos..pos + len];
+			if b >= 0x20 && encode_char(c).as_deref() == Some(bytes) {
+				if c == '{' {
+					out.push('{');
+				}
+				out.push(c);
+			} else {
+				for b in bytes {
+					write!(out, "{{{:#04X}}}", b).unwrap();
+				}
+			}
+			pos += len;
+		} else {
+			write!(out, "{{{:#04X}}}", b).unwrap();
+			pos += 1;
+		}
+	}
+	out
+}
+
+/// A piece of script text, as produced by [`decode_with_controls`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextToken {
+	/// A run of decoded text.
+	Text(String),
+	/// A control code, including its argument bytes.
+	Control(Vec<u8>),
+	/// A byte sequence that could not be decoded.
+	Invalid(EncodedChar),
+}
+
+/// Decodes script text, passing through control codes as they are.
+///
+/// `control_lengths` maps each control byte to the number of argument bytes after it, which may
+/// be anything, including bytes that would otherwise be text. Other bytes are decoded as usual,
+/// with consecutive characters merged into one [`TextToken::Text`].
+///
+/// As with [`escape_controls`], invalid sequences are resynchronized after the first byte, which is
+/// the only byte their [`TextToken::Invalid`] holds. A control code whose arguments are cut off by the end of the input is given as
+/// [`TextToken::Invalid`], followed by whatever the arguments decode to.
+pub fn decode_with_controls(input: &[u8], control_lengths: &HashMap<u8, usize>) -> Vec<TextToken> {
+	let mut tokens = Vec::new();
+	let mut text = String::new();
+	let mut pos = 0;
+	while let Some(&b) = input.get(pos) {
+		let token = match control_lengths.get(&b) {
+			Some(&args) => match input.get(pos..pos + 1 + args) {
+				Some(control) => {
+					pos += control.len();
+					TextToken::Control(control.to_vec())
+				}
+				None => {
+					pos += 1;
+					TextToken::Invalid(EncodedChar::One([b]))
+				}
+			},
+			None => match decode_one(&input[pos..]).unwrap() {
+				(Ok(c), len) => {
+					text.push(c);
+					pos += len;
+					continue;
+				}
+				(Err(_), _) => {
+					pos += 1;
+					TextToken::Invalid(EncodedChar::One([b]))
+				}
+			},
+		};
+		if !text.is_empty() {
+			tokens.push(TextToken::Text(std::mem::take(&mut text)));
+		}
+		tokens.push(token);
+	}
+	if !text.is_empty() {
+		tokens.push(TextToken::Text(text));
+	}
+	tokens
+}
+
+/// Encodes markup produced by [`escape_controls`].
+pub fn unescape_controls(s: &str, table: &ControlTable) -> Result<Vec<u8>, UnescapeError> {
+	let mut out = Vec::new();
+	let mut pos = 0;
+	while let Some(char) = s[pos..].chars().next() {
+		let error = |kind| UnescapeError {
+			position: pos,
+			kind,
+		};
+		if char != '{' {
+			out.extend(encode_char(char).ok_or(error(UnescapeErrorKind::Unencodable(char)))?);
+			pos += char.len_utf8();
+			continue;
+		}
+		if s[pos + 1..].starts_with('{') {
+			out.push(b'{');
+			pos += 2;
+			continue;
+		}
+		let end = s[pos..]
+			.find('}')
+			.ok_or(error(UnescapeErrorKind::Malformed))?;
+		let tag = &s[pos + 1..pos + end];
+		if tag.contains('{') {
+			return Err(error(UnescapeErrorKind::Malformed));
+		}
+		let mut parts = tag.split(' ');
+		let name = parts.next().unwrap();
+		let arg = parts.next();
+		if parts.next().is_some() {
+			return Err(error(UnescapeErrorKind::Malformed));
+		}
+		if let Some(hex) = name.strip_prefix("0x") {
+			let byte = u8::from_str_radix(hex, 16)
+				.ok()
+				.filter(|_| arg.is_none() && !hex.starts_with('+'));
+			out.push(byte.ok_or(error(UnescapeErrorKind::Malformed))?);
+		} else {
+			let &byte = table
+				.by_name
+				.get(name)
+				.ok_or(error(UnescapeErrorKind::UnknownTag))?;
+			let args = table.by_byte[&byte].1;
+			let value = match (args, arg) {
+				(0, None) => 0,
+				(1.., Some(arg)) => parse_int(arg).ok_or(error(UnescapeErrorKind::Malformed))?,
+				_ => return Err(error(UnescapeErrorKind::Malformed)),
+			};
+			if args < 8 && value >> (8 * args) != 0 {
+				return Err(error(UnescapeErrorKind::Malformed));
+			}
+			out.push(byte);
+			out.extend_from_slice(&value.to_le_bytes()[..args]);
+		}
+		pos += end + 1;
+	}
+	Ok(out)
+}
+
+fn parse_int(s: &str) -> Option<u64> {
+	if s.starts_with('+') {
+		return None;
+	}
+	match s.strip_prefix("0x") {
+		Some(hex) if !hex.starts_with('+') => u64::from_str_radix(hex, 16).ok(),
+		Some(_) => None,
+		None => s.parse().ok(),
+	}
+}
+
+/// An error from [`unescape_controls`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UnescapeError {
+	/// The UTF-8 offset of the offending tag or character.
+	pub position: usize,
+	/// What was wrong with it.
+	pub kind: UnescapeErrorKind,
+}
+
+/// The reason for an [`UnescapeError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnescapeErrorKind {
+	/// The tag was not in the control table.
+	UnknownTag,
+	/// A tag was unclosed, or had the wrong number of arguments or an invalid argument.
+	Malformed,
+	/// The character cannot be encoded.
+	Unencodable(char),
+}
+
+impl fmt::Display for UnescapeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.kind {
+			UnescapeErrorKind::UnknownTag => write!(f, "unknown tag")?,
+			UnescapeErrorKind::Malformed => write!(f, "malformed tag")?,
+			UnescapeErrorKind::Unencodable(c) => write!(f, "cannot encode {c:?}")?,
+		}
+		write!(f, " at position {}", self.position)
+	}
+}
+
+impl std::error::Error for UnescapeError {}
+
+#[test]
+fn escape_fixtures() {
+	let table = ControlTable::default();
+	let fixtures: &[(&[u8], &str)] = &[
+		(
+			b"\x07\x02\x93\xFA\x96\x7B\x07\x00\x01",
+			"{color 2}日本{color 0}{line}",
+		),
+		(b"\x1F\xF3\x01\x02\x03", "{item 0x01F3}{wait}{page}"),
+		(b"{a}\x05\x81\x20\x1F\x01", "{{a}{0x05}{0x81} {0x1F}{line}"),
+		(b"\x87\x90\x81\xE0", "{0x87}{0x90}≒"),
+		(b"", ""),
+	];
+	for &(bytes, markup) in fixtures {
+		assert_eq!(escape_controls(bytes, &table), markup);
+		assert_eq!(unescape_controls(markup, &table).as_deref(), Ok(bytes));
+	}
+}
+
+#[test]
+fn escape_roundtrip() {
+	let tables = [
+		ControlTable::default(),
+		ControlTable::new(),
+		ControlTable::new().with(0x02, "w", 8).with(0x41, "A", 0),
+	];
+	let mut state = 1u32;
+	for table in &tables {
+		for len in 0..200 {
+			let bytes = (0..len)
+				.map(|_| {
+					state = state.wrapping_mul(1103515245).wrapping_add(12345);
+					let b = (state >> 16) as u8;
+					if b & 3 == 0 {
+						b & 0x1F
+					} else {
+						b
+					}
+				})
+				.collect::<Vec<_>>();
+			let markup = escape_controls(&bytes, table);
+			assert_eq!(unescape_controls(&markup, table), Ok(bytes), "{markup}");
+		}
+	}
+}
+
+#[test]
+fn unescape_errors() {
+	let table = ControlTable::default();
+	let err = |s, position, kind| {
+		assert_eq!(
+			unescape_controls(s, &table),
+			Err(UnescapeError { position, kind })
+		)
+	};
+	err("日{bogus}", 3, UnescapeErrorKind::UnknownTag);
+	err("a{line", 1, UnescapeErrorKind::Malformed);
+	err("a{li{ne}", 1, UnescapeErrorKind::Malformed);
+	err("{line 1}", 0, UnescapeErrorKind::Malformed);
+	err("{color}", 0, UnescapeErrorKind::Malformed);
+	err("{color 256}", 0, UnescapeErrorKind::Malformed);
+	err("{color x}", 0, UnescapeErrorKind::Malformed);
+	err("{color 1 2}", 0, UnescapeErrorKind::Malformed);
+	err("{0x100}", 0, UnescapeErrorKind::Malformed);
+	err("{0x1 2}", 0, UnescapeErrorKind::Malformed);
+	err("{}", 0, UnescapeErrorKind::UnknownTag);
+	err("ab₂", 2, UnescapeErrorKind::Unencodable('₂'));
+	assert_eq!(
+		unescape_controls("}{color 0x0A}{item 7}", &table).as_deref(),
+		Ok(&b"}\x07\x0A\x1F\x07\x00"[..])
+	);
+}
+
+#[test]
+fn table_override() {
+	let table = ControlTable::default()
+		.with(0x07, "colour", 1)
+		.with(0x01, "nl", 0);
+	assert_eq!(escape_controls(b"\x07\x01\x01", &table), "{colour 1}{nl}");
+	assert_eq!(
+		unescape_controls("{color 1}", &table).unwrap_err().kind,
+		UnescapeErrorKind::UnknownTag
+	);
+	assert_eq!(escape_controls(b"\x01", &ControlTable::new()), "{0x01}");
+}
+
+#[test]
+fn with_controls() {
+	let lengths = HashMap::from([(0x01, 0), (0x07, 1), (0x1F, 2)]);
+	let input = b"\x07\x02\x82\xA0\x82\xA2\x01\x1F\x93\xFAA\x80B\x07";
+	assert_eq!(
+		decode_with_controls(input, &lengths),
+		[
+			TextToken::Control(vec![0x07, 0x02]),
+			TextToken::Text("あい".into()),
+			TextToken::Control(vec![0x01]),
+			TextToken::Control(vec![0x1F, 0x93, 0xFA]),
+			TextToken::Text("A".into()),
+			TextToken::Invalid(EncodedChar::One([0x80])),
+			TextToken::Text("B".into()),
+			TextToken::Invalid(EncodedChar::One([0x07])),
+		]
+	);
+	// An invalid pair is resynchronized after its first byte, in case the second is a control.
+	assert_eq!(
+		decode_with_controls(b"\xF0\x01x", &lengths),
+		[
+			TextToken::Invalid(EncodedChar::One([0xF0])),
+			TextToken::Control(vec![0x01]),
+			TextToken::Text("x".into()),
+		]
+	);
+	assert_eq!(decode_with_controls(b"", &lengths), []);
+}
@@ -0,0 +1,82 @@
+//! Conversion of file names.
+//!
+//! These are thin wrappers around [`crate::decode`] and [`crate::encode`], going through `str`.
+//! On Windows an [`OsStr`] is WTF-16, and elsewhere it is an arbitrary byte string, so in both
+//! cases it may contain parts that are not valid Unicode, such as unpaired surrogates or invalid
+//! UTF-8. Such parts cannot be encoded as Shift JIS.
+
+use std::ffi::{OsStr, OsString};
+
+use crate::{decode, decode_lossy, encode, encode_lossy, DecodeError, EncodeError};
+
+/// Decodes a file name.
+pub fn decode_os_string(input: &[u8]) -> Result<OsString, DecodeError> {
+	Ok(decode(input)?.into())
+}
+
+/// Decodes a file name, lossily, as per [`crate::decode_lossy`].
+pub fn decode_os_string_lossy(input: &[u8]) -> OsString {
+	decode_lossy(input).into()
+}
+
+/// Encodes a file name.
+///
+/// If the name is not valid Unicode, the error's position is the offset of the first invalid part
+/// in [`OsStr::as_encoded_bytes`], and its char is U+FFFD REPLACEMENT CHARACTER.
+pub fn encode_os_str(s: &OsStr) -> Result<Vec<u8>, EncodeError> {
+	let bytes = s.as_encoded_bytes();
+	let str = std::str::from_utf8(bytes).map_err(|e| EncodeError {
+		position: e.valid_up_to(),
+		char: char::REPLACEMENT_CHARACTER,
+	})?;
+	encode(str).map_err(|pos| EncodeError::at(str, pos))
+}
+
+/// Encodes a file name, lossily.
+///
+/// Parts that are not valid Unicode are replaced as per [`OsStr::to_string_lossy`], and then
+/// encoded as per [`crate::encode_lossy`].
+pub fn encode_os_str_lossy(s: &OsStr) -> Vec<u8> {
+	encode_lossy(&s.to_string_lossy())
+}
+
+#[test]
+fn roundtrip() {
+	let bytes = crate::encode("セーブ/データ01.sav").unwrap();
+	let name = decode_os_string(&bytes).unwrap();
+	assert_eq!(name, OsStr::new("セーブ/データ01.sav"));
+	assert_eq!(encode_os_str(&name), Ok(bytes.clone()));
+	assert_eq!(encode_os_str_lossy(&name), bytes);
+	assert_eq!(
+		std::path::PathBuf::from(decode_os_string_lossy(&bytes)),
+		std::path::Path::new("セーブ").join("データ01.sav")
+	);
+}
+
+#[test]
+fn errors() {
+	assert_eq!(decode_os_string(b"a\x80").unwrap_err().position, 1);
+	assert_eq!(decode_os_string_lossy(b"a\x80"), OsStr::new("a�"));
+	assert_eq!(
+		encode_os_str(OsStr::new("a₂")),
+		Err(EncodeError {
+			position: 1,
+			char: '₂'
+		})
+	);
+}
+
+#[cfg(unix)]
+#[test]
+fn not_unicode() {
+	use std::os::unix::ffi::OsStrExt;
+	let name = OsStr::from_bytes(b"ab\xFFc");
+	assert_eq!(
+		encode_os_str(name),
+		Err(EncodeError {
+			position: 2,
+			char: char::REPLACEMENT_CHARACTER
+		})
+	);
+	assert_eq!(encode_os_str_lossy(name), b"ab\x81\x45c");
+}
@@ -0,0 +1,547 @@
+//! Helpers for strings embedded in binary records.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::sink::{self, SinkError};
+use crate::{decode, decode_lossy, encode, encode_char, DecodeError, EncodeError};
+
+/// Reads a string prefixed with its length in bytes, as a little-endian `u16`.
+///
+/// I/O errors, including running out of input, are returned in the outer `Result`, while decoding
+/// errors are returned in the inner one. Either way, the whole record is consumed.
+pub fn read_length_prefixed<R: Read>(r: &mut R) -> io::Result<Result<String, DecodeError>> {
+	let mut len = [0; 2];
+	r.read_exact(&mut len)?;
+	let mut buf = vec![0; u16::from_le_bytes(len) as usize];
+	r.read_exact(&mut buf)?;
+	Ok(decode(&buf).map_err(DecodeError::from))
+}
+
+/// Writes a string prefixed with its length in bytes, as a little-endian `u16`.
+///
+/// If the string cannot be encoded, nothing is written and the inner `Result` is an error. If the
+/// encoded string is longer than `u16::MAX` bytes, an [`io::ErrorKind::InvalidInput`] is returned.
+pub fn write_length_prefixed<W: Write>(w: &mut W, s: &str) -> io::Result<Result<(), EncodeError>> {
+	let buf = match encode(s) {
+		Ok(buf) => buf,
+		Err(pos) => return Ok(Err(EncodeError::at(s, pos))),
+	};
+	let len = u16::try_from(buf.len())
+		.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "string too long"))?;
+	w.write_all(&len.to_le_bytes())?;
+	w.write_all(&buf)?;
+	Ok(Ok(()))
+}
+
+/// An error from [`encode_fixed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FixedError {
+	/// The string could not be encoded.
+	Encode(EncodeError),
+	/// The encoded string is longer than the field.
+	TooLong {
+		/// The length of the encoded string.
+		len: usize,
+		/// The width of the field.
+		width: usize,
+	},
+}
+
+impl fmt::Display for FixedError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			FixedError::Encode(e) => e.fmt(f),
+			FixedError::TooLong { len, width } => {
+				write!(f, "encoded length {len} exceeds field width {width}")
+			}
+		}
+	}
+}
+
+impl std::error::Error for FixedError {}
+
+/// Encodes a string into a fixed-width field, padded with `pad`.
+pub fn encode_fixed(s: &str, width: usize, pad: u8) -> Result<Vec<u8>, FixedError> {
+	let mut buf = encode(s).map_err(|pos| FixedError::Encode(EncodeError::at(s, pos)))?;
+	if buf.len() > width {
+		return Err(FixedError::TooLong {
+			len: buf.len(),
+			width,
+		});
+	}
+	buf.resize(width, pad);
+	Ok(buf)
+}
+
+/// Like [`encode_fixed`], but cuts off strings that are too long.
+///
+/// The cut is made between characters, so if a two-byte character would only partly fit, it is
+/// replaced with padding too. Characters after the cut are not checked for encodability.
+pub fn encode_fixed_truncate(s: &str, width: usize, pad: u8) -> Result<Vec<u8>, EncodeError> {
+	let mut buf = Vec::with_capacity(width);
+	for (pos, char) in s.char_indices() {
+		if buf.len() == width {
+			break;
+		}
+		let enc = encode_char(char).ok_or(EncodeError::at(s, pos))?;
+		if buf.len() + enc.len() > width {
+			break;
+		}
+		buf.extend(enc);
+	}
+	buf.resize(width, pad);
+	Ok(buf)
+}
+
+/// The size of a little-endian length prefix, for [`decode_prefixed`] and [`encode_prefixed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrefixWidth {
+	U8,
+	U16,
+	U32,
+}
+
+impl PrefixWidth {
+	fn size(self) -> usize {
+		match self {
+			PrefixWidth::U8 => 1,
+			PrefixWidth::U16 => 2,
+			PrefixWidth::U32 => 4,
+		}
+	}
+
+	fn max(self) -> usize {
+		match self {
+			PrefixWidth::U8 => u8::MAX as usize,
+			PrefixWidth::U16 => u16::MAX as usize,
+			PrefixWidth::U32 => u32::MAX as usize,
+		}
+	}
+}
+
+/// An error from [`decode_prefixed`] or [`encode_prefixed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrefixedError {
+	/// The input ends before the end of the prefix or string.
+	Truncated,
+	/// The length includes a NUL terminator, but the string does not end with one.
+	MissingNul,
+	/// The encoded string is too long for the prefix.
+	TooLong {
+		/// The length that would be written.
+		len: usize,
+	},
+	/// The string could not be decoded. The position is an offset in the whole input.
+	Decode(DecodeError),
+	/// The string could not be encoded.
+	Encode(EncodeError),
+}
+
+impl fmt::Display for PrefixedError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			PrefixedError::Truncated => write!(f, "length-prefixed string is truncated"),
+			PrefixedError::MissingNul => write!(f, "length-prefixed string lacks NUL terminator"),
+			PrefixedError::TooLong { len } => write!(f, "length {len} does not fit in prefix"),
+			PrefixedError::Decode(e) => e.fmt(f),
+			PrefixedError::Encode(e) => e.fmt(f),
+		}
+	}
+}
+
+impl std::error::Error for PrefixedError {}
+
+/// Decodes a string at the start of the input, prefixed with its length in bytes.
+///
+/// If `nul` is set, the length includes a NUL terminator, which is not part of the returned
+/// string. Returns the string and the number of bytes consumed, including the prefix.
+pub fn decode_prefixed(
+	input: &[u8],
+	width: PrefixWidth,
+	nul: bool,
+) -> Result<(String, usize), PrefixedError> {
+	let size = width.size();
+	let prefix = input.get(..size).ok_or(PrefixedError::Truncated)?;
+	let mut len = [0; 8];
+	len[..size].copy_from_slice(prefix);
+	let len = usize::try_from(u64::from_le_bytes(len)).map_err(|_| PrefixedError::Truncated)?;
+	let end = size.checked_add(len).ok_or(PrefixedError::Truncated)?;
+	let mut bytes = input.get(size..end).ok_or(PrefixedError::Truncated)?;
+	if nul {
+		bytes = bytes.strip_suffix(&[0]).ok_or(PrefixedError::MissingNul)?;
+	}
+	let s = decode(bytes).map_err(|(pos, bytes)| {
+		PrefixedError::Decode(DecodeError {
+			position: size + pos,
+			bytes,
+		})
+	})?;
+	Ok((s, end))
+}
+
+/// Encodes a string prefixed with its length in bytes, as read by [`decode_prefixed`].
+pub fn encode_prefixed(s: &str, width: PrefixWidth, nul: bool) -> Result<Vec<u8>, PrefixedError> {
+	let bytes = encode(s).map_err(|pos| PrefixedError::Encode(EncodeError::at(s, pos)))?;
+	let len = bytes.len() + nul as usize;
+	if len > width.max() {
+		return Err(PrefixedError::TooLong { len });
+	}
+	let mut out = Vec::with_capacity(width.size() + len);
+	out.extend_from_slice(&(len as u64).to_le_bytes()[..width.size()]);
+	out.extend(bytes);
+	if nul {
+		out.push(0);
+	}
+	Ok(out)
+}
+
+/// What to do with bytes after the last NUL in [`decode_string_table`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Unterminated {
+	/// Treat them as an error.
+	#[default]
+	Error,
+	/// Treat them as a final entry.
+	Entry,
+}
+
+/// An error from [`decode_string_table`], saying which entry failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StringTableError {
+	/// The last entry has no NUL terminator.
+	Unterminated {
+		/// The index of the entry.
+		index: usize,
+		/// The offset of the entry in the input.
+		offset: usize,
+	},
+	/// An entry could not be decoded. The position is an offset in the whole input.
+	Decode {
+		/// The index of the entry.
+		index: usize,
+		/// The offset of the entry in the input.
+		offset: usize,
+		/// The error.
+		error: DecodeError,
+	},
+}
+
+impl fmt::Display for StringTableError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			StringTableError::Unterminated { index, offset } => {
+				write!(f, "entry {index} at {offset} lacks NUL terminator")
+			}
+			StringTableError::Decode {
+				index,
+				offset,
+				error,
+			} => write!(f, "entry {index} at {offset}: {error}"),
+		}
+	}
+}
+
+impl std::error::Error for StringTableError {}
+
+/// Splits a blob of back-to-back NUL-terminated strings into entries, with their offsets.
+///
+/// Consecutive NULs give empty entries. Empty input gives no entries. Since NUL is never a trail
+/// byte, a two-byte character cannot hide a terminator.
+fn string_table_entries(input: &[u8]) -> impl Iterator<Item = (usize, &[u8], bool)> {
+	let mut pos = 0;
+	std::iter::from_fn(move || {
+		let rest = input.get(pos..).filter(|r| !r.is_empty())?;
+		let start = pos;
+		match rest.iter().position(|&b| b == 0) {
+			Some(len) => {
+				pos += len + 1;
+				Some((start, &rest[..len], true))
+			}
+			None => {
+				pos = input.len();
+				Some((start, rest, false))
+			}
+		}
+	})
+}
+
+/// Decodes a string table: a blob of back-to-back NUL-terminated strings.
+///
+/// Each entry is returned with the offset of its first byte, for matching against pointer tables.
+/// `unterminated` says what to do with bytes after the last NUL.
+pub fn decode_string_table(
+	input: &[u8],
+	unterminated: Unterminated,
+) -> Result<Vec<(usize, String)>, StringTableError> {
+	let mut out = Vec::new();
+	for (index, (offset, bytes, terminated)) in string_table_entries(input).enumerate() {
+		if !terminated && unterminated == Unterminated::Error {
+			return Err(StringTableError::Unterminated { index, offset });
+		}
+		let s = decode(bytes).map_err(|(pos, bytes)| StringTableError::Decode {
+			index,
+			offset,
+			error: DecodeError {
+				position: offset + pos,
+				bytes,
+			},
+		})?;
+		out.push((offset, s));
+	}
+	Ok(out)
+}
+
+/// Like [`decode_string_table`], but decodes each entry as per [`decode_lossy`].
+///
+/// Bytes after the last NUL are always treated as a final entry.
+pub fn decode_string_table_lossy(input: &[u8]) -> Vec<(usize, String)> {
+	string_table_entries(input)
+		.map(|(offset, bytes, _)| (offset, decode_lossy(bytes)))
+		.collect()
+}
+
+/// Encodes strings as a string table, as read by [`decode_string_table`].
+///
+/// Returns the blob and the offset of each entry. The error position is the UTF-8 offset in the
+/// entries joined with NUL, as with [`crate::encode_join`]. A NUL within an entry is an error,
+/// since it would split the entry in two.
+pub fn encode_string_table(entries: &[&str]) -> Result<(Vec<u8>, Vec<usize>), EncodeError> {
+	let mut out = Vec::new();
+	let mut offsets = Vec::with_capacity(entries.len());
+	let mut start = 0;
+	for entry in entries {
+		offsets.push(out.len());
+		let encode_char = |char| encode_char(char).filter(|_| char != '\0');
+		match sink::encode_into(&mut out, entry, encode_char, |_, _| None) {
+			Ok(()) => {}
+			Err(SinkError::Unencodable(pos)) => {
+				return Err(EncodeError {
+					position: start + pos,
+					..EncodeError::at(entry, pos)
+				});
+			}
+			Err(SinkError::Sink(_, e)) => match e {},
+		}
+		out.push(0);
+		start += entry.len() + 1;
+	}
+	Ok((out, offsets))
+}
+
+#[test]
+fn length_prefixed() {
+	let mut buf = Vec::new();
+	write_length_prefixed(&mut buf, "日本").unwrap().unwrap();
+	write_length_prefixed(&mut buf, "").unwrap().unwrap();
+	assert_eq!(buf, [4, 0, 0x93, 0xFA, 0x96, 0x7B, 0, 0]);
+	assert_eq!(
+		write_length_prefixed(&mut buf, "日₂").unwrap(),
+		Err(EncodeError {
+			position: 3,
+			char: '₂'
+		}),
+	);
+	assert_eq!(buf.len(), 8);
+	let long = "a".repeat(0x10000);
+	let err = write_length_prefixed(&mut buf, &long).unwrap_err();
+	assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+	buf.extend([3, 0, 0x41, 0x81, 0x20, 2, 0, 0x41]);
+	let r = &mut &buf[..];
+	assert_eq!(read_length_prefixed(r).unwrap().as_deref(), Ok("日本"));
+	assert_eq!(read_length_prefixed(r).unwrap().as_deref(), Ok(""));
+	assert_eq!(
+		read_length_prefixed(r).unwrap(),
+		Err(DecodeError {
+			position: 1,
+			bytes: crate::EncodedChar::Two([0x81, 0x20])
+		}),
+	);
+	let err = read_length_prefixed(r).unwrap_err();
+	assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn fixed() {
+	assert_eq!(encode_fixed("日本", 6, 0).unwrap(), b"\x93\xFA\x96\x7B\0\0");
+	assert_eq!(encode_fixed("日本", 4, 0).unwrap(), b"\x93\xFA\x96\x7B");
+	assert_eq!(encode_fixed("", 2, b' ').unwrap(), b"  ");
+	assert_eq!(
+		encode_fixed("日本", 3, 0),
+		Err(FixedError::TooLong { len: 4, width: 3 })
+	);
+	assert_eq!(
+		encode_fixed("a₂", 8, 0),
+		Err(FixedError::Encode(EncodeError {
+			position: 1,
+			char: '₂'
+		}))
+	);
+
+	assert_eq!(encode_fixed_truncate("日本", 3, 0).unwrap(), b"\x93\xFA\0");
+	assert_eq!(
+		encode_fixed_truncate("a日本", 4, b' ').unwrap(),
+		b"a\x93\xFA "
+	);
+	assert_eq!(encode_fixed_truncate("ab₂", 2, 0).unwrap(), b"ab");
+	assert_eq!(
+		encode_fixed_truncate("a₂", 8, 0),
+		Err(EncodeError {
+			position: 1,
+			char: '₂'
+		})
+	);
+}
+
+#[test]
+fn prefixed() {
+	use PrefixWidth::*;
+	let cases: &[(PrefixWidth, bool, &[u8])] = &[
+		(U8, false, b"\x02\x93\xFA"),
+		(U16, false, b"\x02\x00\x93\xFA"),
+		(U32, false, b"\x02\x00\x00\x00\x93\xFA"),
+		(U8, true, b"\x03\x93\xFA\x00"),
+		(U32, true, b"\x03\x00\x00\x00\x93\xFA\x00"),
+	];
+	for &(width, nul, bytes) in cases {
+		assert_eq!(encode_prefixed("日", width, nul).as_deref(), Ok(bytes));
+		assert_eq!(
+			decode_prefixed(bytes, width, nul),
+			Ok(("日".to_owned(), bytes.len()))
+		);
+	}
+
+	let mut buf = Vec::new();
+	for s in ["日本", "", "ABC"] {
+		buf.extend(encode_prefixed(s, U16, true).unwrap());
+	}
+	let mut pos = 0;
+	let mut strings = Vec::new();
+	while pos < buf.len() {
+		let (s, len) = decode_prefixed(&buf[pos..], U16, true).unwrap();
+		strings.push(s);
+		pos += len;
+	}
+	assert_eq!(strings, ["日本", "", "ABC"]);
+
+	assert_eq!(
+		decode_prefixed(b"\x05ab", U8, false),
+		Err(PrefixedError::Truncated)
+	);
+	assert_eq!(
+		decode_prefixed(b"\x01", U16, false),
+		Err(PrefixedError::Truncated)
+	);
+	assert_eq!(
+		decode_prefixed(b"\xFF\xFF\xFF\xFF", U32, false),
+		Err(PrefixedError::Truncated)
+	);
+	assert_eq!(
+		decode_prefixed(b"\x02ab", U8, true),
+		Err(PrefixedError::MissingNul)
+	);
+	assert_eq!(
+		decode_prefixed(b"\x00", U8, true),
+		Err(PrefixedError::MissingNul)
+	);
+	assert_eq!(
+		decode_prefixed(b"\x02\x00a\x80", U16, false),
+		Err(PrefixedError::Decode(DecodeError {
+			position: 3,
+			bytes: crate::EncodedChar::One([0x80])
+		}))
+	);
+	let long = "日".repeat(128);
+	assert_eq!(
+		encode_prefixed(&long, U8, false),
+		Err(PrefixedError::TooLong { len: 256 })
+	);
+	let max = format!("{}a", &long[3..]);
+	assert_eq!(encode_prefixed(&max, U8, false).unwrap().len(), 256);
+	assert_eq!(
+		encode_prefixed(&max, U8, true),
+		Err(PrefixedError::TooLong { len: 256 })
+	);
+	assert!(matches!(
+		encode_prefixed("₂", U8, false),
+		Err(PrefixedError::Encode(_))
+	));
+}
+
+#[test]
+fn string_table() {
+	let entries = ["日本", "", "", "ABC", "英雄伝説"];
+	let (blob, offsets) = encode_string_table(&entries).unwrap();
+	assert_eq!(&blob[..11], b"\x93\xFA\x96\x7B\0\0\0ABC\0");
+	assert_eq!(offsets, [0, 5, 6, 7, 11]);
+	let decoded = decode_string_table(&blob, Unterminated::Error).unwrap();
+	assert_eq!(decoded.len(), entries.len());
+	for ((offset, s), (&pointer, entry)) in decoded.iter().zip(offsets.iter().zip(entries)) {
+		assert_eq!((*offset, s.as_str()), (pointer, entry));
+		let end = blob[pointer..].iter().position(|&b| b == 0).unwrap();
+		assert_eq!(
+			crate::decode(&blob[pointer..pointer + end]).as_deref(),
+			Ok(entry)
+		);
+	}
+	assert_eq!(decode_string_table_lossy(&blob), decoded);
+	assert_eq!(decode_string_table(b"", Unterminated::Error), Ok(vec![]));
+	assert_eq!(encode_string_table(&[]), Ok((vec![], vec![])));
+}
+
+#[test]
+fn string_table_unterminated() {
+	let blob = b"ab\0\0cd";
+	assert_eq!(
+		decode_string_table(blob, Unterminated::Error),
+		Err(StringTableError::Unterminated {
+			index: 2,
+			offset: 4
+		})
+	);
+	let entries = decode_string_table(blob, Unterminated::Entry).unwrap();
+	assert_eq!(
+		entries,
+		[
+			(0, "ab".to_owned()),
+			(3, "".to_owned()),
+			(4, "cd".to_owned())
+		]
+	);
+	assert_eq!(decode_string_table_lossy(blob), entries);
+}
+
+#[test]
+fn string_table_errors() {
+	assert_eq!(
+		decode_string_table(b"a\0b\x80\0", Unterminated::Error),
+		Err(StringTableError::Decode {
+			index: 1,
+			offset: 2,
+			error: DecodeError {
+				position: 3,
+				bytes: crate::EncodedChar::One([0x80])
+			}
+		})
+	);
+	// A lead byte before the terminator does not swallow it.
+	assert_eq!(
+		decode_string_table_lossy(b"\x93\0a\0"),
+		[(0, "�".to_owned()), (2, "a".to_owned())]
+	);
+	assert_eq!(
+		encode_string_table(&["ab", "c₂"]),
+		Err(EncodeError {
+			position: 4,
+			char: '₂'
+		})
+	);
+	assert_eq!(
+		encode_string_table(&["a", "b\0c"]),
+		Err(EncodeError {
+			position: 3,
+			char: '\0'
+		})
+	);
+}
@@ -0,0 +1,466 @@
+//! Splitting encoded strings into text and control codes, fields, or sentences, and finding text
+//! in binary data.
+
+use std::ops::Range;
+
+use crate::class::{is_lead_byte, is_trail_byte};
+use crate::sink::{self, SinkError};
+use crate::{decode, decode_one, decode_units, encode_char, DecodeError};
+
+/// A run of text or control bytes, as produced by [`segments`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Segment<'a> {
+	/// Bytes to be decoded as text. These may still contain invalid sequences.
+	Text { pos: usize, bytes: &'a [u8] },
+	/// Control bytes.
+	Control { pos: usize, bytes: &'a [u8] },
+}
+
+impl<'a> Segment<'a> {
+	/// The bytes of the segment.
+	pub fn bytes(&self) -> &'a [u8] {
+		match *self {
+			Segment::Text { bytes, .. } | Segment::Control { bytes, .. } => bytes,
+		}
+	}
+
+	/// The byte range of the segment in the input.
+	pub fn range(&self) -> Range<usize> {
+		match *self {
+			Segment::Text { pos, bytes } | Segment::Control { pos, bytes } => {
+				pos..pos + bytes.len()
+			}
+		}
+	}
+}
+
+/// Splits the input into maximal runs of text and control bytes, where control bytes are
+/// `00`–`1F`.
+///
+/// See [`segments_with`] for details.
+pub fn segments(input: &[u8]) -> impl Iterator<Item = Segment<'_>> {
+	segments_with(input, |b| b < 0x20)
+}
+
+/// Splits the input into maximal runs of text and control bytes, as determined by `is_control`.
+///
+/// A two-byte character is always kept whole in a text segment, even if its trail byte would be
+/// considered a control byte on its own. With the default control set this cannot happen, since
+/// trail bytes are never below `40`: a lead byte followed by a control byte is instead an invalid
+/// lone lead byte at the end of a text segment.
+pub fn segments_with(
+	input: &[u8],
+	is_control: impl Fn(u8) -> bool,
+) -> impl Iterator<Item = Segment<'_>> {
+	let mut pos = 0;
+	std::iter::from_fn(move || {
+		let start = pos;
+		let control = is_control(*input.get(pos)?);
+		while let Some(&b) = input.get(pos) {
+			if is_control(b) != control {
+				break;
+			}
+			pos += 1;
+			let lead = is_lead_byte(b);
+			if !control && lead && input.get(pos).is_some_and(|&b| is_trail_byte(b)) {
+				pos += 1;
+			}
+		}
+		let bytes = &input[start..pos];
+		Some(if control {
+			Segment::Control { pos: start, bytes }
+		} else {
+			Segment::Text { pos: start, bytes }
+		})
+	})
+}
+
+/// Splits the input on a delimiter byte, and decodes each field.
+///
+/// As with [`segments_with`], a delimiter byte that is the trail byte of a two-byte character does
+/// not split it. Delimiters below `40`, such as tab and comma, can never be trail bytes.
+///
+/// Like [`str::split`], this yields one more field than there are delimiters, so empty input gives
+/// a single empty field. Error positions are offsets in the whole input.
+pub fn decode_split(
+	input: &[u8],
+	delim: u8,
+) -> impl Iterator<Item = Result<String, DecodeError>> + '_ {
+	let mut pos = 0;
+	let mut done = false;
+	std::iter::from_fn(move || {
+		if done {
+			return None;
+		}
+		let start = pos;
+		while let Some(&b) = input.get(pos) {
+			if b == delim {
+				break;
+			}
+			pos += 1;
+			let lead = is_lead_byte(b);
+			if lead && input.get(pos).is_some_and(|&b| is_trail_byte(b)) {
+				pos += 1;
+			}
+		}
+		let field = &input[start..pos];
+		if pos < input.len() {
+			pos += 1;
+		} else {
+			done = true;
+		}
+		Some(decode(field).map_err(|(p, bytes)| DecodeError {
+			position: start + p,
+			bytes,
+		}))
+	})
+}
+
+/// Encodes fields and joins them with a delimiter byte, the reverse of [`decode_split`].
+///
+/// The error position is the UTF-8 offset in the fields joined with the delimiter, so that this
+/// gives the same result as [`crate::encode`] on the joined string.
+///
+/// # Panics
+/// If the delimiter is not ASCII.
+pub fn encode_join<'a>(
+	fields: impl IntoIterator<Item = &'a str>,
+	delim: u8,
+) -> Result<Vec<u8>, usize> {
+	assert!(delim.is_ascii(), "delimiter must be ASCII");
+	let mut out = Vec::new();
+	let mut offset = 0;
+	for (i, field) in fields.into_iter().enumerate() {
+		if i != 0 {
+			out.push(delim);
+			offset += 1;
+		}
+		match sink::encode_into(&mut out, field, encode_char, |_, _| None) {
+			Ok(()) => {}
+			Err(SinkError::Unencodable(pos)) => return Err(offset + pos),
+			Err(SinkError::Sink(_, e)) => match e {},
+		}
+		offset += field.len();
+	}
+	Ok(out)
+}
+
+/// Finds runs of printable text in binary data, like the Unix `strings` command.
+///
+/// A run is a maximal sequence of characters that decode validly and are not control characters,
+/// except for tab. Runs of fewer than `min_len` characters are skipped. Each run is returned with
+/// its byte offset.
+///
+/// An invalid sequence ends the run before it, and scanning resumes at its second byte, so that a
+/// run starting right after a stray lead byte is still found.
+pub fn extract_strings(input: &[u8], min_len: usize) -> Vec<(usize, String)> {
+	let mut out = Vec::new();
+	let mut run = String::new();
+	let (mut start, mut chars) = (0, 0);
+	let mut pos = 0;
+	while pos <= input.len() {
+		let printable = match decode_one(&input[pos..]) {
+			Some((Ok(char), len)) if !char.is_control() || char == '\t' => Some((char, len)),
+			_ => None,
+		};
+		match printable {
+			Some((char, len)) => {
+				if chars == 0 {
+					start = pos;
+				}
+				run.push(char);
+				chars += 1;
+				pos += len;
+			}
+			None => {
+				if chars != 0 && chars >= min_len {
+					out.push((start, std::mem::take(&mut run)));
+				}
+				run.clear();
+				chars = 0;
+				pos += 1;
+			}
+		}
+	}
+	out
+}
+
+/// Whether a character ends a sentence by default: `。！？` and their half-width forms.
+fn is_terminator(c: char) -> bool {
+	matches!(c, '。' | '！' | '？' | '｡' | '!' | '?')
+}
+
+/// Whether a character closes a quote or bracket by default, and so stays with a sentence that
+/// ends inside it.
+fn is_closer(c: char) -> bool {
+	matches!(c, '」' | '』' | '）' | '】' | '｣' | ')')
+}
+
+/// Splits a sequence of characters with byte ranges into sentences, returning the end of each.
+fn sentence_ends(
+	mut units: impl Iterator<Item = (Range<usize>, Option<char>)>,
+	is_terminator: impl Fn(char) -> bool,
+	is_closer: impl Fn(char) -> bool,
+) -> impl Iterator<Item = usize> {
+	let mut end = None;
+	let mut pending = None;
+	std::iter::from_fn(move || {
+		let mut ended = false;
+		while let Some((range, c)) = pending.take().or_else(|| units.next()) {
+			match c {
+				Some(c) if is_terminator(c) => ended = true,
+				Some(c) if ended && is_closer(c) => {}
+				_ if ended => {
+					pending = Some((range.clone(), c));
+					return Some(range.start);
+				}
+				_ => {}
+			}
+			end = Some(range.end);
+		}
+		end.take()
+	})
+}
+
+/// Splits encoded text into sentences, as per [`split_sentences_with`] with the default rules.
+///
+/// Sentences end at `。！？｡!?`, followed by any of the closing brackets `」』）】｣)`.
+pub fn split_sentences(input: &[u8]) -> impl Iterator<Item = (Range<usize>, &[u8])> {
+	split_sentences_with(input, is_terminator, is_closer)
+}
+
+/// Splits encoded text into sentences, giving the byte range and bytes of each.
+///
+/// A sentence ends after a run of terminators, together with any closing brackets right after
+/// them, so `「はい！？」` is one sentence. Whatever follows, including spaces and newlines, starts
+/// the next one; newlines alone do not end a sentence. Since the input is split into characters
+/// first, a terminator byte that is the trail byte of a kanji is not mistaken for one. Invalid
+/// sequences are neither terminators nor brackets.
+///
+/// The sentences cover the whole input, and empty input gives none.
+pub fn split_sentences_with(
+	input: &[u8],
+	is_terminator: impl Fn(char) -> bool,
+	is_closer: impl Fn(char) -> bool,
+) -> impl Iterator<Item = (Range<usize>, &[u8])> {
+	let units = decode_units(input).map(|(range, c)| (range, c.ok()));
+	let mut start = 0;
+	sentence_ends(units, is_terminator, is_closer).map(move |end| {
+		let range = start..end;
+		start = end;
+		(range.clone(), &input[range])
+	})
+}
+
+/// Like [`split_sentences`], but on a string.
+pub fn split_sentences_str(s: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+	split_sentences_str_with(s, is_terminator, is_closer)
+}
+
+/// Like [`split_sentences_with`], but on a string. The ranges are UTF-8 offsets.
+pub fn split_sentences_str_with(
+	s: &str,
+	is_terminator: impl Fn(char) -> bool,
+	is_closer: impl Fn(char) -> bool,
+) -> impl Iterator<Item = (Range<usize>, &str)> {
+	let units = s
+		.char_indices()
+		.map(|(pos, c)| (pos..pos + c.len_utf8(), Some(c)));
+	let mut start = 0;
+	sentence_ends(units, is_terminator, is_closer).map(move |end| {
+		let range = start..end;
+		start = end;
+		(range.clone(), &s[range])
+	})
+}
+
+#[test]
+fn test_segments() {
+	let input = b"\x01\x02\x93\xFA\x96\x7B\x0A\x41\x42\x03";
+	assert_eq!(
+		segments(input).collect::<Vec<_>>(),
+		[
+			Segment::Control {
+				pos: 0,
+				bytes: b"\x01\x02"
+			},
+			Segment::Text {
+				pos: 2,
+				bytes: b"\x93\xFA\x96\x7B"
+			},
+			Segment::Control {
+				pos: 6,
+				bytes: b"\x0A"
+			},
+			Segment::Text {
+				pos: 7,
+				bytes: b"AB"
+			},
+			Segment::Control {
+				pos: 9,
+				bytes: b"\x03"
+			},
+		],
+	);
+	assert_eq!(segments(input).map(|s| s.range()).last(), Some(9..10));
+
+	let segs = segments_with(input, |b| b < 0x20 && b != b'\n').collect::<Vec<_>>();
+	assert_eq!(segs.len(), 3);
+	assert_eq!(crate::decode(segs[1].bytes()).as_deref(), Ok("日本\nAB"));
+
+	assert_eq!(segments(b"").count(), 0);
+	assert_eq!(
+		segments(b"\x93").collect::<Vec<_>>(),
+		[Segment::Text {
+			pos: 0,
+			bytes: b"\x93"
+		}]
+	);
+}
+
+#[test]
+fn trail_byte_below_20() {
+	// No trail byte is below 0x40, so a control byte after a lead byte is never part of a character.
+	assert_eq!(
+		segments(b"\x93\x01\xFA").collect::<Vec<_>>(),
+		[
+			Segment::Text {
+				pos: 0,
+				bytes: b"\x93"
+			},
+			Segment::Control {
+				pos: 1,
+				bytes: b"\x01"
+			},
+			Segment::Text {
+				pos: 2,
+				bytes: b"\xFA"
+			},
+		],
+	);
+	// But with a larger control set, the trail byte is kept with its lead byte.
+	assert_eq!(
+		segments_with(b"\x95\x5C\x5C", |b| b == b'\\').collect::<Vec<_>>(),
+		[
+			Segment::Text {
+				pos: 0,
+				bytes: b"\x95\x5C"
+			},
+			Segment::Control {
+				pos: 2,
+				bytes: b"\x5C"
+			}
+		],
+	);
+}
+
+#[test]
+fn test_decode_split() {
+	let split = |input: &[u8], delim| decode_split(input, delim).collect::<Vec<_>>();
+	let ok = |s: &str| Ok(s.to_owned());
+	assert_eq!(
+		split(b"\x93\xFA\t\x96\x7B\t\tA", b'\t'),
+		[ok("日"), ok("本"), ok(""), ok("A")]
+	);
+	assert_eq!(split(b"", b','), [ok("")]);
+	assert_eq!(split(b"a,", b','), [ok("a"), ok("")]);
+	// 0x7C is the trail byte of ポ, but also `|`.
+	let input = crate::encode("ポ|a").unwrap();
+	assert_eq!(input, b"\x83\x7C|a");
+	assert_eq!(split(&input, b'|'), [ok("ポ"), ok("a")]);
+	assert_eq!(
+		split(b"a,b\x81,c\x80", b','),
+		[
+			ok("a"),
+			Err(DecodeError {
+				position: 3,
+				bytes: crate::EncodedChar::One([0x81])
+			}),
+			Err(DecodeError {
+				position: 6,
+				bytes: crate::EncodedChar::One([0x80])
+			}),
+		]
+	);
+}
+
+#[test]
+fn test_encode_join() {
+	assert_eq!(
+		encode_join(["日", "", "A"], b'\t').unwrap(),
+		b"\x93\xFA\t\tA"
+	);
+	assert_eq!(encode_join([], b',').unwrap(), b"");
+	assert_eq!(encode_join([""], b',').unwrap(), b"");
+	let fields = ["ポ", "a", "b₂"];
+	let joined = encode_join(fields, b'|');
+	assert_eq!(joined, Err(7));
+	assert_eq!(joined, crate::encode(&fields.join("|")));
+	let bytes = encode_join(["ポ", "a"], b'|').unwrap();
+	let fields = decode_split(&bytes, b'|').collect::<Result<Vec<_>, _>>();
+	assert_eq!(fields.unwrap(), ["ポ", "a"]);
+}
+
+#[test]
+fn test_extract_strings() {
+	let mut input = b"\x00\x01".to_vec();
+	input.extend(crate::encode("英雄伝説").unwrap());
+	input.extend(b"\x00\xFFab\x00\x80");
+	input.extend(crate::encode("空の軌跡\tFC").unwrap());
+	assert_eq!(
+		extract_strings(&input, 3),
+		[(2, "英雄伝説".to_owned()), (16, "空の軌跡\tFC".to_owned())]
+	);
+	assert_eq!(extract_strings(&input, 2)[1], (12, "ab".to_owned()));
+	assert_eq!(extract_strings(&input, 7).len(), 1);
+	// `85 61` is unassigned, but scanning resumes right after the lead byte.
+	assert_eq!(extract_strings(b"\x85abc", 3), [(1, "abc".to_owned())]);
+	assert_eq!(extract_strings(b"", 0), []);
+	assert_eq!(extract_strings(b"\x00", 0), []);
+	assert_eq!(extract_strings(b"abc", 0), [(0, "abc".to_owned())]);
+}
+
+#[test]
+fn sentences() {
+	let split = |s: &str| {
+		let bytes = crate::encode(s).unwrap();
+		let from_bytes = split_sentences(&bytes)
+			.map(|(r, b)| {
+				assert_eq!(&bytes[r], b);
+				crate::decode(b).unwrap()
+			})
+			.collect::<Vec<_>>();
+		let from_str = split_sentences_str(s)
+			.map(|(r, t)| {
+				assert_eq!(&s[r], t);
+				t.to_owned()
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(from_bytes, from_str);
+		from_str
+	};
+	assert_eq!(
+		split("「行くぞ。」「はい！」"),
+		["「行くぞ。」", "「はい！」"]
+	);
+	assert_eq!(split("本当に！？嘘でしょ。"), ["本当に！？", "嘘でしょ。"]);
+	assert_eq!(split("ｿｳｶ｡ﾅﾙﾎﾄﾞ!? OK"), ["ｿｳｶ｡", "ﾅﾙﾎﾄﾞ!?", " OK"]);
+	assert_eq!(split("それは\n長い文だ。次"), ["それは\n長い文だ。", "次"]);
+	assert_eq!(split("『終わり。』）"), ["『終わり。』）"]);
+	assert_eq!(split(""), Vec::<String>::new());
+	assert_eq!(split("。"), ["。"]);
+}
+
+#[test]
+fn sentences_trail_bytes() {
+	// The trail bytes of 娃 and 哀 are `A1` and `A3`, which are also ｡ and ｣ on their own.
+	let bytes = crate::encode("娃哀。次").unwrap();
+	assert_eq!(bytes[..4], *b"\x88\xA1\x88\xA3");
+	let ranges = split_sentences(&bytes).map(|(r, _)| r).collect::<Vec<_>>();
+	assert_eq!(ranges, [0..6, 6..8]);
+	// An invalid pair is not split either, even though its second byte is `!`.
+	let ranges = split_sentences(b"\x85!a!b")
+		.map(|(r, _)| r)
+		.collect::<Vec<_>>();
+	assert_eq!(ranges, [0..4, 4..5]);
+}
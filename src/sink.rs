@@ -0,0 +1,418 @@
+//! The shared encoding and decoding loops, and the outputs they can write to.
+//!
+//! Every encoding function goes through [`encode_into`], differing only in where the bytes go, how
+//! each character is encoded, and what happens to characters that cannot be. Decoding functions go
+//! through [`decode_into`] in the same way, except those that look further ahead than one
+//! character.
+
+use std::convert::Infallible;
+use std::io::{self, Write};
+use std::{iter, slice};
+
+use crate::trace::{Trace, DEFAULT_LEVEL};
+use crate::{
+	encode_char, EncodeError, EncodedChar, FixedError, TranscodeError, TranscodeErrorKind,
+};
+
+/// Somewhere to write encoded bytes to.
+pub(crate) trait EncodeSink {
+	type Error;
+
+	/// Writes the bytes of one character. On error, nothing is written.
+	fn put(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl EncodeSink for Vec<u8> {
+	type Error = Infallible;
+
+	fn put(&mut self, bytes: &[u8]) -> Result<(), Infallible> {
+		self.extend_from_slice(bytes);
+		Ok(())
+	}
+}
+
+/// A slice being filled from the start.
+pub(crate) struct SliceSink<'a> {
+	buf: &'a mut [u8],
+	len: usize,
+}
+
+/// The slice in a [`SliceSink`] is full.
+pub(crate) struct Full;
+
+impl EncodeSink for SliceSink<'_> {
+	type Error = Full;
+
+	fn put(&mut self, bytes: &[u8]) -> Result<(), Full> {
+		let end = self.len + bytes.len();
+		self.buf
+			.get_mut(self.len..end)
+			.ok_or(Full)?
+			.copy_from_slice(bytes);
+		self.len = end;
+		Ok(())
+	}
+}
+
+/// A writer, counting the bytes written.
+pub(crate) struct WriteSink<W> {
+	w: W,
+	len: usize,
+}
+
+impl<W: Write> EncodeSink for WriteSink<W> {
+	type Error = io::Error;
+
+	fn put(&mut self, bytes: &[u8]) -> io::Result<()> {
+		self.w.write_all(bytes)?;
+		self.len += bytes.len();
+		Ok(())
+	}
+}
+
+/// Why [`encode_into`] stopped, with the UTF-8 offset of the character it stopped at.
+pub(crate) enum SinkError<E> {
+	/// The character could not be encoded.
+	Unencodable(usize),
+	/// The sink failed.
+	Sink(usize, E),
+}
+
+/// Encodes a string into a sink.
+///
+/// Each character is encoded with `encode_char`. If that fails, `fallback` is called with the
+/// character's offset and the character, and may give bytes to write instead.
+#[inline]
+pub(crate) fn encode_into<S: EncodeSink>(
+	sink: &mut S,
+	str: &str,
+	encode_char: impl Fn(char) -> Option<EncodedChar>,
+	mut fallback: impl FnMut(usize, char) -> Option<EncodedChar>,
+) -> Result<(), SinkError<S::Error>> {
+	for (pos, char) in str.char_indices() {
+		let enc = encode_char(char)
+			.or_else(|| fallback(pos, char))
+			.ok_or(SinkError::Unencodable(pos))?;
+		sink.put(&enc).map_err(|e| SinkError::Sink(pos, e))?;
+	}
+	Ok(())
+}
+
+/// Encodes a string into a new vec, failing at the first character that cannot be encoded.
+pub(crate) fn encode_strict(
+	str: &str,
+	encode_char: impl Fn(char) -> Option<EncodedChar>,
+) -> Result<Vec<u8>, usize> {
+	let mut out = Vec::with_capacity(str.len());
+	match encode_into(&mut out, str, encode_char, |_, _| None) {
+		Ok(()) => Ok(out),
+		Err(SinkError::Unencodable(pos)) => Err(pos),
+		Err(SinkError::Sink(_, e)) => match e {},
+	}
+}
+
+/// Encodes a string into a new vec, replacing characters that cannot be encoded with
+/// [`EncodedChar::REPLACEMENT`].
+pub(crate) fn encode_replacing(
+	str: &str,
+	encode_char: impl Fn(char) -> Option<EncodedChar>,
+) -> Vec<u8> {
+	let mut out = Vec::with_capacity(str.len());
+	let mut trace = Trace::encode(str.len(), DEFAULT_LEVEL);
+	match encode_into(&mut out, str, encode_char, |pos, char| {
+		trace.replaced_char(pos, char, EncodedChar::REPLACEMENT);
+		Some(EncodedChar::REPLACEMENT)
+	}) {
+		Ok(()) => out,
+		Err(SinkError::Unencodable(_)) => unreachable!(),
+		Err(SinkError::Sink(_, e)) => match e {},
+	}
+}
+
+/// Somewhere to write decoded characters, or other items decoded from one character each, to.
+pub(crate) trait DecodeSink<T = char> {
+	type Error;
+
+	/// Writes one item.
+	fn put(&mut self, item: T) -> Result<(), Self::Error>;
+}
+
+impl DecodeSink for String {
+	type Error = Infallible;
+
+	fn put(&mut self, char: char) -> Result<(), Infallible> {
+		self.push(char);
+		Ok(())
+	}
+}
+
+impl<T> DecodeSink<T> for Vec<T> {
+	type Error = Infallible;
+
+	fn put(&mut self, item: T) -> Result<(), Infallible> {
+		self.push(item);
+		Ok(())
+	}
+}
+
+/// The input bytes after the first byte of a character, for [`decode_into`].
+pub(crate) type Rest<'a> = iter::Copied<slice::Iter<'a, u8>>;
+
+/// Why [`decode_into`] stopped.
+pub(crate) enum DecodeSinkError<E> {
+	/// The byte sequence at this offset could not be decoded.
+	Undecodable(usize, EncodedChar),
+	/// The sink failed.
+	Sink(E),
+}
+
+/// Decodes a byte slice into a sink.
+///
+/// Each character is decoded with `decode_char`, from its first byte and the rest of the input. If
+/// that fails, `fallback` is called with the sink and the sequence's offset and bytes, and may write
+/// something in its place; if it returns false, decoding stops there.
+///
+/// Callers pass `decode_char` as a closure of their own rather than a shared function, so that each
+/// gets its own copy of [`crate::decode_char_from`] inlined into the loop.
+#[inline]
+pub(crate) fn decode_into<T, S: DecodeSink<T>>(
+	sink: &mut S,
+	input: &[u8],
+	mut decode_char: impl FnMut(u8, &mut Rest) -> Result<T, EncodedChar>,
+	mut fallback: impl FnMut(&mut S, usize, EncodedChar) -> bool,
+) -> Result<(), DecodeSinkError<S::Error>> {
+	let mut iter = input.iter().copied();
+	while let Some(b1) = iter.next() {
+		match decode_char(b1, &mut iter) {
+			Ok(item) => sink.put(item).map_err(DecodeSinkError::Sink)?,
+			Err(enc) => {
+				let pos = input.len() - iter.len() - enc.len();
+				if !fallback(sink, pos, enc) {
+					return Err(DecodeSinkError::Undecodable(pos, enc));
+				}
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Decodes a byte slice into a new string, failing at the first invalid sequence.
+pub(crate) fn decode_strict(
+	input: &[u8],
+	decode_char: impl FnMut(u8, &mut Rest) -> Result<char, EncodedChar>,
+) -> Result<String, (usize, EncodedChar)> {
+	let mut out = String::new();
+	match decode_into(&mut out, input, decode_char, |_, _, _| false) {
+		Ok(()) => Ok(out),
+		Err(DecodeSinkError::Undecodable(pos, enc)) => Err((pos, enc)),
+		Err(DecodeSinkError::Sink(e)) => match e {},
+	}
+}
+
+/// Decodes a byte slice into a string, replacing invalid sequences with the unicode replacement
+/// character.
+pub(crate) fn decode_replacing(
+	out: &mut String,
+	input: &[u8],
+	decode_char: impl FnMut(u8, &mut Rest) -> Result<char, EncodedChar>,
+) {
+	let mut trace = Trace::decode(input.len(), DEFAULT_LEVEL);
+	match decode_into(out, input, decode_char, |out, pos, enc| {
+		trace.replaced_bytes(pos, &enc, '�');
+		out.push('�');
+		true
+	}) {
+		Ok(()) => {}
+		Err(DecodeSinkError::Undecodable(..)) => unreachable!(),
+		Err(DecodeSinkError::Sink(e)) => match e {},
+	}
+}
+
+/// Encodes a string, appending it to a vec.
+///
+/// On error, `out` is left as it was.
+pub fn encode_to(out: &mut Vec<u8>, str: &str) -> Result<(), EncodeError> {
+	let start = out.len();
+	match encode_into(out, str, encode_char, |_, _| None) {
+		Ok(()) => Ok(()),
+		Err(SinkError::Unencodable(pos)) => {
+			out.truncate(start);
+			Err(EncodeError::at(str, pos))
+		}
+		Err(SinkError::Sink(_, e)) => match e {},
+	}
+}
+
+/// Encodes as much of a string as possible, returning the bytes, the rest of the string starting at
+/// the character that could not be encoded, and the error for that character.
+///
+/// On success, the rest is empty and there is no error. The error's position is the same as from
+/// [`crate::encode`].
+pub fn encode_partial(str: &str) -> (Vec<u8>, &str, Option<EncodeError>) {
+	let mut out = Vec::with_capacity(str.len());
+	let (rest, error) = encode_partial_to(&mut out, str);
+	(out, rest, error)
+}
+
+/// Like [`encode_partial`], but appends to a vec.
+///
+/// Unlike [`encode_to`], the bytes before the error are kept, so that a caller can substitute
+/// something for the character and continue with the rest into the same vec.
+pub fn encode_partial_to<'a>(out: &mut Vec<u8>, str: &'a str) -> (&'a str, Option<EncodeError>) {
+	match encode_into(out, str, encode_char, |_, _| None) {
+		Ok(()) => ("", None),
+		Err(SinkError::Unencodable(pos)) => (&str[pos..], Some(EncodeError::at(str, pos))),
+		Err(SinkError::Sink(_, e)) => match e {},
+	}
+}
+
+/// Encodes a string into the start of a slice, returning the number of bytes written.
+///
+/// If the encoded string does not fit, the error gives its full length as
+/// [`FixedError::TooLong`], unless a later character cannot be encoded. Either way, the contents
+/// of the slice are unspecified on error.
+pub fn encode_to_slice(buf: &mut [u8], str: &str) -> Result<usize, FixedError> {
+	let width = buf.len();
+	let mut sink = SliceSink { buf, len: 0 };
+	match encode_into(&mut sink, str, encode_char, |_, _| None) {
+		Ok(()) => Ok(sink.len),
+		Err(SinkError::Unencodable(pos)) => Err(FixedError::Encode(EncodeError::at(str, pos))),
+		Err(SinkError::Sink(pos, Full)) => {
+			let mut len = sink.len;
+			for (i, char) in str[pos..].char_indices() {
+				let enc = encode_char(char).ok_or(EncodeError::at(str, pos + i));
+				len += enc.map_err(FixedError::Encode)?.len();
+			}
+			Err(FixedError::TooLong { len, width })
+		}
+	}
+}
+
+/// Encodes a string into a writer, returning the number of bytes written.
+///
+/// Each character is written separately, so `w` should usually be buffered. On error, the
+/// characters before the one at the error's position have been written.
+pub fn encode_to_writer(w: impl Write, str: &str) -> Result<usize, TranscodeError> {
+	let mut sink = WriteSink { w, len: 0 };
+	match encode_into(&mut sink, str, encode_char, |_, _| None) {
+		Ok(()) => Ok(sink.len),
+		Err(SinkError::Unencodable(pos)) => {
+			let char = str[pos..].chars().next().unwrap();
+			Err(TranscodeError::at(pos, TranscodeErrorKind::Encode(char)))
+		}
+		Err(SinkError::Sink(pos, e)) => Err(TranscodeError::at(pos, TranscodeErrorKind::Io(e))),
+	}
+}
+
+#[test]
+fn outputs_agree() {
+	let mut state = 1u32;
+	for len in 0..200 {
+		let input = (0..len)
+			.map(|_| {
+				state = state.wrapping_mul(1103515245).wrapping_add(12345);
+				(state >> 16) as u8
+			})
+			.collect::<Vec<_>>();
+		let s = crate::decode_lossy(&input).replace('�', "₂");
+		let expected = crate::encode(&s).map_err(|pos| EncodeError::at(&s, pos));
+
+		let mut out = b"x".to_vec();
+		let result = encode_to(&mut out, &s);
+		assert_eq!(result, expected.as_ref().map(|_| ()).map_err(|e| *e));
+		match &expected {
+			Ok(bytes) => assert_eq!(out[1..], bytes[..]),
+			Err(_) => assert_eq!(out, b"x"),
+		}
+
+		let mut buf = [0; 400];
+		match (encode_to_slice(&mut buf, &s), &expected) {
+			(Ok(n), Ok(bytes)) => assert_eq!(buf[..n], bytes[..]),
+			(Err(FixedError::Encode(e)), Err(f)) => assert_eq!(e, *f),
+			(r, _) => panic!("{r:?} vs {expected:?}"),
+		}
+
+		let mut w = Vec::new();
+		match (encode_to_writer(&mut w, &s), &expected) {
+			(Ok(n), Ok(bytes)) => assert_eq!((n, &w), (bytes.len(), bytes)),
+			(Err(e), Err(f)) => {
+				assert_eq!(e.position, f.position as u64);
+				assert!(matches!(e.kind, TranscodeErrorKind::Encode(c) if c == f.char));
+				assert_eq!(w, crate::encode(&s[..f.position]).unwrap());
+			}
+			(r, _) => panic!("{r:?} vs {expected:?}"),
+		}
+	}
+}
+
+#[test]
+fn slice_too_small() {
+	let mut buf = [0; 4];
+	assert_eq!(encode_to_slice(&mut buf, "a日b"), Ok(4));
+	assert_eq!(buf, *b"a\x93\xFAb");
+	assert_eq!(
+		encode_to_slice(&mut buf, "ab日本"),
+		Err(FixedError::TooLong { len: 6, width: 4 })
+	);
+	assert_eq!(
+		encode_to_slice(&mut buf, "abc日₂"),
+		Err(FixedError::Encode(EncodeError {
+			position: 6,
+			char: '₂'
+		}))
+	);
+	assert_eq!(encode_to_slice(&mut [], ""), Ok(0));
+}
+
+#[test]
+fn writer_error() {
+	struct Failing(usize);
+	impl Write for Failing {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			if self.0 < buf.len() {
+				return Err(io::Error::other("disk full"));
+			}
+			self.0 -= buf.len();
+			Ok(buf.len())
+		}
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+	let e = encode_to_writer(Failing(3), "ab日").unwrap_err();
+	assert!(e.is_io());
+	assert_eq!(e.position, 2);
+	assert_eq!(encode_to_writer(Failing(4), "ab日").unwrap(), 4);
+}
+
+#[test]
+fn partial() {
+	assert_eq!(
+		encode_partial("₂日"),
+		(vec![], "₂日", Some(EncodeError::at("₂日", 0)))
+	);
+	assert_eq!(
+		encode_partial("日本₂"),
+		(
+			b"\x93\xFA\x96\x7B".to_vec(),
+			"₂",
+			Some(EncodeError::at("日本₂", 6))
+		)
+	);
+	assert_eq!(
+		encode_partial("日本"),
+		(b"\x93\xFA\x96\x7B".to_vec(), "", None)
+	);
+	assert_eq!(encode_partial(""), (vec![], "", None));
+
+	// Substituting each unencodable character and resuming gives the same as encode_lossy.
+	let s = "a₂日₃₄b";
+	let mut out = Vec::new();
+	let mut rest = s;
+	while let (r, Some(e)) = encode_partial_to(&mut out, rest) {
+		assert_eq!(r.chars().next(), Some(e.char));
+		out.extend(EncodedChar::REPLACEMENT);
+		rest = &r[e.char.len_utf8()..];
+	}
+	assert_eq!(out, crate::encode_lossy(s));
+}
@@ -0,0 +1,272 @@
+//! Encoding and decoding with a replaceable two-byte table.
+
+use crate::iter::is_lead;
+use crate::{decode_char_from, encode_char, tables, EncodedChar};
+
+/// A mapping between two-byte codes and characters.
+///
+/// Single-byte characters are not part of the table: ASCII and half-width katakana are always
+/// mapped as usual. The provided methods mirror the free functions, which use [`BuiltinTable`].
+pub trait SjisTable {
+	/// The character at row `ku` and cell `ten`, as per [`EncodedChar::kuten`], or `None` if it is
+	/// unassigned. Rows range from 1 to 120, and cells from 1 to 94.
+	fn lookup(&self, ku: usize, ten: usize) -> Option<char>;
+
+	/// The two-byte code of a character, or `None` if it has none.
+	fn reverse(&self, ch: char) -> Option<[u8; 2]>;
+
+	/// Like [`crate::encode_char`], but with this table.
+	fn encode_char(&self, char: char) -> Option<EncodedChar> {
+		match encode_char(char) {
+			Some(EncodedChar::One(b)) => Some(EncodedChar::One(b)),
+			_ => self.reverse(char).map(EncodedChar::Two),
+		}
+	}
+
+	/// Like [`crate::decode_char_from`], but with this table.
+	fn decode_char_from(&self, b1: u8, b2: impl FnOnce() -> Option<u8>) -> Result<char, EncodedChar>
+	where
+		Self: Sized,
+	{
+		if !is_lead(b1) {
+			return decode_char_from(b1, || None);
+		}
+		let enc = EncodedChar::Two([b1, b2().ok_or(EncodedChar::One([b1]))?]);
+		let (ku, ten) = enc.kuten().ok_or(enc)?;
+		self.lookup(ku as usize, ten as usize).ok_or(enc)
+	}
+
+	/// Like [`crate::encode`], but with this table.
+	fn encode(&self, str: &str) -> Result<Vec<u8>, usize> {
+		crate::sink::encode_strict(str, |char| self.encode_char(char))
+	}
+
+	/// Like [`crate::decode`], but with this table.
+	fn decode(&self, input: &[u8]) -> Result<String, (usize, EncodedChar)>
+	where
+		Self: Sized,
+	{
+		crate::sink::decode_strict(input, |b1, rest| self.decode_char_from(b1, || rest.next()))
+	}
+}
+
+/// A hash of the data the built-in tables were generated from.
+///
+/// This differs between builds whose mapping data differs, such as locally patched ones.
+pub const TABLE_VERSION: &str = include!(concat!(env!("OUT_DIR"), "/table_version.rs"));
+
+/// A mismatch between the two directions of a table, as found by [`verify_table`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TableInconsistency {
+	/// A code decodes to a character that encodes to neither it nor another code for the same
+	/// character.
+	Decode {
+		/// The code.
+		code: [u8; 2],
+		/// What it decodes to.
+		char: char,
+		/// What the character encodes to.
+		encoded: Option<[u8; 2]>,
+	},
+	/// A character encodes to a code that does not decode back to it.
+	Encode {
+		/// The character.
+		char: char,
+		/// What it encodes to.
+		code: [u8; 2],
+		/// What the code decodes to.
+		decoded: Option<char>,
+	},
+}
+
+/// Checks that every entry of a table maps back to itself in the other direction.
+///
+/// Every character that [`SjisTable::reverse`] maps must decode back from its code. Every code that
+/// [`SjisTable::lookup`] maps must encode back to itself, or to another code for the same character:
+/// tables have duplicates, such as the NEC and IBM codes for the same kanji, of which only one can
+/// be encoded to.
+///
+/// This checks every character, so it takes a moment; it is meant for running once, such as at
+/// startup in debug builds.
+pub fn verify_table(table: &impl SjisTable) -> Result<(), Vec<TableInconsistency>> {
+	let decode = |code: [u8; 2]| {
+		let (ku, ten) = EncodedChar::Two(code).kuten()?;
+		table.lookup(ku as usize, ten as usize)
+	};
+	let mut errors = Vec::new();
+	for ku in 1..=120 {
+		for ten in 1..=94 {
+			let code = EncodedChar::from_kuten(ku, ten).unwrap().two().unwrap();
+			let Some(char) = table.lookup(ku as usize, ten as usize) else {
+				continue;
+			};
+			let encoded = table.reverse(char);
+			if encoded != Some(code) && encoded.and_then(decode) != Some(char) {
+				errors.push(TableInconsistency::Decode {
+					code,
+					char,
+					encoded,
+				});
+			}
+		}
+	}
+	for char in (0..=0x10FFFF).filter_map(char::from_u32) {
+		let Some(code) = table.reverse(char) else {
+			continue;
+		};
+		let decoded = decode(code);
+		if decoded != Some(char) {
+			errors.push(TableInconsistency::Encode {
+				char,
+				code,
+				decoded,
+			});
+		}
+	}
+	if errors.is_empty() {
+		Ok(())
+	} else {
+		Err(errors)
+	}
+}
+
+/// Checks the built-in tables, as per [`verify_table`].
+pub fn verify_tables() -> Result<(), Vec<TableInconsistency>> {
+	verify_table(&BuiltinTable)
+}
+
+/// The tables built into the crate, as used by [`crate::encode`] and [`crate::decode`].
+///
+/// This overrides the provided methods with the crate's own specialized paths, such as the
+/// `unsafe-fast` decoder, so the free functions lose nothing by going through it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BuiltinTable;
+
+impl SjisTable for BuiltinTable {
+	fn lookup(&self, ku: usize, ten: usize) -> Option<char> {
+		let ku = u8::try_from(ku).ok()?;
+		let ten = u8::try_from(ten).ok()?;
+		let [b1, b2] = EncodedChar::from_kuten(ku, ten)?.two()?;
+		decode_char_from(b1, || Some(b2)).ok()
+	}
+
+	fn reverse(&self, ch: char) -> Option<[u8; 2]> {
+		tables::encode(ch)
+	}
+
+	fn encode_char(&self, char: char) -> Option<EncodedChar> {
+		encode_char(char)
+	}
+
+	fn decode_char_from(
+		&self,
+		b1: u8,
+		b2: impl FnOnce() -> Option<u8>,
+	) -> Result<char, EncodedChar> {
+		decode_char_from(b1, b2)
+	}
+
+	fn decode(&self, input: &[u8]) -> Result<String, (usize, EncodedChar)> {
+		#[cfg(feature = "unsafe-fast")]
+		return crate::fast::decode(input);
+		#[cfg(not(feature = "unsafe-fast"))]
+		return crate::decode_checked(input);
+	}
+}
+
+#[test]
+fn builtin_matches_lookup() {
+	/// The built-in table through only its required methods, without the faster overrides.
+	struct Plain;
+	impl SjisTable for Plain {
+		fn lookup(&self, ku: usize, ten: usize) -> Option<char> {
+			BuiltinTable.lookup(ku, ten)
+		}
+		fn reverse(&self, ch: char) -> Option<[u8; 2]> {
+			BuiltinTable.reverse(ch)
+		}
+	}
+	let mut state = 1u32;
+	for len in 0..300 {
+		let input = (0..len)
+			.map(|_| {
+				state = state.wrapping_mul(1103515245).wrapping_add(12345);
+				(state >> 16) as u8
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(Plain.decode(&input), crate::decode(&input));
+		let s = crate::decode_lossy(&input).replace('�', "₂");
+		assert_eq!(Plain.encode(&s), crate::encode(&s));
+	}
+}
+
+#[test]
+fn custom_table() {
+	/// Only row 4, hiragana, shifted to katakana.
+	struct Shifted;
+	impl SjisTable for Shifted {
+		fn lookup(&self, ku: usize, ten: usize) -> Option<char> {
+			match (ku, ten) {
+				(4, 1..=83) => char::from_u32(0x30A1 + ten as u32 - 1),
+				_ => None,
+			}
+		}
+		fn reverse(&self, ch: char) -> Option<[u8; 2]> {
+			let ten = (ch as u32)
+				.checked_sub(0x30A0)
+				.filter(|t| (1..=83).contains(t))?;
+			EncodedChar::from_kuten(4, ten as u8)?.two()
+		}
+	}
+	let bytes = crate::encode("あいA").unwrap();
+	assert_eq!(Shifted.decode(&bytes).as_deref(), Ok("アイA"));
+	assert_eq!(Shifted.encode("アイA"), Ok(bytes));
+	assert_eq!(Shifted.encode("あ"), Err(0));
+	assert_eq!(
+		Shifted.decode(b"\x93\xFA"),
+		Err((0, EncodedChar::Two([0x93, 0xFA])))
+	);
+	assert_eq!(Shifted.decode(b"\x82"), Err((0, EncodedChar::One([0x82]))));
+}
+
+#[test]
+fn verify() {
+	assert_eq!(verify_tables(), Ok(()));
+	assert_eq!(TABLE_VERSION.len(), 16);
+
+	/// The built-in table with one cell changed, as a locally edited table might be.
+	struct Patched;
+	impl SjisTable for Patched {
+		fn lookup(&self, ku: usize, ten: usize) -> Option<char> {
+			match (ku, ten) {
+				(16, 1) => Some('亞'),
+				(16, 2) => Some('₂'),
+				_ => BuiltinTable.lookup(ku, ten),
+			}
+		}
+		fn reverse(&self, ch: char) -> Option<[u8; 2]> {
+			BuiltinTable.reverse(ch)
+		}
+	}
+	assert_eq!(
+		verify_table(&Patched),
+		// 889F is now a duplicate of 亞's own code, which is allowed, but 亜 no longer round-trips.
+		Err(vec![
+			TableInconsistency::Decode {
+				code: [0x88, 0xA0],
+				char: '₂',
+				encoded: None,
+			},
+			TableInconsistency::Encode {
+				char: '亜',
+				code: [0x88, 0x9F],
+				decoded: Some('亞'),
+			},
+			TableInconsistency::Encode {
+				char: '唖',
+				code: [0x88, 0xA0],
+				decoded: Some('₂'),
+			},
+		])
+	);
+}
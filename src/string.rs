@@ -0,0 +1,496 @@
+//! Borrowed and owned strings of valid Shift JIS.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::width::chars_width;
+use crate::{decode_split, decode_units, encode_char, DecodeError, EncodeError, EncodedChar};
+
+/// A borrowed byte string that is always valid Shift JIS, with the methods of [`str`] that make
+/// sense for it.
+///
+/// Byte indices are as in the encoded bytes, and must be character boundaries. Since Shift JIS is
+/// not self-synchronizing, finding boundaries scans from the start of the string. Patterns are
+/// encoded, and never match the second byte of a character: `"表"`, `95 5C`, does not contain
+/// `"\\"`.
+///
+/// Unlike [`str`], this is a sized wrapper around a borrowed slice, so slicing methods such as
+/// [`get`](Self::get) return an `SjisStr` by value rather than a reference. Borrowing a `[u8]` as
+/// an unsized `&SjisStr` would need a pointer cast, which is unsafe.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SjisStr<'a> {
+	bytes: &'a [u8],
+}
+
+impl<'a> SjisStr<'a> {
+	/// Checks that bytes are valid Shift JIS, and wraps them.
+	pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+		if let Some((range, Err(enc))) = decode_units(bytes).find(|(_, r)| r.is_err()) {
+			return Err(DecodeError::from((range.start, enc)));
+		}
+		Ok(SjisStr { bytes })
+	}
+
+	/// The encoded bytes.
+	pub fn as_bytes(&self) -> &'a [u8] {
+		self.bytes
+	}
+
+	/// The length in bytes.
+	pub fn len(&self) -> usize {
+		self.bytes.len()
+	}
+
+	/// Whether the string is empty.
+	pub fn is_empty(&self) -> bool {
+		self.bytes.is_empty()
+	}
+
+	/// The number of characters.
+	pub fn char_count(&self) -> usize {
+		decode_units(self.bytes).count()
+	}
+
+	/// The characters, as per [`decode_units`].
+	pub fn chars(&self) -> impl DoubleEndedIterator<Item = char> + 'a {
+		decode_units(self.bytes).map(|(_, result)| result.unwrap())
+	}
+
+	/// The characters and their byte indices, as per [`decode_units`].
+	pub fn char_indices(&self) -> impl DoubleEndedIterator<Item = (usize, char)> + 'a {
+		decode_units(self.bytes).map(|(range, result)| (range.start, result.unwrap()))
+	}
+
+	/// Whether a byte index is the start or end of a character. The start and end of the string
+	/// count as boundaries.
+	pub fn is_char_boundary(&self, index: usize) -> bool {
+		index == self.len() || decode_units(self.bytes).any(|(range, _)| range.start == index)
+	}
+
+	/// The byte index of the first match of `pat`, if any. A pattern that cannot be encoded never
+	/// matches, and an empty one matches at 0.
+	pub fn find(&self, pat: &str) -> Option<usize> {
+		let pat = crate::encode(pat).ok()?;
+		let starts = decode_units(self.bytes).map(|(range, _)| range.start);
+		let mut starts = starts.chain(std::iter::once(self.len()));
+		starts.find(|&i| self.bytes[i..].starts_with(&pat))
+	}
+
+	/// Whether `pat` occurs in the string, as per [`find`](Self::find).
+	pub fn contains(&self, pat: &str) -> bool {
+		self.find(pat).is_some()
+	}
+
+	/// Whether the string starts with `pat`.
+	pub fn starts_with(&self, pat: &str) -> bool {
+		crate::encode(pat).is_ok_and(|pat| self.bytes.starts_with(&pat))
+	}
+
+	/// Whether the string ends with `pat`, starting at a character boundary.
+	pub fn ends_with(&self, pat: &str) -> bool {
+		crate::encode(pat).is_ok_and(|pat| {
+			self.bytes.ends_with(&pat) && self.is_char_boundary(self.len() - pat.len())
+		})
+	}
+
+	/// Splits the string at a delimiter byte and decodes the fields, as per [`decode_split`].
+	///
+	/// Only single-byte delimiters are supported, as with [`decode_split`]; a delimiter that is the
+	/// second byte of a character does not split it.
+	pub fn split(&self, delim: u8) -> impl Iterator<Item = String> + 'a {
+		decode_split(self.bytes, delim).map(Result::unwrap)
+	}
+
+	/// Removes leading and trailing whitespace, as per [`char::is_whitespace`]. This includes the
+	/// ideographic space `　`.
+	pub fn trim(&self) -> SjisStr<'a> {
+		self.trim_start().trim_end()
+	}
+
+	/// Removes leading whitespace, as per [`trim`](Self::trim).
+	pub fn trim_start(&self) -> SjisStr<'a> {
+		let start = decode_units(self.bytes)
+			.find(|(_, char)| !char.is_ok_and(char::is_whitespace))
+			.map_or(self.len(), |(range, _)| range.start);
+		SjisStr {
+			bytes: &self.bytes[start..],
+		}
+	}
+
+	/// Removes trailing whitespace, as per [`trim`](Self::trim).
+	pub fn trim_end(&self) -> SjisStr<'a> {
+		let end = decode_units(self.bytes)
+			.rfind(|(_, char)| !char.is_ok_and(char::is_whitespace))
+			.map_or(0, |(range, _)| range.end);
+		SjisStr {
+			bytes: &self.bytes[..end],
+		}
+	}
+
+	/// Slices the string by byte indices, or returns `None` if either end is out of bounds or not a
+	/// character boundary.
+	pub fn get(&self, range: Range<usize>) -> Option<SjisStr<'a>> {
+		let valid = range.start <= range.end && range.end <= self.len();
+		if !valid || !self.is_char_boundary(range.start) || !self.is_char_boundary(range.end) {
+			return None;
+		}
+		Some(SjisStr {
+			bytes: &self.bytes[range],
+		})
+	}
+
+	/// The number of columns the string takes up, as per [`crate::display_width`].
+	pub fn display_width(&self) -> usize {
+		chars_width(self.chars())
+	}
+}
+
+impl AsRef<[u8]> for SjisStr<'_> {
+	fn as_ref(&self) -> &[u8] {
+		self.bytes
+	}
+}
+
+/// Writes the decoded string.
+impl fmt::Display for SjisStr<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.chars()
+			.try_for_each(|char| fmt::Write::write_char(f, char))
+	}
+}
+
+/// An owned byte string that is always valid Shift JIS, for building encoded output.
+///
+/// Every method keeps the bytes valid: characters that cannot be encoded are errors, and byte
+/// indices must be character boundaries, as with [`String`]. Since Shift JIS is not
+/// self-synchronizing, checking a boundary scans from the start of the string.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SjisString {
+	bytes: Vec<u8>,
+}
+
+/// The encoded character of a unit from [`decode_units`].
+fn unit(bytes: &[u8]) -> EncodedChar {
+	match *bytes {
+		[a] => EncodedChar::One([a]),
+		[a, b] => EncodedChar::Two([a, b]),
+		_ => unreachable!(),
+	}
+}
+
+impl SjisString {
+	/// Creates an empty string.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Checks that bytes are valid Shift JIS, and wraps them.
+	pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, DecodeError> {
+		SjisStr::from_bytes(&bytes)?;
+		Ok(SjisString { bytes })
+	}
+
+	/// Encodes a string, as per [`crate::encode`].
+	pub fn encode(s: &str) -> Result<Self, EncodeError> {
+		let mut string = Self::new();
+		string.push_str(s)?;
+		Ok(string)
+	}
+
+	/// The encoded bytes.
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.bytes
+	}
+
+	/// Unwraps the encoded bytes.
+	pub fn into_bytes(self) -> Vec<u8> {
+		self.bytes
+	}
+
+	/// Borrows the string as an [`SjisStr`].
+	pub fn as_sjis_str(&self) -> SjisStr<'_> {
+		SjisStr { bytes: &self.bytes }
+	}
+
+	/// The length in bytes.
+	pub fn len(&self) -> usize {
+		self.bytes.len()
+	}
+
+	/// Whether the string is empty.
+	pub fn is_empty(&self) -> bool {
+		self.bytes.is_empty()
+	}
+
+	/// Whether a byte index is the start or end of a character. The start and end of the string
+	/// count as boundaries.
+	pub fn is_char_boundary(&self, index: usize) -> bool {
+		self.as_sjis_str().is_char_boundary(index)
+	}
+
+	fn units(&self) -> impl DoubleEndedIterator<Item = (Range<usize>, char)> + '_ {
+		decode_units(&self.bytes).map(|(range, result)| (range, result.unwrap()))
+	}
+
+	fn assert_boundary(&self, index: usize) {
+		assert!(
+			self.is_char_boundary(index),
+			"byte index {index} is not a char boundary"
+		);
+	}
+
+	/// Appends a character. On error, nothing is appended; as with [`push_str`](Self::push_str),
+	/// the position is in the pushed text, so it is always 0.
+	pub fn push(&mut self, char: char) -> Result<(), EncodeError> {
+		let enc = encode_char(char).ok_or(EncodeError { position: 0, char })?;
+		self.bytes.extend(enc);
+		Ok(())
+	}
+
+	/// Appends a string. On error, nothing is appended; the position is in `s`.
+	pub fn push_str(&mut self, s: &str) -> Result<(), EncodeError> {
+		crate::encode_to(&mut self.bytes, s)
+	}
+
+	/// Appends an already encoded character, or gives it back if it is not valid.
+	pub fn push_encoded(&mut self, enc: EncodedChar) -> Result<(), EncodedChar> {
+		enc.to_char().ok_or(enc)?;
+		self.bytes.extend(enc);
+		Ok(())
+	}
+
+	/// Inserts a character at a byte index. On error, nothing is inserted, and the position is 0 as
+	/// with [`push`](Self::push).
+	///
+	/// # Panics
+	/// If `index` is not a character boundary.
+	pub fn insert(&mut self, index: usize, char: char) -> Result<(), EncodeError> {
+		self.insert_str(index, char.encode_utf8(&mut [0; 4]))
+	}
+
+	/// Inserts a string at a byte index. On error, nothing is inserted; the position is in `s`.
+	///
+	/// # Panics
+	/// If `index` is not a character boundary.
+	pub fn insert_str(&mut self, index: usize, s: &str) -> Result<(), EncodeError> {
+		self.replace_range(index..index, s)
+	}
+
+	/// Removes the character at a byte index, and returns it.
+	///
+	/// # Panics
+	/// If `index` is not the start of a character.
+	pub fn remove(&mut self, index: usize) -> EncodedChar {
+		let Some((range, _)) = self.units().find(|(range, _)| range.start == index) else {
+			panic!("byte index {index} is not the start of a char");
+		};
+		unit(&self.bytes.drain(range).collect::<Vec<_>>())
+	}
+
+	/// Shortens the string to `len` bytes. Does nothing if it is already shorter.
+	///
+	/// # Panics
+	/// If `len` is not a character boundary.
+	pub fn truncate(&mut self, len: usize) {
+		if len < self.len() {
+			self.assert_boundary(len);
+			self.bytes.truncate(len);
+		}
+	}
+
+	/// Removes the last character, and returns it.
+	///
+	/// This only looks at the end of the string, as per [`crate::DecodeUnits`].
+	pub fn pop(&mut self) -> Option<EncodedChar> {
+		let (range, _) = decode_units(&self.bytes).next_back()?;
+		let enc = unit(&self.bytes[range.clone()]);
+		self.bytes.truncate(range.start);
+		Some(enc)
+	}
+
+	/// Keeps only the characters for which `f` returns true.
+	pub fn retain(&mut self, mut f: impl FnMut(char) -> bool) {
+		let mut bytes = Vec::with_capacity(self.len());
+		for (range, char) in self.units() {
+			if f(char) {
+				bytes.extend_from_slice(&self.bytes[range]);
+			}
+		}
+		self.bytes = bytes;
+	}
+
+	/// Replaces a byte range with a string. On error, nothing is changed; the position is in `s`.
+	///
+	/// # Panics
+	/// If either end of the range is not a character boundary, or the start is after the end.
+	pub fn replace_range(&mut self, range: Range<usize>, s: &str) -> Result<(), EncodeError> {
+		assert!(range.start <= range.end, "range start is after its end");
+		self.assert_boundary(range.start);
+		self.assert_boundary(range.end);
+		let enc = crate::encode(s).map_err(|pos| EncodeError::at(s, pos))?;
+		self.bytes.splice(range, enc);
+		Ok(())
+	}
+}
+
+impl AsRef<[u8]> for SjisString {
+	fn as_ref(&self) -> &[u8] {
+		&self.bytes
+	}
+}
+
+/// Writes the decoded string.
+impl fmt::Display for SjisString {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.as_sjis_str().fmt(f)
+	}
+}
+
+#[test]
+fn sjis_str() {
+	// The trail byte of 表 is `\`, and the string is padded with both kinds of space.
+	let text = "　ｴｽﾃﾙ「表示,a,,表」\\ \u{3000}";
+	let bytes = crate::encode(text).unwrap();
+	let s = SjisStr::from_bytes(&bytes).unwrap();
+	assert_eq!(
+		SjisStr::from_bytes(b"a\x85\x40"),
+		Err(DecodeError::from((
+			1,
+			crate::EncodedChar::Two([0x85, 0x40])
+		)))
+	);
+	assert_eq!(s.to_string(), text);
+	assert_eq!(s.len(), bytes.len());
+	assert_eq!(s.char_count(), text.chars().count());
+	assert!(s.chars().eq(text.chars()));
+	assert!(s.chars().rev().eq(text.chars().rev()));
+	let indices = decode_units(&bytes).map(|(range, char)| (range.start, char.unwrap()));
+	assert!(s.char_indices().eq(indices));
+	assert_eq!(s.display_width(), crate::display_width(text));
+
+	for pat in ["", "表", "a,", ",表」", "\\", "示,a", "x", "₂"] {
+		let found = s.find(pat).map(|i| crate::decode(&bytes[..i]).unwrap());
+		let expected = text.find(pat).map(|i| text[..i].to_owned());
+		assert_eq!(found, expected, "{pat:?}");
+		assert_eq!(s.contains(pat), text.contains(pat), "{pat:?}");
+		assert_eq!(s.starts_with(pat), text.starts_with(pat), "{pat:?}");
+		assert_eq!(s.ends_with(pat), text.ends_with(pat), "{pat:?}");
+	}
+	let table = SjisStr::from_bytes(b"\x95\x5C").unwrap();
+	assert!(!table.contains("\\"));
+	assert!(!table.ends_with("\\"));
+
+	let expected = decode_split(&bytes, b',').map(Result::unwrap);
+	assert!(s.split(b',').eq(expected));
+	assert!(s.split(b',').eq(text.split(',')));
+	assert!(s.split(b'\\').eq(text.split('\\')));
+
+	assert_eq!(s.trim().to_string(), text.trim());
+	assert_eq!(s.trim_start().to_string(), text.trim_start());
+	assert_eq!(s.trim_end().to_string(), text.trim_end());
+	assert!(SjisStr::from_bytes(b" \x81\x40").unwrap().trim().is_empty());
+}
+
+#[test]
+fn sjis_str_get() {
+	let bytes = crate::encode("a表ｱ").unwrap();
+	let s = SjisStr::from_bytes(&bytes).unwrap();
+	assert_eq!(s.get(0..0).unwrap().to_string(), "");
+	assert_eq!(s.get(1..3).unwrap().to_string(), "表");
+	assert_eq!(s.get(3..4).unwrap().to_string(), "ｱ");
+	assert_eq!(s.get(0..4), Some(s));
+	// The middle of 表, reversed and out of bounds.
+	assert_eq!(s.get(2..4), None);
+	assert_eq!(s.get(0..2), None);
+	#[allow(clippy::reversed_empty_ranges)]
+	let reversed = 3..1;
+	assert_eq!(s.get(reversed), None);
+	assert_eq!(s.get(0..5), None);
+	assert!(!s.is_char_boundary(2));
+	assert!(s.is_char_boundary(4));
+}
+
+#[test]
+fn build() {
+	let text = "ｴｽﾃﾙ「はい、Joshua！」\n";
+	let mut s = SjisString::new();
+	for char in text.chars() {
+		s.push(char).unwrap();
+	}
+	assert_eq!(s.as_bytes(), crate::encode(text).unwrap());
+	assert_eq!(s.to_string(), text);
+	assert_eq!(SjisString::encode(text), Ok(s.clone()));
+	assert_eq!(SjisString::from_bytes(s.clone().into_bytes()), Ok(s));
+
+	let mut s = SjisString::encode("日").unwrap();
+	let error = s.push('₂').unwrap_err();
+	assert_eq!((error.position, error.char), (0, '₂'));
+	assert_eq!(s.push_str("ab₂").unwrap_err().position, 2);
+	assert_eq!(
+		s.push_encoded(EncodedChar::Two([0x85, 0x40])),
+		Err(EncodedChar::Two([0x85, 0x40]))
+	);
+	assert_eq!(
+		s.push_encoded(EncodedChar::One([0x81])),
+		Err(EncodedChar::One([0x81]))
+	);
+	s.push_encoded(EncodedChar::Two([0x96, 0x7B])).unwrap();
+	assert_eq!(s.to_string(), "日本");
+	assert_eq!(
+		SjisString::from_bytes(b"ab\x93".to_vec()),
+		Err(DecodeError::from((2, EncodedChar::One([0x93]))))
+	);
+}
+
+#[test]
+fn pop() {
+	// The trail bytes of 表 and ソ are both `\`.
+	let mut s = SjisString::encode("a表ソ\\ｱ").unwrap();
+	let mut popped = Vec::new();
+	while let Some(enc) = s.pop() {
+		popped.push(enc);
+	}
+	assert_eq!(
+		popped,
+		[
+			EncodedChar::One([0xB1]),
+			EncodedChar::One([b'\\']),
+			EncodedChar::Two([0x83, 0x5C]),
+			EncodedChar::Two([0x95, 0x5C]),
+			EncodedChar::One([b'a']),
+		]
+	);
+	assert!(s.is_empty());
+}
+
+#[test]
+fn edit() {
+	let mut s = SjisString::encode("a表b").unwrap();
+	let boundaries = (0..=5)
+		.filter(|&i| s.is_char_boundary(i))
+		.collect::<Vec<_>>();
+	assert_eq!(boundaries, [0, 1, 3, 4]);
+
+	s.insert(1, 'ソ').unwrap();
+	s.insert_str(0, "ｴ").unwrap();
+	assert_eq!(s.to_string(), "ｴaソ表b");
+	let error = s.insert(2, '₂').unwrap_err();
+	assert_eq!((error.position, error.char), (0, '₂'));
+	assert_eq!(s.to_string(), "ｴaソ表b");
+	assert_eq!(s.remove(2), EncodedChar::Two([0x83, 0x5C]));
+	s.replace_range(1..4, "日本").unwrap();
+	assert_eq!(s.to_string(), "ｴ日本b");
+	assert!(s.replace_range(0..1, "~₂").is_err());
+	s.truncate(3);
+	assert_eq!(s.to_string(), "ｴ日");
+	s.truncate(10);
+	assert_eq!(s.len(), 3);
+	assert!(!s.is_char_boundary(2));
+}
+
+#[test]
+fn retain() {
+	let mut s = SjisString::encode("英雄伝説 空の軌跡 FC").unwrap();
+	s.retain(|c| crate::classify(c).is_none_or(|c| c < crate::JisClass::Level1Kanji));
+	assert_eq!(s.to_string(), " の FC");
+}
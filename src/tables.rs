@@ -0,0 +1,154 @@
+//! The mapping tables.
+//!
+//! By default these are static tables generated by the build script. With the `compress-tables`
+//! feature they are instead embedded in compressed form, and decompressed on first use. The
+//! `flat-encode-table` feature replaces the encoding table with a 128 KiB array indexed by
+//! codepoint, which takes precedence over compression.
+
+#[cfg(any(
+	not(any(feature = "compress-tables", feature = "flat-encode-table")),
+	test
+))]
+static UTF8_SJIS: phf::Map<char, [u8; 2]> = include!(concat!(env!("OUT_DIR"), "/utf8sjis.rs"));
+#[cfg(any(not(feature = "compress-tables"), test))]
+static SJIS_UTF8: [[u16; 188]; 50] = include!(concat!(env!("OUT_DIR"), "/sjisutf8.rs"));
+static SJIS_UTF8_EX: &[char] = include!(concat!(env!("OUT_DIR"), "/sjisutf8_ex.rs"));
+/// The IBM extension codes for characters that also have an NEC code, sorted by char.
+static IBM: &[(char, [u8; 2])] = include!(concat!(env!("OUT_DIR"), "/ibm.rs"));
+
+/// Looks up the two-byte encoding of a character.
+#[cfg(not(any(feature = "compress-tables", feature = "flat-encode-table")))]
+pub(crate) fn encode(char: char) -> Option<[u8; 2]> {
+	UTF8_SJIS.get(&char).copied()
+}
+
+#[cfg(feature = "flat-encode-table")]
+static UTF8_SJIS_FLAT: &[u8; 0x20000] =
+	include_bytes!(concat!(env!("OUT_DIR"), "/utf8sjis_flat.bin"));
+
+#[cfg(feature = "flat-encode-table")]
+pub(crate) fn encode(char: char) -> Option<[u8; 2]> {
+	let i = char as usize * 2;
+	let code = [*UTF8_SJIS_FLAT.get(i)?, UTF8_SJIS_FLAT[i + 1]];
+	(code != [0, 0]).then_some(code)
+}
+
+/// Looks up a two-byte character, given the row and cell indices.
+#[cfg(not(feature = "compress-tables"))]
+pub(crate) fn decode(a: usize, b: usize) -> Option<char> {
+	from_u16(SJIS_UTF8[a][b])
+}
+
+#[cfg(all(feature = "compress-tables", not(feature = "flat-encode-table")))]
+pub(crate) fn encode(char: char) -> Option<[u8; 2]> {
+	let table = compressed::utf8_sjis();
+	let i = table.binary_search_by_key(&char, |e| e.0).ok()?;
+	Some(table[i].1)
+}
+
+#[cfg(feature = "compress-tables")]
+pub(crate) fn decode(a: usize, b: usize) -> Option<char> {
+	from_u16(compressed::sjis_utf8()[a][b])
+}
+
+/// Looks up the IBM extension code of a character, if it has one.
+pub(crate) fn encode_ibm(char: char) -> Option<[u8; 2]> {
+	let i = IBM.binary_search_by_key(&char, |e| e.0).ok()?;
+	Some(IBM[i].1)
+}
+
+fn from_u16(ch: u16) -> Option<char> {
+	match ch {
+		0 => None,
+		ch => Some(char::from_u32(ch as u32).unwrap_or_else(|| SJIS_UTF8_EX[ch as usize - 0xD800])),
+	}
+}
+
+#[cfg(feature = "compress-tables")]
+mod compressed {
+	use std::sync::OnceLock;
+
+	static SJIS_UTF8_Z: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/sjisutf8.bin"));
+
+	#[cfg(any(not(feature = "flat-encode-table"), test))]
+	/// The encoding table, sorted by char.
+	///
+	/// This is the inverse of the decoding table, preferring the lowest code for duplicates.
+	pub(super) fn utf8_sjis() -> &'static [(char, [u8; 2])] {
+		static TABLE: OnceLock<Vec<(char, [u8; 2])>> = OnceLock::new();
+		TABLE.get_or_init(|| {
+			let mut table = Vec::new();
+			for (a, row) in sjis_utf8().iter().enumerate() {
+				for (b, &ch) in row.iter().enumerate() {
+					if let Some(char) = super::from_u16(ch) {
+						let b1 = match a {
+							0x00..=0x1E => a + 0x81,
+							0x1F..=0x2E => a - 0x1F + 0xE0,
+							_ => a - 0x2F + 0xFA,
+						};
+						let b2 = if b < 0x3F { b + 0x40 } else { b - 0x3F + 0x80 };
+						table.push((char, [b1 as u8, b2 as u8]));
+					}
+				}
+			}
+			table.sort();
+			table.dedup_by_key(|e| e.0);
+			table
+		})
+	}
+
+	pub(super) fn sjis_utf8() -> &'static [[u16; 188]; 50] {
+		static TABLE: OnceLock<Box<[[u16; 188]; 50]>> = OnceLock::new();
+		TABLE.get_or_init(|| {
+			let mut z = SJIS_UTF8_Z;
+			let mut table = Box::new([[0; 188]; 50]);
+			let mut cells = table.iter_mut().flatten();
+			let mut value = 0;
+			while !z.is_empty() {
+				let token = varint(&mut z);
+				let run = if token & 1 != 0 { varint(&mut z) } else { 0 };
+				let delta = unzigzag(token >> 1);
+				for _ in 0..=run {
+					value += delta;
+					*cells.next().unwrap() = value as u16;
+				}
+			}
+			table
+		})
+	}
+
+	fn varint(z: &mut &[u8]) -> u32 {
+		let mut v = 0;
+		let mut shift = 0;
+		loop {
+			let (&b, rest) = z.split_first().unwrap();
+			*z = rest;
+			v |= ((b & 0x7F) as u32) << shift;
+			if b < 0x80 {
+				return v;
+			}
+			shift += 7;
+		}
+	}
+
+	fn unzigzag(v: u32) -> i32 {
+		(v >> 1) as i32 ^ -((v & 1) as i32)
+	}
+
+	#[test]
+	fn same_as_uncompressed() {
+		assert_eq!(*sjis_utf8(), super::SJIS_UTF8);
+		assert_eq!(utf8_sjis().len(), super::UTF8_SJIS.len());
+		for &(char, code) in utf8_sjis() {
+			assert_eq!(super::UTF8_SJIS.get(&char), Some(&code));
+		}
+	}
+}
+
+#[cfg(feature = "flat-encode-table")]
+#[test]
+fn flat_same_as_phf() {
+	for char in (0..=0x10FFFF).filter_map(char::from_u32) {
+		assert_eq!(encode(char), UTF8_SJIS.get(&char).copied(), "{char:?}");
+	}
+}
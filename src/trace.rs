@@ -0,0 +1,259 @@
+//! Events for lossy replacements, under the `tracing` feature.
+//!
+//! Each lossy conversion creates a [`Trace`], which holds a span around the conversion and emits
+//! an event for each replacement. Without the feature, it is empty and does nothing.
+
+use crate::EncodedChar;
+
+/// The level that events and spans are emitted at.
+#[cfg(feature = "tracing")]
+pub(crate) type Level = tracing::Level;
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone, Copy)]
+pub(crate) struct Level;
+
+/// The level used by the free functions and by default.
+#[cfg(feature = "tracing")]
+pub(crate) const DEFAULT_LEVEL: Level = tracing::Level::DEBUG;
+#[cfg(not(feature = "tracing"))]
+pub(crate) const DEFAULT_LEVEL: Level = Level;
+
+/// Expands a tracing macro with a level that is only known at runtime, since the macros need a
+/// constant one.
+#[cfg(feature = "tracing")]
+macro_rules! at_level {
+	($mac:ident!($level:expr, $($args:tt)*)) => {
+		match $level {
+			tracing::Level::ERROR => tracing::$mac!(tracing::Level::ERROR, $($args)*),
+			tracing::Level::WARN => tracing::$mac!(tracing::Level::WARN, $($args)*),
+			tracing::Level::INFO => tracing::$mac!(tracing::Level::INFO, $($args)*),
+			tracing::Level::DEBUG => tracing::$mac!(tracing::Level::DEBUG, $($args)*),
+			tracing::Level::TRACE => tracing::$mac!(tracing::Level::TRACE, $($args)*),
+		}
+	};
+}
+
+/// A lossy conversion in progress.
+///
+/// When dropped, the number of replacements is recorded on the span.
+#[cfg(feature = "tracing")]
+pub(crate) struct Trace {
+	level: Level,
+	span: tracing::span::EnteredSpan,
+	replacements: usize,
+}
+
+#[cfg(feature = "tracing")]
+impl Trace {
+	/// Starts tracing an encoding of `len` bytes of UTF-8.
+	pub(crate) fn encode(len: usize, level: Level) -> Self {
+		let span = at_level!(span!(
+			level,
+			"encode_lossy",
+			len,
+			replacements = tracing::field::Empty
+		));
+		Trace {
+			level,
+			span: span.entered(),
+			replacements: 0,
+		}
+	}
+
+	/// Starts tracing a decoding of `len` bytes of Shift JIS.
+	pub(crate) fn decode(len: usize, level: Level) -> Self {
+		let span = at_level!(span!(
+			level,
+			"decode_lossy",
+			len,
+			replacements = tracing::field::Empty
+		));
+		Trace {
+			level,
+			span: span.entered(),
+			replacements: 0,
+		}
+	}
+
+	/// Records that the character at UTF-8 offset `offset` was encoded as `replacement`.
+	pub(crate) fn replaced_char(&mut self, offset: usize, char: char, replacement: EncodedChar) {
+		self.replacements += 1;
+		let replacement = format_args!("{:02X?}", replacement.as_bytes());
+		at_level!(event!(
+			self.level,
+			offset,
+			char = ?char,
+			replacement = %replacement,
+			"replaced unencodable character"
+		));
+	}
+
+	/// Records that the bytes at offset `offset` were decoded as `replacement`.
+	pub(crate) fn replaced_bytes(&mut self, offset: usize, bytes: &[u8], replacement: char) {
+		self.replacements += 1;
+		let bytes = format_args!("{bytes:02X?}");
+		at_level!(event!(
+			self.level,
+			offset,
+			bytes = %bytes,
+			replacement = ?replacement,
+			"replaced invalid bytes"
+		));
+	}
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for Trace {
+	fn drop(&mut self) {
+		self.span.record("replacements", self.replacements);
+	}
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Trace;
+
+#[cfg(not(feature = "tracing"))]
+impl Trace {
+	#[inline(always)]
+	pub(crate) fn encode(_len: usize, _level: Level) -> Self {
+		Trace
+	}
+
+	#[inline(always)]
+	pub(crate) fn decode(_len: usize, _level: Level) -> Self {
+		Trace
+	}
+
+	#[inline(always)]
+	pub(crate) fn replaced_char(&mut self, _offset: usize, _char: char, _replacement: EncodedChar) {
+	}
+
+	#[inline(always)]
+	pub(crate) fn replaced_bytes(&mut self, _offset: usize, _bytes: &[u8], _replacement: char) {}
+}
+
+/// Runs `f`, returning the fields of the events it emitted at `level` or above, and the
+/// replacement counts recorded on spans.
+#[cfg(all(test, feature = "tracing"))]
+fn capture(level: Level, f: impl FnOnce()) -> (Vec<Vec<(String, String)>>, Vec<String>) {
+	use std::sync::{Arc, Mutex};
+	use tracing::field::{Field, Visit};
+	use tracing::span::{Id, Record};
+	use tracing_subscriber::filter::LevelFilter;
+	use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+	#[derive(Default)]
+	struct Fields(Vec<(String, String)>);
+	impl Visit for Fields {
+		fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+			self.0.push((field.name().to_owned(), format!("{value:?}")));
+		}
+	}
+
+	type Captured = Arc<Mutex<(Vec<Vec<(String, String)>>, Vec<String>)>>;
+	struct Capture(Captured);
+	impl<S: tracing::Subscriber> Layer<S> for Capture {
+		fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+			let mut fields = Fields::default();
+			event.record(&mut fields);
+			self.0.lock().unwrap().0.push(fields.0);
+		}
+		fn on_record(&self, _: &Id, values: &Record<'_>, _: Context<'_, S>) {
+			let mut fields = Fields::default();
+			values.record(&mut fields);
+			let counts = &mut self.0.lock().unwrap().1;
+			counts.extend(fields.0.into_iter().map(|(_, value)| value));
+		}
+	}
+
+	let captured = Captured::default();
+	let subscriber = tracing_subscriber::registry()
+		.with(LevelFilter::from_level(level))
+		.with(Capture(captured.clone()));
+	tracing::subscriber::with_default(subscriber, f);
+	let captured = captured.lock().unwrap();
+	(captured.0.clone(), captured.1.clone())
+}
+
+#[cfg(all(test, feature = "tracing"))]
+fn fields(list: &[(&str, &str)]) -> Vec<(String, String)> {
+	list.iter()
+		.map(|(k, v)| (k.to_string(), v.to_string()))
+		.collect()
+}
+
+#[test]
+#[cfg(feature = "tracing")]
+fn encode_events() {
+	let (events, counts) = capture(Level::DEBUG, || {
+		assert_eq!(crate::encode_lossy("a₂日₃"), b"a\x81\x45\x93\xFA\x81\x45");
+	});
+	let message = ("message", "replaced unencodable character");
+	assert_eq!(
+		events,
+		[
+			fields(&[
+				message,
+				("offset", "1"),
+				("char", "'₂'"),
+				("replacement", "[81, 45]")
+			]),
+			fields(&[
+				message,
+				("offset", "7"),
+				("char", "'₃'"),
+				("replacement", "[81, 45]")
+			]),
+		]
+	);
+	assert_eq!(counts, ["2"]);
+}
+
+#[test]
+#[cfg(feature = "tracing")]
+fn decode_events() {
+	let (events, counts) = capture(Level::DEBUG, || {
+		assert_eq!(crate::decode_lossy(b"a\x80\x93\xFA\xF0\x40"), "a�日�");
+	});
+	let message = ("message", "replaced invalid bytes");
+	assert_eq!(
+		events,
+		[
+			fields(&[
+				message,
+				("offset", "1"),
+				("bytes", "[80]"),
+				("replacement", "'�'")
+			]),
+			fields(&[
+				message,
+				("offset", "4"),
+				("bytes", "[F0, 40]"),
+				("replacement", "'�'")
+			]),
+		]
+	);
+	assert_eq!(counts, ["2"]);
+
+	let (events, counts) = capture(Level::DEBUG, || {
+		crate::decode_lossy(b"abc");
+	});
+	assert_eq!((events.len(), &counts[..]), (0, &["0".to_owned()][..]));
+}
+
+#[test]
+#[cfg(feature = "tracing")]
+fn codec_level() {
+	let warn = crate::Codec::builder()
+		.lossy(true)
+		.trace_level(Level::WARN)
+		.build();
+	let debug = crate::Codec::builder().lossy(true).build();
+	let run = |codec: &crate::Codec| {
+		codec.decode(b"\x80\x80").unwrap();
+		codec.encode("₂").unwrap();
+	};
+	assert_eq!(capture(Level::WARN, || run(&warn)).0.len(), 3);
+	assert_eq!(capture(Level::WARN, || run(&debug)).0.len(), 0);
+	assert_eq!(capture(Level::DEBUG, || run(&debug)).0.len(), 3);
+}
@@ -0,0 +1,391 @@
+//! Streaming conversion between readers and writers.
+//!
+//! The input is read in fixed-size chunks, so the memory use does not depend on the size of the
+//! input. Characters split between chunks are carried over to the next one.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::{decode_char_from, encode_char, EncodedChar};
+
+const BUF_SIZE: usize = 8192;
+
+/// Statistics from a successful [`transcode_sjis_to_utf8`] or [`transcode_utf8_to_sjis`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TranscodeStats {
+	/// The number of bytes read.
+	pub bytes_in: u64,
+	/// The number of bytes written.
+	pub bytes_out: u64,
+	/// The number of replacement characters written in place of invalid input.
+	pub replacements: u64,
+}
+
+/// An error from [`transcode_sjis_to_utf8`] or [`transcode_utf8_to_sjis`].
+///
+/// Everything before the error has been written.
+#[derive(Debug)]
+pub struct TranscodeError {
+	/// The offset in the input of the offending sequence, or for I/O errors the number of bytes
+	/// read before the error.
+	pub position: u64,
+	/// What went wrong.
+	pub kind: TranscodeErrorKind,
+}
+
+/// The reason for a [`TranscodeError`].
+#[derive(Debug)]
+pub enum TranscodeErrorKind {
+	/// Reading or writing failed.
+	Io(io::Error),
+	/// The input contained an invalid Shift JIS sequence.
+	Decode(EncodedChar),
+	/// The input contained invalid UTF-8.
+	InvalidUtf8,
+	/// The input contained a character that cannot be encoded.
+	Encode(char),
+	/// The input was not valid in its encoding, other than Shift JIS or UTF-8.
+	Malformed,
+	/// The input contained a Shift JIS character that has no equivalent in the output encoding.
+	Unrepresentable(EncodedChar),
+}
+
+impl TranscodeError {
+	pub(crate) fn at(position: usize, kind: TranscodeErrorKind) -> Self {
+		TranscodeError {
+			position: position as u64,
+			kind,
+		}
+	}
+
+	/// Whether the error was caused by reading or writing, rather than by the input's contents.
+	pub fn is_io(&self) -> bool {
+		matches!(self.kind, TranscodeErrorKind::Io(_))
+	}
+}
+
+impl fmt::Display for TranscodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match &self.kind {
+			TranscodeErrorKind::Io(e) => write!(f, "{e}")?,
+			TranscodeErrorKind::Decode(bytes) => write!(f, "cannot decode {:02X?}", &**bytes)?,
+			TranscodeErrorKind::InvalidUtf8 => write!(f, "invalid UTF-8")?,
+			TranscodeErrorKind::Encode(c) => write!(f, "cannot encode {c:?}")?,
+			TranscodeErrorKind::Malformed => write!(f, "malformed input")?,
+			TranscodeErrorKind::Unrepresentable(bytes) => {
+				write!(f, "cannot represent {:02X?}", &**bytes)?
+			}
+		}
+		write!(f, " at position {}", self.position)
+	}
+}
+
+impl std::error::Error for TranscodeError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match &self.kind {
+			TranscodeErrorKind::Io(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+/// Reads as much as is available into `buf`, retrying on interruption.
+fn fill(r: &mut impl Read, buf: &mut [u8], read: u64) -> Result<usize, TranscodeError> {
+	loop {
+		match r.read(buf) {
+			Ok(n) => return Ok(n),
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+			Err(e) => return Err(io_error(read, e)),
+		}
+	}
+}
+
+fn io_error(position: u64, e: io::Error) -> TranscodeError {
+	TranscodeError {
+		position,
+		kind: TranscodeErrorKind::Io(e),
+	}
+}
+
+/// Decodes Shift JIS from a reader, writing UTF-8 to a writer.
+///
+/// If `lossy` is set, invalid sequences are replaced as per [`crate::decode_lossy`], and the
+/// output is the same as that of decoding the whole input at once.
+pub fn transcode_sjis_to_utf8<R: Read, W: Write>(
+	mut r: R,
+	mut w: W,
+	lossy: bool,
+) -> Result<TranscodeStats, TranscodeError> {
+	let mut stats = TranscodeStats::default();
+	let mut buf = [0; BUF_SIZE];
+	let mut len = 0;
+	let mut out = String::new();
+	loop {
+		let read = stats.bytes_in + len as u64;
+		let n = fill(&mut r, &mut buf[len..], read)?;
+		let eof = n == 0;
+		len += n;
+		let mut pos = 0;
+		let mut error = None;
+		while pos < len {
+			let b2 = buf[..len].get(pos + 1).copied();
+			let mut needs_b2 = false;
+			let result = decode_char_from(buf[pos], || {
+				needs_b2 = true;
+				b2
+			});
+			if needs_b2 && b2.is_none() && !eof {
+				break;
+			}
+			match result {
+				Ok(char) => out.push(char),
+				Err(_) if lossy => {
+					out.push('�');
+					stats.replacements += 1;
+				}
+				Err(bytes) => {
+					error = Some(TranscodeError {
+						position: stats.bytes_in + pos as u64,
+						kind: TranscodeErrorKind::Decode(bytes),
+					});
+					break;
+				}
+			}
+			pos += if needs_b2 && b2.is_some() { 2 } else { 1 };
+		}
+		let read = stats.bytes_in + len as u64;
+		w.write_all(out.as_bytes()).map_err(|e| io_error(read, e))?;
+		stats.bytes_out += out.len() as u64;
+		out.clear();
+		if let Some(error) = error {
+			return Err(error);
+		}
+		stats.bytes_in += pos as u64;
+		buf.copy_within(pos..len, 0);
+		len -= pos;
+		if eof {
+			break;
+		}
+	}
+	w.flush().map_err(|e| io_error(stats.bytes_in, e))?;
+	Ok(stats)
+}
+
+/// Encodes UTF-8 from a reader as Shift JIS, writing it to a writer.
+///
+/// If `lossy` is set, invalid UTF-8 is replaced as per [`String::from_utf8_lossy`], and then
+/// unencodable characters as per [`crate::encode_lossy`]. The output is the same as that of
+/// encoding the whole input at once.
+pub fn transcode_utf8_to_sjis<R: Read, W: Write>(
+	mut r: R,
+	mut w: W,
+	lossy: bool,
+) -> Result<TranscodeStats, TranscodeError> {
+	let mut stats = TranscodeStats::default();
+	let mut buf = [0; BUF_SIZE];
+	let mut len = 0;
+	let mut out = Vec::new();
+	loop {
+		let read = stats.bytes_in + len as u64;
+		let n = fill(&mut r, &mut buf[len..], read)?;
+		let eof = n == 0;
+		len += n;
+		let mut pos = 0;
+		let mut error = None;
+		while pos < len && error.is_none() {
+			let (valid, invalid) = match std::str::from_utf8(&buf[pos..len]) {
+				Ok(s) => (s, None),
+				Err(e) => {
+					let s = std::str::from_utf8(&buf[pos..pos + e.valid_up_to()]).unwrap();
+					(s, Some(e.error_len()))
+				}
+			};
+			for (i, char) in valid.char_indices() {
+				match encode_char(char) {
+					Some(enc) => out.extend(enc),
+					None if lossy => {
+						out.extend(EncodedChar::REPLACEMENT);
+						stats.replacements += 1;
+					}
+					None => {
+						error = Some(TranscodeError {
+							position: stats.bytes_in + (pos + i) as u64,
+							kind: TranscodeErrorKind::Encode(char),
+						});
+						break;
+					}
+				}
+			}
+			if error.is_some() {
+				break;
+			}
+			pos += valid.len();
+			let invalid_len = match invalid {
+				None => continue,
+				Some(None) if !eof => break,
+				Some(None) => len - pos,
+				Some(Some(n)) => n,
+			};
+			if lossy {
+				out.extend(EncodedChar::REPLACEMENT);
+				stats.replacements += 1;
+				pos += invalid_len;
+			} else {
+				error = Some(TranscodeError {
+					position: stats.bytes_in + pos as u64,
+					kind: TranscodeErrorKind::InvalidUtf8,
+				});
+			}
+		}
+		let read = stats.bytes_in + len as u64;
+		w.write_all(&out).map_err(|e| io_error(read, e))?;
+		stats.bytes_out += out.len() as u64;
+		out.clear();
+		if let Some(error) = error {
+			return Err(error);
+		}
+		stats.bytes_in += pos as u64;
+		buf.copy_within(pos..len, 0);
+		len -= pos;
+		if eof {
+			break;
+		}
+	}
+	w.flush().map_err(|e| io_error(stats.bytes_in, e))?;
+	Ok(stats)
+}
+
+/// A reader that returns at most a few bytes at a time.
+#[cfg(test)]
+struct Trickle<'a>(&'a [u8], usize);
+
+#[cfg(test)]
+impl Read for Trickle<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		self.1 = self.1 % 7 + 1;
+		let n = self.1.min(buf.len()).min(self.0.len());
+		buf[..n].copy_from_slice(&self.0[..n]);
+		self.0 = &self.0[n..];
+		Ok(n)
+	}
+}
+
+#[cfg(test)]
+fn generate(len: usize, pieces: &[&[u8]]) -> Vec<u8> {
+	let mut state = 1u32;
+	let mut out = Vec::new();
+	while out.len() < len {
+		state = state.wrapping_mul(1103515245).wrapping_add(12345);
+		out.extend(pieces[(state >> 16) as usize % pieces.len()]);
+	}
+	out
+}
+
+#[test]
+fn sjis_to_utf8() {
+	let sjis = crate::encode("日本語ｶﾀｶﾅ①").unwrap();
+	let pieces: &[&[u8]] = &[
+		b"a",
+		b"\n",
+		&sjis,
+		b"\x80",
+		b"\x81\x7F",
+		b"\xFD\x40",
+		b"\x88",
+	];
+	for input in [generate(50000, pieces), generate(50000, &pieces[..3])] {
+		let expected = crate::decode_lossy(&input);
+		for trickle in [false, true] {
+			let mut out = Vec::new();
+			let stats = if trickle {
+				transcode_sjis_to_utf8(Trickle(&input, 0), &mut out, true)
+			} else {
+				transcode_sjis_to_utf8(&input[..], &mut out, true)
+			}
+			.unwrap();
+			assert!(out == expected.as_bytes());
+			assert_eq!(stats.bytes_in, input.len() as u64);
+			assert_eq!(stats.bytes_out, out.len() as u64);
+			assert_eq!(stats.replacements, expected.matches('�').count() as u64);
+
+			let mut out = Vec::new();
+			let result = transcode_sjis_to_utf8(Trickle(&input, 0), &mut out, false);
+			match crate::decode(&input) {
+				Ok(s) => assert_eq!(result.unwrap().bytes_out, s.len() as u64),
+				Err((pos, bytes)) => {
+					let err = result.unwrap_err();
+					assert_eq!(err.position, pos as u64);
+					assert!(matches!(err.kind, TranscodeErrorKind::Decode(b) if b == bytes));
+					assert!(out == crate::decode(&input[..pos]).unwrap().as_bytes());
+				}
+			}
+		}
+	}
+	// A lead byte at the very end.
+	let mut out = Vec::new();
+	let stats = transcode_sjis_to_utf8(&b"a\x88"[..], &mut out, true).unwrap();
+	assert_eq!((&out[..], stats.replacements), ("a�".as_bytes(), 1));
+}
+
+#[test]
+fn utf8_to_sjis() {
+	let pieces: &[&[u8]] = &[
+		b"a",
+		"日本語ｶﾀｶﾅ①".as_bytes(),
+		"₂".as_bytes(),
+		b"\xE6\x97",
+		b"\xFF",
+	];
+	for input in [generate(50000, pieces), generate(50000, &pieces[..2])] {
+		let expected = crate::encode_lossy(&String::from_utf8_lossy(&input));
+		let mut out = Vec::new();
+		let stats = transcode_utf8_to_sjis(Trickle(&input, 0), &mut out, true).unwrap();
+		assert!(out == expected);
+		assert_eq!(stats.bytes_in, input.len() as u64);
+		assert_eq!(stats.bytes_out, out.len() as u64);
+
+		let mut out = Vec::new();
+		let result = transcode_utf8_to_sjis(Trickle(&input, 0), &mut out, false);
+		match std::str::from_utf8(&input) {
+			Ok(s) => assert!(result.unwrap().bytes_out == crate::encode(s).unwrap().len() as u64),
+			Err(e) => {
+				let err = result.unwrap_err();
+				assert!(!err.is_io());
+				assert!(err.position <= e.valid_up_to() as u64);
+			}
+		}
+	}
+}
+
+#[test]
+fn error_past_buffer() {
+	let mut input = "あ".repeat(BUF_SIZE).into_bytes();
+	input.extend("₂".as_bytes());
+	let err = transcode_utf8_to_sjis(&input[..], io::sink(), false).unwrap_err();
+	assert_eq!(err.position, 3 * BUF_SIZE as u64);
+	assert!(matches!(err.kind, TranscodeErrorKind::Encode('₂')));
+
+	let mut input = crate::encode(&"あ".repeat(BUF_SIZE)).unwrap();
+	input.insert(BUF_SIZE + 1000, 0x80);
+	let mut out = Vec::new();
+	let err = transcode_sjis_to_utf8(Trickle(&input, 0), &mut out, false).unwrap_err();
+	assert_eq!(err.position, BUF_SIZE as u64 + 1000);
+	assert_eq!(err.to_string(), "cannot decode [80] at position 9192");
+	assert_eq!(out.len(), 3 * (BUF_SIZE + 1000) / 2);
+}
+
+#[test]
+fn io_errors() {
+	struct Broken;
+	impl Write for Broken {
+		fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+			Err(io::ErrorKind::BrokenPipe.into())
+		}
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+	let err = transcode_sjis_to_utf8(&b"abc"[..], Broken, false).unwrap_err();
+	assert!(err.is_io());
+	assert_eq!(err.position, 3);
+}
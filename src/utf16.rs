@@ -0,0 +1,263 @@
+//! Conversion to and from UTF-16.
+
+use std::fmt;
+
+use crate::sink::{self, DecodeSink, DecodeSinkError};
+use crate::{decode_char_from, encode_char, DecodeError, EncodedChar};
+
+/// An error from [`encode_utf16`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EncodeUtf16Error {
+	/// The code unit at this index is a surrogate without its other half.
+	UnpairedSurrogate(usize),
+	/// The character starting at this index cannot be encoded.
+	Unencodable(usize, char),
+}
+
+impl EncodeUtf16Error {
+	/// The index of the offending code unit.
+	pub fn index(&self) -> usize {
+		match *self {
+			EncodeUtf16Error::UnpairedSurrogate(i) | EncodeUtf16Error::Unencodable(i, _) => i,
+		}
+	}
+}
+
+impl fmt::Display for EncodeUtf16Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			EncodeUtf16Error::UnpairedSurrogate(i) => write!(f, "unpaired surrogate at index {i}"),
+			EncodeUtf16Error::Unencodable(i, c) => write!(f, "cannot encode {c:?} at index {i}"),
+		}
+	}
+}
+
+impl std::error::Error for EncodeUtf16Error {}
+
+/// Iterates over the characters in UTF-16, with the index of their first code unit.
+fn chars_utf16(units: &[u16]) -> impl Iterator<Item = (usize, Result<char, u16>)> + '_ {
+	let mut index = 0;
+	char::decode_utf16(units.iter().copied()).map(move |c| {
+		let i = index;
+		index += c.as_ref().map_or(1, |c| c.len_utf16());
+		(i, c.map_err(|e| e.unpaired_surrogate()))
+	})
+}
+
+/// Encodes a UTF-16 string.
+///
+/// Unpaired surrogates are errors, distinct from characters that cannot be encoded.
+pub fn encode_utf16(units: &[u16]) -> Result<Vec<u8>, EncodeUtf16Error> {
+	let mut out = Vec::with_capacity(units.len() * 2);
+	for (i, char) in chars_utf16(units) {
+		let char = char.map_err(|_| EncodeUtf16Error::UnpairedSurrogate(i))?;
+		out.extend(encode_char(char).ok_or(EncodeUtf16Error::Unencodable(i, char))?);
+	}
+	Ok(out)
+}
+
+/// Encodes a UTF-16 string, lossily.
+///
+/// Unpaired surrogates and characters that cannot be encoded are replaced with
+/// [`EncodedChar::REPLACEMENT`].
+pub fn encode_utf16_lossy(units: &[u16]) -> Vec<u8> {
+	let mut out = Vec::with_capacity(units.len() * 2);
+	for (_, char) in chars_utf16(units) {
+		let enc = char.ok().and_then(encode_char);
+		out.extend(enc.unwrap_or(EncodedChar::REPLACEMENT));
+	}
+	out
+}
+
+/// Decodes a byte slice into UTF-16.
+pub fn decode_to_utf16(input: &[u8]) -> Result<Vec<u16>, DecodeError> {
+	let mut out = Vec::with_capacity(input.len());
+	decode_to_units(&mut out, input, |unit| [unit])?;
+	Ok(out)
+}
+
+/// UTF-16 output, with each code unit written as the items from `unit`.
+struct Utf16Sink<'a, T, U> {
+	out: &'a mut Vec<T>,
+	unit: fn(u16) -> U,
+}
+
+impl<T, U: IntoIterator<Item = T>> DecodeSink for Utf16Sink<'_, T, U> {
+	type Error = std::convert::Infallible;
+
+	fn put(&mut self, char: char) -> Result<(), Self::Error> {
+		for &unit in &*char.encode_utf16(&mut [0; 2]) {
+			self.out.extend((self.unit)(unit));
+		}
+		Ok(())
+	}
+}
+
+/// Decodes a byte slice into UTF-16, appending each code unit to `out` as per `unit`.
+///
+/// On error, `out` is left as it was.
+fn decode_to_units<T, U: IntoIterator<Item = T>>(
+	out: &mut Vec<T>,
+	input: &[u8],
+	unit: fn(u16) -> U,
+) -> Result<(), DecodeError> {
+	let start = out.len();
+	let mut sink = Utf16Sink { out, unit };
+	match sink::decode_into(
+		&mut sink,
+		input,
+		|b1, rest| decode_char_from(b1, || rest.next()),
+		|_, _, _| false,
+	) {
+		Ok(()) => Ok(()),
+		Err(DecodeSinkError::Undecodable(pos, enc)) => {
+			sink.out.truncate(start);
+			Err(DecodeError::from((pos, enc)))
+		}
+		Err(DecodeSinkError::Sink(e)) => match e {},
+	}
+}
+
+/// Decodes a byte slice into UTF-16LE bytes.
+pub fn decode_to_utf16le(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+	let mut out = Vec::with_capacity(input.len() * 2);
+	decode_to_utf16le_into(&mut out, input, false)?;
+	Ok(out)
+}
+
+/// Decodes a byte slice into UTF-16BE bytes.
+pub fn decode_to_utf16be(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+	let mut out = Vec::with_capacity(input.len() * 2);
+	decode_to_utf16be_into(&mut out, input, false)?;
+	Ok(out)
+}
+
+/// Decodes a byte slice into UTF-16LE bytes, appending them to a vec.
+///
+/// If `nul` is set, a NUL terminator is appended after the string. On error, `out` is left as it
+/// was.
+pub fn decode_to_utf16le_into(
+	out: &mut Vec<u8>,
+	input: &[u8],
+	nul: bool,
+) -> Result<(), DecodeError> {
+	decode_to_utf16_bytes(out, input, nul, u16::to_le_bytes)
+}
+
+/// Decodes a byte slice into UTF-16BE bytes, appending them to a vec.
+///
+/// See [`decode_to_utf16le_into`].
+pub fn decode_to_utf16be_into(
+	out: &mut Vec<u8>,
+	input: &[u8],
+	nul: bool,
+) -> Result<(), DecodeError> {
+	decode_to_utf16_bytes(out, input, nul, u16::to_be_bytes)
+}
+
+fn decode_to_utf16_bytes(
+	out: &mut Vec<u8>,
+	input: &[u8],
+	nul: bool,
+	to_bytes: fn(u16) -> [u8; 2],
+) -> Result<(), DecodeError> {
+	decode_to_units(out, input, to_bytes)?;
+	if nul {
+		out.extend([0, 0]);
+	}
+	Ok(())
+}
+
+#[test]
+fn roundtrip() {
+	let s = "日本ファルコム、ｶﾀｶﾅ①≒ABC";
+	let units = s.encode_utf16().collect::<Vec<_>>();
+	let bytes = crate::encode(s).unwrap();
+	assert_eq!(encode_utf16(&units), Ok(bytes.clone()));
+	assert_eq!(encode_utf16_lossy(&units), bytes);
+	assert_eq!(decode_to_utf16(&bytes), Ok(units));
+	assert_eq!(
+		decode_to_utf16(&[0x41, 0x80]),
+		Err(DecodeError {
+			position: 1,
+			bytes: EncodedChar::One([0x80])
+		})
+	);
+}
+
+#[test]
+fn surrogates() {
+	let units = [0x41, 0xD800, 0x42, 0xDC00];
+	assert_eq!(
+		encode_utf16(&units),
+		Err(EncodeUtf16Error::UnpairedSurrogate(1))
+	);
+	assert_eq!(encode_utf16(&units[2..]).unwrap_err().index(), 1);
+	assert_eq!(encode_utf16_lossy(&units), b"A\x81\x45B\x81\x45");
+
+	let units = "a😀亜".encode_utf16().collect::<Vec<_>>();
+	assert_eq!(units.len(), 4);
+	assert_eq!(
+		encode_utf16(&units),
+		Err(EncodeUtf16Error::Unencodable(1, '😀'))
+	);
+	assert_eq!(encode_utf16_lossy(&units), crate::encode_lossy("a😀亜"));
+	assert_eq!(encode_utf16_lossy(&units), b"a\x81\x45\x88\x9F");
+}
+
+#[test]
+fn utf16_bytes() {
+	let mut state = 1u32;
+	for len in 0..300 {
+		let input = (0..len)
+			.map(|_| {
+				state = state.wrapping_mul(1103515245).wrapping_add(12345);
+				(state >> 16) as u8
+			})
+			.collect::<Vec<_>>();
+		match crate::decode(&input) {
+			Ok(s) => {
+				let units = s.encode_utf16().collect::<Vec<_>>();
+				let le = units
+					.iter()
+					.flat_map(|u| u.to_le_bytes())
+					.collect::<Vec<_>>();
+				let be = units
+					.iter()
+					.flat_map(|u| u.to_be_bytes())
+					.collect::<Vec<_>>();
+				assert_eq!(decode_to_utf16le(&input).unwrap(), le);
+				assert_eq!(decode_to_utf16be(&input).unwrap(), be);
+			}
+			Err(e) => {
+				assert_eq!(decode_to_utf16le(&input), Err(e.into()));
+				assert_eq!(decode_to_utf16be(&input), Err(e.into()));
+			}
+		}
+		// Also check some valid input, since random bytes are rarely valid.
+		let lossy = crate::encode_lossy(&crate::decode_lossy(&input));
+		let s = crate::decode(&lossy).unwrap();
+		let le = s
+			.encode_utf16()
+			.flat_map(u16::to_le_bytes)
+			.collect::<Vec<_>>();
+		assert_eq!(decode_to_utf16le(&lossy).unwrap(), le);
+	}
+}
+
+#[test]
+fn utf16_bytes_into() {
+	let mut out = vec![0xFF];
+	decode_to_utf16le_into(&mut out, b"A\x82\xA0", true).unwrap();
+	assert_eq!(out, [0xFF, 0x41, 0x00, 0x42, 0x30, 0x00, 0x00]);
+	decode_to_utf16be_into(&mut out, b"A", false).unwrap();
+	assert_eq!(out[7..], [0x00, 0x41]);
+	assert_eq!(
+		decode_to_utf16le_into(&mut out, b"AB\x80", true),
+		Err(DecodeError {
+			position: 2,
+			bytes: EncodedChar::One([0x80])
+		})
+	);
+	assert_eq!(out.len(), 9);
+}
@@ -0,0 +1,69 @@
+//! Choice between NEC-selected and IBM extension codes.
+
+use crate::{encode_char, tables, EncodedChar};
+
+/// Which code to use for characters that are in both the NEC-selected IBM extensions (rows 89–92)
+/// and the IBM extensions (lead bytes `FA`–`FC`).
+///
+/// This affects encoding only; both codes are always accepted when decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VendorPreference {
+	/// Use the NEC code, as [`encode_char`] does.
+	#[default]
+	Nec,
+	/// Use the IBM code.
+	Ibm,
+}
+
+impl VendorPreference {
+	/// Like [`crate::encode_char`], but with this preference.
+	pub fn encode_char(self, char: char) -> Option<EncodedChar> {
+		match self {
+			VendorPreference::Ibm => match encode_char(char) {
+				Some(EncodedChar::Two([0xED | 0xEE, _])) => {
+					tables::encode_ibm(char).map(EncodedChar::Two)
+				}
+				enc => enc,
+			},
+			VendorPreference::Nec => encode_char(char),
+		}
+	}
+
+	/// Like [`crate::encode`], but with this preference.
+	pub fn encode(self, str: &str) -> Result<Vec<u8>, usize> {
+		crate::sink::encode_strict(str, |char| self.encode_char(char))
+	}
+
+	/// Like [`crate::encode_lossy`], but with this preference.
+	pub fn encode_lossy(self, str: &str) -> Vec<u8> {
+		crate::sink::encode_replacing(str, |char| self.encode_char(char))
+	}
+}
+
+#[test]
+fn vendor_preference() {
+	use VendorPreference::*;
+	assert_eq!(Nec.encode_char('髙'), Some(EncodedChar::Two([0xEE, 0xE0])));
+	assert_eq!(Ibm.encode_char('髙'), Some(EncodedChar::Two([0xFB, 0xFC])));
+	assert_eq!(
+		crate::decode(&[0xEE, 0xE0, 0xFB, 0xFC]).as_deref(),
+		Ok("髙髙")
+	);
+	assert_eq!(
+		Ibm.encode("ⅰ∵纊").as_deref(),
+		Ok(&[0xFA, 0x40, 0x81, 0xE6, 0xFA, 0x5C][..])
+	);
+	assert_eq!(
+		Nec.encode("ⅰ∵纊").as_deref(),
+		Ok(&[0xEE, 0xEF, 0x81, 0xE6, 0xED, 0x40][..])
+	);
+	assert_eq!(crate::decode(&[0xFC, 0x4B]).as_deref(), Ok("黑"));
+	assert_eq!(
+		crate::decode(&[0xFC, 0x4C]),
+		Err((0, EncodedChar::Two([0xFC, 0x4C])))
+	);
+	// Characters with a JIS X 0208 or NEC row 13 code keep it, as in Windows.
+	for char in "亜あA①₂∵￢Ⅰ㈱№℡".chars() {
+		assert_eq!(Ibm.encode_char(char), Nec.encode_char(char));
+	}
+}
@@ -0,0 +1,155 @@
+//! The `shift_jis` encoding of the WHATWG Encoding Standard, for [`crate::Spec::Whatwg`].
+//!
+//! The standard's index jis0208 is Microsoft's mapping, and its user-defined area maps to the
+//! private use area the same way Windows does, so this reuses the CP932 reference tables. The
+//! standard's encoder skips the NEC-selected IBM extensions and takes the first remaining code,
+//! which is exactly the code marked as preferred in `cp932.txt`.
+
+use std::slice;
+
+use crate::cp932::{cp932_decode, cp932_encode};
+use crate::EncodedChar;
+
+/// Decodes a single character as per the standard's decoder, taking the trail byte from `rest`.
+///
+/// Where the standard restores an ASCII trail byte after an error, it is left in `rest`, and the
+/// error only covers the lead byte.
+pub(crate) fn decode_char(b1: u8, rest: &mut slice::Iter<u8>) -> Result<char, EncodedChar> {
+	match b1 {
+		0x80 => Ok('\u{80}'),
+		0x81..=0x9F | 0xE0..=0xFC => {
+			let Some(&b2) = rest.as_slice().first() else {
+				return Err(EncodedChar::One([b1]));
+			};
+			let enc = EncodedChar::Two([b1, b2]);
+			if b2.is_ascii() {
+				let char = cp932_decode(enc).ok_or(EncodedChar::One([b1]))?;
+				rest.next();
+				Ok(char)
+			} else {
+				rest.next();
+				cp932_decode(enc).ok_or(enc)
+			}
+		}
+		_ => cp932_decode(EncodedChar::One([b1])).ok_or(EncodedChar::One([b1])),
+	}
+}
+
+/// Encodes a single character as per the standard's encoder.
+pub(crate) fn encode_char(char: char) -> Option<EncodedChar> {
+	match char {
+		'\u{80}' => Some(EncodedChar::One([0x80])),
+		'¥' => Some(EncodedChar::One([b'\\'])),
+		'‾' => Some(EncodedChar::One([b'~'])),
+		'−' => cp932_encode('－'),
+		'\u{E000}'..='\u{E757}' => None,
+		_ => cp932_encode(char),
+	}
+}
+
+/// The bytes of a pointer into index jis0208, as computed by the standard's encoder.
+#[cfg(test)]
+fn pointer_bytes(pointer: usize) -> EncodedChar {
+	let (lead, trail) = ((pointer / 188) as u8, (pointer % 188) as u8);
+	let lead_offset = if lead < 0x1F { 0x81 } else { 0xC1 };
+	let offset = if trail < 0x3F { 0x40 } else { 0x41 };
+	EncodedChar::Two([lead + lead_offset, trail + offset])
+}
+
+#[cfg(test)]
+fn decode_lossy(input: &[u8]) -> String {
+	let mut out = String::new();
+	let mut iter = input.iter();
+	while let Some(&b1) = iter.next() {
+		out.push(decode_char(b1, &mut iter).unwrap_or('�'));
+	}
+	out
+}
+
+#[test]
+fn decoder() {
+	// Each case follows a step of the standard's shift_jis decoder.
+	let cases: &[(&[u8], &str)] = &[
+		(b"\x00\x5C\x7E\x7F", "\0\\~\x7F"),
+		(b"\x80", "\u{80}"),
+		(b"\xA1\xDF", "｡ﾟ"),
+		(b"\xA0\xFD\xFE\xFF", "����"),
+		(b"\x81\x40\x81\x5F\x81\x7C\x81\x7E", "\u{3000}＼－×"),
+		(b"\x87\x40\xED\x40\xEE\xEF\xFA\x40\xFC\x4B", "①纊ⅰⅰ黑"),
+		// The user-defined area, pointers 8836 to 10715.
+		(b"\xF0\x40\xF0\xFC\xF9\xFC", "\u{E000}\u{E0BB}\u{E757}"),
+		// An ASCII trail byte is restored after an error, but others are consumed.
+		(b"\x81\x7F", "�\x7F"),
+		(b"\x85\x40", "�@"),
+		(b"\x81\x0A", "�\n"),
+		(b"\x85\x9F", "�"),
+		(b"\x81\xFD\x41", "�A"),
+		(b"\xFC\xFC", "�"),
+		(b"\x81\xAD\x81\x40", "�\u{3000}"),
+		(b"\x81", "�"),
+		(b"a\xE0", "a�"),
+	];
+	for &(input, expected) in cases {
+		assert_eq!(decode_lossy(input), expected, "{input:02X?}");
+	}
+}
+
+#[test]
+fn encoder() {
+	let cases: &[(char, Option<&[u8]>)] = &[
+		('\0', Some(b"\x00")),
+		('\\', Some(b"\x5C")),
+		('~', Some(b"\x7E")),
+		('\u{80}', Some(b"\x80")),
+		('¥', Some(b"\x5C")),
+		('‾', Some(b"\x7E")),
+		('ｱ', Some(b"\xB1")),
+		('−', Some(b"\x81\x7C")),
+		('－', Some(b"\x81\x7C")),
+		('＼', Some(b"\x81\x5F")),
+		// JIS X 0208 over the NEC row 13, and the NEC row 13 over the IBM extensions.
+		('∵', Some(b"\x81\xE6")),
+		('Ⅰ', Some(b"\x87\x54")),
+		('￢', Some(b"\x81\xCA")),
+		// The IBM extensions over the NEC-selected ones.
+		('纊', Some(b"\xFA\x5C")),
+		('ⅰ', Some(b"\xFA\x40")),
+		('\u{E000}', None),
+		('\u{E757}', None),
+		('\u{81}', None),
+		('〜', None),
+		('\u{A0}', None),
+	];
+	for &(char, expected) in cases {
+		assert_eq!(encode_char(char).as_deref(), expected, "{char:?}");
+	}
+}
+
+#[test]
+fn index_pointers() {
+	// Every pointer decodes as per index jis0208, and every character in the index encodes to its
+	// first pointer outside the NEC-selected IBM extensions, pointers 8272 to 8835.
+	let mut first = std::collections::HashMap::new();
+	let mut assigned = 0;
+	for pointer in 0..60 * 188 {
+		let enc = pointer_bytes(pointer);
+		let [b1, b2] = enc.two().unwrap();
+		let char = decode_char(b1, &mut [b2].iter());
+		if (8836..=10715).contains(&pointer) {
+			assert_eq!(
+				char,
+				char::from_u32(0xE000 + pointer as u32 - 8836).ok_or(enc)
+			);
+			continue;
+		}
+		let Ok(char) = char else { continue };
+		assigned += 1;
+		if !(8272..=8835).contains(&pointer) {
+			first.entry(char).or_insert(enc);
+		}
+	}
+	assert_eq!(assigned, 7724);
+	for (char, enc) in first {
+		assert_eq!(encode_char(char), Some(enc), "{char:?}");
+	}
+}
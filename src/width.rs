@@ -0,0 +1,247 @@
+//! Conversion between full-width and half-width forms, and measuring text by them.
+
+use std::borrow::Cow;
+
+use crate::sink::{self, Rest};
+use crate::{decode_char_from, DecodeError};
+
+/// Converts the full-width forms of ASCII characters, `！` through `～`, to ASCII.
+///
+/// Other characters, including the ideographic space `　`, are left as is.
+pub fn fold_fullwidth_ascii(s: &str) -> String {
+	s.chars().map(|c| narrow(c, false)).collect()
+}
+
+/// Converts the full-width forms of ASCII characters and the ideographic space `　` to ASCII.
+///
+/// Kana, including half-width katakana, and kanji are left as is. Borrows the input if nothing
+/// needs converting.
+pub fn to_halfwidth_ascii(s: &str) -> Cow<'_, str> {
+	to_halfwidth_ascii_with(s, true)
+}
+
+/// Like [`to_halfwidth_ascii`], but only converts the ideographic space if `space` is set.
+pub fn to_halfwidth_ascii_with(s: &str, space: bool) -> Cow<'_, str> {
+	if s.chars().all(|c| narrow(c, space) == c) {
+		Cow::Borrowed(s)
+	} else {
+		Cow::Owned(s.chars().map(|c| narrow(c, space)).collect())
+	}
+}
+
+/// Decodes a byte slice, converting full-width ASCII as per [`to_halfwidth_ascii_with`].
+pub fn decode_halfwidth_ascii(input: &[u8], space: bool) -> Result<String, DecodeError> {
+	let decode_char =
+		|b1, rest: &mut Rest| decode_char_from(b1, || rest.next()).map(|c| narrow(c, space));
+	sink::decode_strict(input, decode_char).map_err(DecodeError::from)
+}
+
+fn narrow(c: char, space: bool) -> char {
+	match c {
+		'！'..='～' => char::from_u32(c as u32 - 0xFEE0).unwrap(),
+		'　' if space => ' ',
+		c => c,
+	}
+}
+
+/// Converts printable ASCII characters and space to their full-width forms, `！` through `～` and
+/// the ideographic space `　`.
+///
+/// Control characters such as newlines, and non-ASCII characters, are left as is.
+pub fn to_fullwidth(s: &str) -> String {
+	to_fullwidth_with(s, |_| false)
+}
+
+/// Like [`to_fullwidth`], but leaves characters for which `keep` returns true as is.
+pub fn to_fullwidth_with(s: &str, keep: impl Fn(char) -> bool) -> String {
+	s.chars().map(|c| widen(c, &keep)).collect()
+}
+
+/// Encodes a string, converting ASCII to full-width as per [`to_fullwidth`].
+///
+/// The error position is the UTF-8 offset in the input string, as with [`crate::encode`].
+pub fn encode_fullwidth(s: &str) -> Result<Vec<u8>, usize> {
+	encode_fullwidth_with(s, |_| false)
+}
+
+/// Encodes a string, converting ASCII to full-width as per [`to_fullwidth_with`].
+pub fn encode_fullwidth_with(s: &str, keep: impl Fn(char) -> bool) -> Result<Vec<u8>, usize> {
+	crate::sink::encode_strict(s, |char| crate::encode_char(widen(char, &keep)))
+}
+
+fn widen(c: char, keep: impl Fn(char) -> bool) -> char {
+	match c {
+		_ if keep(c) => c,
+		' ' => '　',
+		'!'..='~' => char::from_u32(c as u32 + 0xFEE0).unwrap(),
+		c => c,
+	}
+}
+
+/// The number of columns a string takes up when encoded: one for each single-byte character, and
+/// two for each two-byte one.
+///
+/// Characters that cannot be encoded count as two columns, like the `・` that
+/// [`crate::encode_lossy`] replaces them with. Control characters count as one, like other ASCII.
+pub fn display_width(s: &str) -> usize {
+	chars_width(s.chars())
+}
+
+/// The width of a sequence of characters, as per [`display_width`].
+pub(crate) fn chars_width(chars: impl Iterator<Item = char>) -> usize {
+	chars.map(char_width).sum()
+}
+
+pub(crate) fn char_width(c: char) -> usize {
+	crate::encode_char(c).map_or(2, |e| e.len())
+}
+
+/// Word-wraps text so that each line is at most `max_cols` columns wide, as per [`display_width`].
+///
+/// Lines are broken at a space or ideographic space where possible, which is then dropped. Text
+/// without spaces, as is usual in Japanese, is broken between any two characters. Existing line
+/// breaks are kept, and a single character wider than `max_cols` gets a line of its own.
+pub fn wrap_columns(s: &str, max_cols: usize) -> Vec<String> {
+	let mut lines = Vec::new();
+	for para in s.lines() {
+		let mut line = String::new();
+		let mut width = 0;
+		// The offset and column of the last space in `line`, and its width.
+		let mut space = None;
+		for c in para.chars() {
+			let w = char_width(c);
+			if width + w > max_cols && is_space(c) {
+				lines.push(std::mem::take(&mut line));
+				width = 0;
+				space = None;
+				continue;
+			}
+			while width + w > max_cols && !line.is_empty() {
+				match space.take() {
+					Some((i, col, space_width)) => {
+						let rest = line.split_off(i);
+						lines.push(std::mem::replace(&mut line, rest));
+						line.remove(0);
+						width -= col + space_width;
+					}
+					None => {
+						lines.push(std::mem::take(&mut line));
+						width = 0;
+					}
+				}
+			}
+			if is_space(c) && !line.is_empty() {
+				space = Some((line.len(), width, w));
+			}
+			line.push(c);
+			width += w;
+		}
+		lines.push(line);
+	}
+	lines
+}
+
+fn is_space(c: char) -> bool {
+	c == ' ' || c == '　'
+}
+
+#[test]
+fn fullwidth_ascii_roundtrip() {
+	for c in '！'..='～' {
+		let enc = crate::encode_char(c).unwrap_or_else(|| panic!("{c:?}"));
+		assert_eq!(enc.len(), 2);
+		assert_eq!(crate::decode(&enc).as_deref(), Ok(&*c.to_string()));
+	}
+}
+
+#[test]
+fn test_fold_fullwidth_ascii() {
+	assert_eq!(
+		fold_fullwidth_ascii("ＡＢＣ　ａｂｃ１２３！～＼"),
+		"ABC　abc123!~\\"
+	);
+	assert_eq!(fold_fullwidth_ascii("日本ｱ"), "日本ｱ");
+}
+
+#[test]
+fn test_to_fullwidth() {
+	let ascii = (' '..='~').collect::<String>();
+	let wide = to_fullwidth(&ascii);
+	assert_eq!(wide.chars().count(), ascii.len());
+	assert!(wide
+		.chars()
+		.all(|c| crate::encode_char(c).is_some_and(|e| e.len() == 2)));
+	assert_eq!(fold_fullwidth_ascii(&wide).replace('　', " "), ascii);
+	let enc = encode_fullwidth(&ascii).unwrap();
+	assert_eq!(crate::decode(&enc).unwrap(), wide);
+
+	assert_eq!(to_fullwidth("HP 100/100\n"), "ＨＰ　１００／１００\n");
+	assert_eq!(
+		to_fullwidth_with("HP 100/100", |c| c.is_ascii_digit()),
+		"ＨＰ　100／100"
+	);
+	assert_eq!(to_fullwidth("日本ｱ語 Lv"), "日本ｱ語　Ｌｖ");
+	assert_eq!(
+		encode_fullwidth_with("A 1", |c| c == ' '),
+		crate::encode("Ａ １")
+	);
+	assert_eq!(encode_fullwidth("ab₂"), Err(2));
+}
+
+#[test]
+fn test_to_halfwidth_ascii() {
+	assert_eq!(to_halfwidth_ascii("Ｌｖ．３５"), "Lv.35");
+	assert_eq!(to_halfwidth_ascii("ＨＰ　１０"), "HP 10");
+	assert_eq!(to_halfwidth_ascii_with("ＨＰ　１０", false), "HP　10");
+	assert!(matches!(to_halfwidth_ascii("ｶﾀｶﾅ　"), Cow::Owned(s) if s == "ｶﾀｶﾅ "));
+	assert!(matches!(
+		to_halfwidth_ascii_with("ｶﾀｶﾅ日本　", false),
+		Cow::Borrowed("ｶﾀｶﾅ日本　")
+	));
+
+	let enc = crate::encode("Ｌｖ．３５　ｶﾀｶﾅカタカナ").unwrap();
+	assert_eq!(
+		decode_halfwidth_ascii(&enc, true).as_deref(),
+		Ok("Lv.35 ｶﾀｶﾅカタカナ")
+	);
+	assert_eq!(
+		decode_halfwidth_ascii(&enc, false).as_deref(),
+		Ok("Lv.35　ｶﾀｶﾅカタカナ")
+	);
+	assert_eq!(
+		decode_halfwidth_ascii(b"a\x80", true).unwrap_err().position,
+		1
+	);
+}
+
+#[test]
+fn test_display_width() {
+	assert_eq!(display_width(""), 0);
+	assert_eq!(display_width("HP 100"), 6);
+	assert_eq!(display_width("ｶﾀｶﾅ"), 4);
+	assert_eq!(display_width("日本語　Ａ"), 10);
+	assert_eq!(display_width("〜\n"), 3);
+}
+
+#[test]
+fn test_wrap_columns() {
+	assert_eq!(
+		wrap_columns("The quick brown fox", 10),
+		["The quick", "brown fox"]
+	);
+	assert_eq!(
+		wrap_columns("吾輩は猫である。名前はまだ無い。", 10),
+		["吾輩は猫で", "ある。名前", "はまだ無い", "。"]
+	);
+	assert_eq!(
+		wrap_columns("　「おはよう　ございます」", 14),
+		["　「おはよう", "ございます」"]
+	);
+	assert_eq!(wrap_columns("ab 日本", 5), ["ab", "日本"]);
+	assert_eq!(wrap_columns(" 日本", 3), [" 日", "本"]);
+	assert_eq!(wrap_columns("ab\n\ncd ef", 3), ["ab", "", "cd", "ef"]);
+	assert_eq!(wrap_columns("日本", 1), ["日", "本"]);
+	for line in wrap_columns(&"ｱｲｳ エオ 漢字かな ".repeat(20), 13) {
+		assert!(display_width(&line) <= 13, "{line:?}");
+	}
+}
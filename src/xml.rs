@@ -0,0 +1,147 @@
+//! Encoding for embedding in XML.
+
+use crate::encode_char;
+
+/// How [`encode_xml_with`] writes numeric character references.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NcrRadix {
+	/// `&#8322;`
+	Decimal,
+	/// `&#x2082;`
+	#[default]
+	Hex,
+}
+
+/// Which XML-significant characters [`encode_xml_with`] escapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum XmlEscape {
+	/// `&`, `<` and `>`, for text content.
+	#[default]
+	Text,
+	/// `&`, `<`, `>`, `"` and `'`, for attribute values.
+	Attribute,
+	/// Nothing. Note that a literal `&` may then be mistaken for a reference when decoding.
+	None,
+}
+
+/// Options for [`encode_xml_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct XmlOptions {
+	/// How to write characters that cannot be encoded.
+	pub radix: NcrRadix,
+	/// Which characters to write as named entities.
+	pub escape: XmlEscape,
+}
+
+/// Encodes a string for XML, with the default [`XmlOptions`].
+pub fn encode_xml(s: &str) -> Vec<u8> {
+	encode_xml_with(s, XmlOptions::default())
+}
+
+/// Encodes a string for XML.
+///
+/// Characters that cannot be encoded are written as numeric character references, and
+/// XML-significant characters are written as named entities, according to the options.
+pub fn encode_xml_with(s: &str, options: XmlOptions) -> Vec<u8> {
+	let mut out = Vec::new();
+	for char in s.chars() {
+		let entity = match (char, options.escape) {
+			(_, XmlEscape::None) => None,
+			('&', _) => Some("&amp;"),
+			('<', _) => Some("&lt;"),
+			('>', _) => Some("&gt;"),
+			('"', XmlEscape::Attribute) => Some("&quot;"),
+			('\'', XmlEscape::Attribute) => Some("&apos;"),
+			_ => None,
+		};
+		if let Some(entity) = entity {
+			out.extend(entity.bytes());
+		} else if let Some(enc) = encode_char(char) {
+			out.extend(enc);
+		} else {
+			let ncr = match options.radix {
+				NcrRadix::Decimal => format!("&#{};", char as u32),
+				NcrRadix::Hex => format!("&#x{:X};", char as u32),
+			};
+			out.extend(ncr.bytes());
+		}
+	}
+	out
+}
+
+/// Resolves numeric character references and the five predefined entities in decoded text.
+///
+/// Anything that is not a valid reference is kept as is.
+pub fn decode_xml_entities(s: &str) -> String {
+	let mut out = String::new();
+	let mut rest = s;
+	while let Some(i) = rest.find('&') {
+		out.push_str(&rest[..i]);
+		rest = &rest[i..];
+		match rest
+			.find(';')
+			.and_then(|end| Some((resolve(&rest[1..end])?, end)))
+		{
+			Some((char, end)) => {
+				out.push(char);
+				rest = &rest[end + 1..];
+			}
+			None => {
+				out.push('&');
+				rest = &rest[1..];
+			}
+		}
+	}
+	out.push_str(rest);
+	out
+}
+
+fn resolve(name: &str) -> Option<char> {
+	let code = match name {
+		"amp" => return Some('&'),
+		"lt" => return Some('<'),
+		"gt" => return Some('>'),
+		"quot" => return Some('"'),
+		"apos" => return Some('\''),
+		_ => name.strip_prefix('#')?,
+	};
+	let (digits, radix) = match code.strip_prefix(['x', 'X']) {
+		Some(hex) => (hex, 16),
+		None => (code, 10),
+	};
+	if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+		return None;
+	}
+	char::from_u32(u32::from_str_radix(digits, radix).ok()?)
+}
+
+#[test]
+fn test_encode_xml() {
+	let s = "A&B <₂> \"日\" '😀'";
+	assert_eq!(
+		encode_xml(s),
+		b"A&amp;B &lt;&#x2082;&gt; \"\x93\xFA\" '&#x1F600;'",
+	);
+	let attr = XmlOptions {
+		radix: NcrRadix::Decimal,
+		escape: XmlEscape::Attribute,
+	};
+	assert_eq!(
+		encode_xml_with(s, attr),
+		b"A&amp;B &lt;&#8322;&gt; &quot;\x93\xFA&quot; &apos;&#128512;&apos;",
+	);
+	let none = XmlOptions {
+		radix: NcrRadix::Hex,
+		escape: XmlEscape::None,
+	};
+	assert_eq!(encode_xml_with("&<₂", none), b"&<&#x2082;");
+
+	for options in [XmlOptions::default(), attr] {
+		let dec = crate::decode(&encode_xml_with(s, options)).unwrap();
+		assert_eq!(decode_xml_entities(&dec), s);
+	}
+	assert_eq!(
+		decode_xml_entities("&&#;&#x;&#xD800;&bogus;&#65&#65;"),
+		"&&#;&#x;&#xD800;&bogus;&#65A"
+	);
+}